@@ -1,24 +1,114 @@
+use std::cell::RefCell;
+use std::collections::BinaryHeap;
 use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
 use std::time::Duration;
 
 use async_trait::async_trait;
-use curl::easy::{Easy2, Handler};
-use curl::multi::Multi;
+use curl::easy::{Easy2, Handler, List};
+use curl::multi::{Easy2Handle, Multi};
+#[cfg(feature = "stream")]
+use futures_core::Stream;
+use http::HeaderMap;
 use log::trace;
 use tokio::runtime::Builder;
-use tokio::sync::mpsc::{self, Sender};
-use tokio::sync::oneshot;
+use tokio::sync::mpsc::error::TrySendError;
+#[cfg(feature = "tower")]
+use tokio::sync::mpsc::OwnedPermit;
+use tokio::sync::mpsc::{self, Sender, UnboundedReceiver, UnboundedSender};
+use tokio::sync::{oneshot, Notify, OwnedSemaphorePermit, Semaphore};
+#[cfg(feature = "stream")]
+use tokio::task::JoinSet;
 use tokio::task::LocalSet;
-use tokio::time::sleep;
+use tokio::time::{sleep, sleep_until, Instant, MissedTickBehavior};
+#[cfg(feature = "tracing")]
+use tracing::Instrument;
 
+#[cfg(feature = "bytes")]
+use crate::coalesce::Coalescer;
+use crate::connection::{self, Connection, ConnectionSession};
+use crate::curl::setopt_long;
+#[cfg(feature = "bytes")]
+use crate::error::CurlError;
 use crate::error::Error;
+#[cfg(feature = "bytes")]
+use crate::handler::BytesHandler;
+use crate::retry::RetryPolicy;
+use crate::share::{CurlShare, ShareData};
 
+/// `CURLOPT_UPKEEP_INTERVAL_MS`, which curl-sys does not define.
+const CURLOPT_UPKEEP_INTERVAL_MS: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 281;
+
+/// Actor is the transport `AsyncCurl` performs its requests through.
+///
+/// [`CurlActor`] performs them with libcurl, while another implementation
+/// can return canned responses to test code built on `AsyncCurl` without
+/// a server.
+/// ```
+/// use async_curl::actor::Actor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::error::Error;
+/// use async_curl::handler::CollectingHandler;
+/// use async_trait::async_trait;
+/// use curl::easy::{Easy2, Handler};
+///
+/// #[derive(Clone)]
+/// struct MockActor;
+///
+/// #[async_trait]
+/// impl Actor<CollectingHandler> for MockActor {
+///     async fn send_request(
+///         &self,
+///         mut easy2: Easy2<CollectingHandler>,
+///     ) -> Result<Easy2<CollectingHandler>, Error<CollectingHandler>> {
+///         easy2.get_mut().write(b"canned").unwrap();
+///         Ok(easy2)
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let text = AsyncCurl::new(MockActor, CollectingHandler::new())
+///     .url("https://example.com")?
+///     .finalize()
+///     .perform_text()
+///     .await?;
+/// assert_eq!(text, "canned");
+/// # Ok(())
+/// # }
+/// ```
 #[async_trait]
-pub trait Actor<H>
+pub trait Actor<H>: Send + Sync
 where
     H: Handler + Debug + Send + 'static,
 {
+    /// Performs the request and returns the Easy2 once it finished.
     async fn send_request(&self, easy2: Easy2<H>) -> Result<Easy2<H>, Error<H>>;
+
+    /// Returns an Easy2 for a new request with `handler`.
+    ///
+    /// `AsyncCurl::new` gets its Easy2 from here.
+    fn easy_handle(&self, handler: H) -> Easy2<H> {
+        Easy2::new(handler)
+    }
+
+    /// Returns the `name: value` header lines every request of the actor
+    /// sends, unless the request sets a header of the same name.
+    ///
+    /// `AsyncCurl` keeps them when headers are added to a request.
+    fn default_headers(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Takes back an Easy2 the caller is done with, which is dropped
+    /// unless the actor keeps it for reuse.
+    fn recycle(&self, easy2: Easy2<H>) {
+        drop(easy2);
+    }
 }
 
 /// CurlActor is responsible for performing
@@ -151,12 +241,34 @@ where
 /// # }
 /// ```
 ///
-#[derive(Clone)]
 pub struct CurlActor<H>
 where
     H: Handler + Debug + Send + 'static,
 {
     request_sender: Sender<Request<H>>,
+    metrics: Arc<Metrics>,
+    pool: Option<Arc<HandlePool<H>>>,
+    buffer_sizes: BufferSizes,
+    default_headers: Arc<[String]>,
+    #[cfg(feature = "bytes")]
+    coalescer: Arc<Coalescer>,
+}
+
+impl<H> Clone for CurlActor<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            request_sender: self.request_sender.clone(),
+            metrics: self.metrics.clone(),
+            pool: self.pool.clone(),
+            buffer_sizes: self.buffer_sizes,
+            default_headers: self.default_headers.clone(),
+            #[cfg(feature = "bytes")]
+            coalescer: self.coalescer.clone(),
+        }
+    }
 }
 
 impl<H> Default for CurlActor<H>
@@ -177,11 +289,49 @@ where
     /// curl asynchronously, await the response in the oneshot receiver and
     /// return Easy2 back to the caller.
     async fn send_request(&self, easy2: Easy2<H>) -> Result<Easy2<H>, Error<H>> {
-        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<Result<Easy2<H>, Error<H>>>();
-        self.request_sender
-            .send(Request(easy2, oneshot_sender))
-            .await?;
-        oneshot_receiver.await?
+        dispatch(
+            self.request_sender.clone(),
+            easy2,
+            RequestOptions::default(),
+        )
+        .await
+    }
+
+    fn easy_handle(&self, handler: H) -> Easy2<H> {
+        CurlActor::easy_handle(self, handler)
+    }
+
+    fn default_headers(&self) -> Vec<String> {
+        self.default_headers.to_vec()
+    }
+
+    fn recycle(&self, easy2: Easy2<H>) {
+        CurlActor::recycle(self, easy2);
+    }
+}
+
+/// Lets a shared actor, such as an `Arc<dyn Actor<H>>`, be given to
+/// `AsyncCurl`, so the implementation can be chosen at runtime.
+#[async_trait]
+impl<H, A> Actor<H> for Arc<A>
+where
+    H: Handler + Debug + Send + 'static,
+    A: Actor<H> + ?Sized,
+{
+    async fn send_request(&self, easy2: Easy2<H>) -> Result<Easy2<H>, Error<H>> {
+        (**self).send_request(easy2).await
+    }
+
+    fn easy_handle(&self, handler: H) -> Easy2<H> {
+        (**self).easy_handle(handler)
+    }
+
+    fn default_headers(&self) -> Vec<String> {
+        (**self).default_headers()
+    }
+
+    fn recycle(&self, easy2: Easy2<H>) {
+        (**self).recycle(easy2);
     }
 }
 
@@ -191,36 +341,1101 @@ where
 {
     /// This creates the new instance of CurlActor to handle Curl perform asynchronously using Curl Multi
     /// in a background thread to avoid blocking of other tasks.
+    ///
+    /// This is the zero-config shortcut for `CurlActorBuilder::new().build()`.
     pub fn new() -> Self {
-        let (request_sender, mut request_receiver) = mpsc::channel::<Request<H>>(1);
-        let runtime = Builder::new_current_thread().enable_all().build().unwrap();
-
-        std::thread::spawn(move || {
-            let local = LocalSet::new();
-            local.spawn_local(async move {
-                while let Some(Request(easy2, oneshot_sender)) = request_receiver.recv().await {
-                    tokio::task::spawn_local(async move {
-                        let response = perform_curl_multi(easy2).await;
-                        if let Err(res) = oneshot_sender.send(response) {
-                            trace!("Warning! The receiver has been dropped. {:?}", res);
+        CurlActorBuilder::new().build()
+    }
+
+    /// This sends Easy2 into the background task like `send_request`, but also
+    /// returns a [`TransferHandle`] that can cancel this specific transfer later
+    /// without holding on to the returned future.
+    ///
+    /// The request is only queued once the returned future is polled, and an
+    /// aborted transfer resolves with [`Error::Cancelled`].
+    pub fn send_request_with_handle(
+        &self,
+        easy2: Easy2<H>,
+    ) -> (
+        impl Future<Output = Result<Easy2<H>, Error<H>>> + Send + 'static,
+        TransferHandle,
+    ) {
+        let (control_sender, control_receiver) = mpsc::unbounded_channel();
+        let options = RequestOptions {
+            control: Some(control_receiver),
+            ..Default::default()
+        };
+        let response = dispatch(self.request_sender.clone(), easy2, options);
+        (
+            response,
+            TransferHandle {
+                control: control_sender,
+            },
+        )
+    }
+
+    /// This sends an Easy2 set up with `CURLOPT_CONNECT_ONLY` into the background
+    /// task, and returns the [`Connection`] once libcurl has connected it.
+    pub(crate) async fn connect(&self, easy2: Easy2<H>) -> Result<Connection<H>, Error<H>> {
+        let (io_sender, io_receiver) = mpsc::unbounded_channel();
+        let (connected_sender, connected_receiver) = oneshot::channel();
+        let options = RequestOptions {
+            connection: Some(ConnectionSession {
+                io: io_receiver,
+                connected: connected_sender,
+            }),
+            ..Default::default()
+        };
+        let response = dispatch(self.request_sender.clone(), easy2, options);
+        Connection::open(io_sender, connected_receiver, response).await
+    }
+
+    /// This sends Easy2 into the background task like `send_request`, but caps
+    /// the whole transfer at `timeout`, overriding the actor's default timeout.
+    /// A transfer that runs out of time resolves with [`Error::Timeout`].
+    pub async fn send_request_timeout(
+        &self,
+        easy2: Easy2<H>,
+        timeout: Duration,
+    ) -> Result<Easy2<H>, Error<H>> {
+        let options = RequestOptions {
+            timeout: Some(timeout),
+            ..Default::default()
+        };
+        dispatch(self.request_sender.clone(), easy2, options).await
+    }
+
+    /// This sends Easy2 into the background task like `send_request`, but
+    /// stops the transfer once `deadline` has passed, overriding the actor's
+    /// default timeout. A transfer that misses it resolves with [`Error::Timeout`].
+    ///
+    /// Unlike `send_request_timeout`, the time spent waiting in the queue
+    /// counts too, so a deadline passed down from a caller is kept as is.
+    pub async fn send_request_deadline(
+        &self,
+        easy2: Easy2<H>,
+        deadline: Instant,
+    ) -> Result<Easy2<H>, Error<H>> {
+        let options = RequestOptions {
+            deadline: Some(deadline),
+            ..Default::default()
+        };
+        dispatch(self.request_sender.clone(), easy2, options).await
+    }
+
+    /// This sends Easy2 into the background task like `send_request`, but
+    /// while requests wait for a free slot of `max_concurrent` or a rate limit
+    /// token, the ones of a higher `priority` are started first.
+    ///
+    /// The worker takes up to `CurlActorBuilder::capacity` of the waiting
+    /// requests out of its queue to compare them, and a request still waiting
+    /// for room in the queue does not compete until it got in.
+    pub async fn send_request_prioritized(
+        &self,
+        easy2: Easy2<H>,
+        priority: Priority,
+    ) -> Result<Easy2<H>, Error<H>> {
+        let options = RequestOptions {
+            priority,
+            ..Default::default()
+        };
+        dispatch(self.request_sender.clone(), easy2, options).await
+    }
+
+    /// This queues Easy2 right away like `send_request` if the queue of the
+    /// background worker has room, and returns the future awaiting its response.
+    ///
+    /// When the queue is full, or the worker is no longer running, the Easy2
+    /// is given back in the error instead of waiting, so a caller can shed
+    /// the load, e.g. by answering with a 503. Dropping the returned future
+    /// cancels the queued request like dropping `send_request` does.
+    pub fn try_send_request(
+        &self,
+        easy2: Easy2<H>,
+    ) -> Result<PendingResponse<H>, TrySendError<Easy2<H>>> {
+        let (request, response) = Request::new(easy2, RequestOptions::default());
+        match self.request_sender.try_send(request) {
+            Ok(()) => Ok(PendingResponse(response)),
+            Err(TrySendError::Full(request)) => {
+                trace!("The queue of the background worker is full.");
+                Err(TrySendError::Full(request.easy2))
+            }
+            Err(TrySendError::Closed(request)) => {
+                trace!("The background worker is no longer running.");
+                Err(TrySendError::Closed(request.easy2))
+            }
+        }
+    }
+
+    /// This sends a request like `send_request`, trying it again according to
+    /// `policy` while it fails with a retryable error, or while the server
+    /// throttles it when `RetryPolicy::respect_retry_after` is on.
+    ///
+    /// The Easy2 is consumed by every attempt, so `make_easy` builds a
+    /// fresh one each time. The error of the last attempt is returned.
+    pub async fn send_request_retry<F>(
+        &self,
+        make_easy: F,
+        policy: &RetryPolicy<H>,
+    ) -> Result<Easy2<H>, Error<H>>
+    where
+        F: Fn() -> Easy2<H>,
+    {
+        let mut attempt = 1;
+        loop {
+            let delay = match self.send_request(make_easy()).await {
+                Ok(easy2) if attempt < policy.attempts() => {
+                    match policy.throttled_delay(&easy2, attempt) {
+                        Some(delay) => {
+                            trace!("Attempt {attempt} was throttled, retrying in {delay:?}");
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(attempt, ?delay, "retrying the throttled request");
+                            delay
                         }
-                    });
+                        None => return Ok(easy2),
+                    }
+                }
+                Err(err) if attempt < policy.attempts() && policy.is_retryable(&err) => {
+                    let delay = policy.delay(attempt);
+                    trace!("Attempt {attempt} failed, retrying in {delay:?}: {err}");
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(attempt, ?delay, error = %err, "retrying the request");
+                    delay
                 }
+                response => return response,
+            };
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Returns how many requests wait to be started, either in the queue
+    /// or for a rate limit token or a free slot of `max_concurrent`.
+    pub fn queued(&self) -> usize {
+        let in_channel = self.request_sender.max_capacity() - self.request_sender.capacity();
+        in_channel + self.metrics.waiting.load(Ordering::SeqCst)
+    }
+
+    /// Returns the sender of the queue of the background worker.
+    #[cfg(feature = "tower")]
+    pub(crate) fn request_sender(&self) -> Sender<Request<H>> {
+        self.request_sender.clone()
+    }
+
+    /// Returns how many transfers the background worker is performing.
+    pub fn in_flight(&self) -> usize {
+        self.metrics.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// This creates a CurlActor like `new` that keeps up to `size` finished
+    /// Easy2 handles for reuse, see `CurlActorBuilder::handle_pool`.
+    pub fn with_handle_pool(size: usize) -> Self {
+        CurlActorBuilder::new().handle_pool(size).build()
+    }
+
+    /// Returns an Easy2 for a new request with `handler`, reusing a handle of
+    /// the pool when there is one. A reused handle is `reset` first, so it
+    /// keeps its TLS session cache but none of the options of its previous
+    /// request.
+    ///
+    /// The default buffer sizes and headers of the actor are applied to the
+    /// Easy2, and `AsyncCurl::new` gets its Easy2 from here.
+    pub fn easy_handle(&self, handler: H) -> Easy2<H> {
+        let pooled = self
+            .pool
+            .as_ref()
+            .and_then(|pool| pool.handles.lock().ok()?.pop());
+        let mut easy2 = match pooled {
+            Some(mut easy2) => {
+                easy2.reset();
+                *easy2.get_mut() = handler;
+                easy2
+            }
+            None => Easy2::new(handler),
+        };
+        self.buffer_sizes.apply(&mut easy2);
+        if !self.default_headers.is_empty() {
+            let mut list = List::new();
+            let headers = self
+                .default_headers
+                .iter()
+                .try_for_each(|header| list.append(header))
+                .and_then(|_| easy2.http_headers(list));
+            if let Err(err) = headers {
+                trace!("{err}");
+            }
+        }
+        easy2
+    }
+
+    /// Gives a finished Easy2 back to the pool for reuse by `easy_handle`.
+    /// The handle is dropped if the actor has no pool or the pool is full.
+    pub fn recycle(&self, easy2: Easy2<H>) {
+        let Some(pool) = &self.pool else {
+            return;
+        };
+        if let Ok(mut handles) = pool.handles.lock() {
+            if handles.len() < pool.size {
+                handles.push(easy2);
+            }
+        }
+    }
+
+    /// Returns how many Easy2 handles wait in the pool for reuse.
+    pub fn pooled(&self) -> usize {
+        self.pool
+            .as_ref()
+            .and_then(|pool| Some(pool.handles.lock().ok()?.len()))
+            .unwrap_or(0)
+    }
+
+    /// Performs every Easy2 of `requests` and yields their responses in the
+    /// order they finish, not the order they were sent in, so fast requests
+    /// don't wait behind slow ones.
+    ///
+    /// Each request is queued as soon as `requests` yields it, and the stream
+    /// ends once `requests` ended and every response was yielded.
+    #[cfg(feature = "stream")]
+    pub fn stream<S>(
+        &self,
+        requests: S,
+    ) -> impl Stream<Item = Result<Easy2<H>, Error<H>>> + Send + 'static
+    where
+        S: Stream<Item = Easy2<H>> + Send + 'static,
+    {
+        Completions {
+            request_sender: self.request_sender.clone(),
+            requests: Some(Box::pin(requests)),
+            transfers: JoinSet::new(),
+        }
+    }
+
+    /// Returns a [`CurlActorBuilder`] to configure the actor before its
+    /// background worker is spawned.
+    pub fn builder() -> CurlActorBuilder {
+        CurlActorBuilder::new()
+    }
+
+    fn spawn(mut config: CurlActorBuilder) -> Self {
+        // Every transfer gets a Multi of its own, which takes the connection
+        // cache with it, so a pool or upkeep keeps its connections in a share instead.
+        if config.share.is_none()
+            && (config.handle_pool.is_some() || config.upkeep_interval.is_some())
+        {
+            config.share = CurlShare::new(&[
+                ShareData::Dns,
+                ShareData::SslSessions,
+                ShareData::Connections,
+            ])
+            .map_err(|err| trace!("{err}"))
+            .ok();
+        }
+        let (request_sender, request_receiver) = mpsc::channel::<Request<H>>(config.capacity);
+        let limiter = config
+            .max_concurrent
+            .map(|max| Arc::new(Semaphore::new(max)));
+        let rate_limiter = config
+            .rate_limit
+            .map(|(requests_per_sec, burst)| TokenBucket::new(requests_per_sec, burst));
+        let queue = Arc::new(tokio::sync::Mutex::new(Queue {
+            requests: request_receiver,
+            rate_limiter,
+            pending: BinaryHeap::new(),
+            capacity: config.capacity,
+            sequence: 0,
+        }));
+        let metrics = Arc::new(Metrics::default());
+
+        for worker in 0..config.workers {
+            let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+            let queue = queue.clone();
+            let limiter = limiter.clone();
+            let worker_metrics = metrics.clone();
+            let config = config.clone();
+            // The first worker keeps up the connections of the share for all of them.
+            let upkeep = config
+                .upkeep_interval
+                .filter(|_| worker == 0)
+                .zip(config.share.clone());
+            std::thread::spawn(move || {
+                let local = LocalSet::new();
+                let requests = async move {
+                    loop {
+                        // The worker holding the queue starts the next request in order,
+                        // so a worker busy with its own transfers takes fewer of them.
+                        let mut queue = queue.lock().await;
+                        let Some((
+                            Request {
+                                easy2,
+                                sender: mut oneshot_sender,
+                                options,
+                            },
+                            permit,
+                        )) = queue.admit(limiter.as_ref(), &worker_metrics).await
+                        else {
+                            break;
+                        };
+                        drop(queue);
+                        // A connection stays open for as long as its `Connection` is kept.
+                        let deadline = match options.connection {
+                            Some(_) => None,
+                            None => options.deadline.or_else(|| {
+                                let timeout = options.timeout.or(config.default_timeout)?;
+                                Some(Instant::now() + timeout)
+                            }),
+                        };
+                        let in_flight = InFlight::start(worker_metrics.clone());
+                        let share = config.share.clone();
+                        #[cfg(feature = "tracing")]
+                        let span = options.span.clone().unwrap_or_else(tracing::Span::none);
+                        #[cfg(feature = "tracing")]
+                        let transfer_span = span.clone();
+                        let transfer = async move {
+                            let _permit = permit;
+                            let pause = PauseState::default();
+                            // Dropping the transfer future detaches the Easy2 from its Multi,
+                            // so a caller that gave up on the response also stops the transfer.
+                            let response = tokio::select! {
+                                response = perform_curl_multi(easy2, share.as_ref(), &pause, options.connection) => response,
+                                _ = oneshot_sender.closed() => {
+                                    trace!("The receiver has been dropped, cancelling the transfer.");
+                                    #[cfg(feature = "tracing")]
+                                    tracing::debug!("transfer cancelled, the receiver has been dropped");
+                                    return;
+                                }
+                                _ = wait_for_abort(options.control, &pause) => {
+                                    trace!("The transfer has been aborted through its handle.");
+                                    Err(Error::Cancelled)
+                                }
+                                _ = wait_for_deadline(deadline) => {
+                                    trace!("The transfer has timed out, cancelling the transfer.");
+                                    Err(Error::Timeout)
+                                }
+                            };
+                            log_transfer(&response);
+                            #[cfg(feature = "tracing")]
+                            record_transfer(&transfer_span, &response);
+                            // The transfer is over by the time the caller sees the response.
+                            drop(in_flight);
+                            if let Err(res) = oneshot_sender.send(response) {
+                                trace!("Warning! The receiver has been dropped. {:?}", res);
+                            }
+                        };
+                        #[cfg(feature = "tracing")]
+                        let transfer = transfer.instrument(span);
+                        tokio::task::spawn_local(transfer);
+                    }
+                };
+                local.spawn_local(async move {
+                    match upkeep {
+                        Some((interval, share)) => tokio::select! {
+                            _ = requests => {}
+                            _ = upkeep_share(share, interval) => {}
+                        },
+                        None => requests.await,
+                    }
+                });
+                runtime.block_on(local);
             });
-            runtime.block_on(local);
+        }
+
+        let pool = config.handle_pool.map(|size| {
+            Arc::new(HandlePool {
+                size,
+                handles: std::sync::Mutex::new(Vec::with_capacity(size)),
+            })
         });
+        Self {
+            request_sender,
+            metrics,
+            pool,
+            buffer_sizes: config.buffer_sizes,
+            default_headers: config.default_headers.into(),
+            #[cfg(feature = "bytes")]
+            coalescer: Arc::default(),
+        }
+    }
+}
 
-        Self { request_sender }
+#[cfg(feature = "bytes")]
+impl CurlActor<BytesHandler> {
+    /// This performs the request built by `make_easy` like `send_request`,
+    /// but concurrent calls with the same `key` share a single transfer and
+    /// each get a clone of its body, e.g. to fill a cache entry only once.
+    ///
+    /// Only the call that starts the transfer builds its Easy2. The transfer
+    /// keeps running if that call is dropped, and a call made after it
+    /// finished starts a new one. Check the status in `make_easy`, e.g.
+    /// through `fail_on_error`, since only the body is shared.
+    pub async fn send_request_coalesced<K, F>(
+        &self,
+        key: K,
+        make_easy: F,
+    ) -> Result<bytes::Bytes, CurlError>
+    where
+        K: std::hash::Hash + Eq + Clone + Send + 'static,
+        F: FnOnce() -> Easy2<BytesHandler>,
+    {
+        self.coalescer.send(self, key, make_easy).await
     }
 }
 
-async fn perform_curl_multi<H: Handler + Debug + Send + 'static>(
+/// CurlActorBuilder configures a [`CurlActor`] with a fluent API
+/// before spawning its background worker.
+/// ```
+/// use async_curl::actor::{CurlActor, CurlActorBuilder};
+/// use curl::easy::{Handler, WriteError};
+///
+/// #[derive(Debug, Clone, Default)]
+/// pub struct ResponseHandler {
+///     data: Vec<u8>,
+/// }
+///
+/// impl Handler for ResponseHandler {
+///     fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+///         self.data.extend_from_slice(data);
+///         Ok(data.len())
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let actor: CurlActor<ResponseHandler> = CurlActorBuilder::new()
+///     .capacity(16)
+///     .max_concurrent(4)
+///     .build();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CurlActorBuilder {
+    capacity: usize,
+    max_concurrent: Option<usize>,
+    default_timeout: Option<Duration>,
+    rate_limit: Option<(u32, u32)>,
+    handle_pool: Option<usize>,
+    share: Option<CurlShare>,
+    workers: usize,
+    buffer_sizes: BufferSizes,
+    default_headers: Vec<String>,
+    upkeep_interval: Option<Duration>,
+}
+
+impl Default for CurlActorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CurlActorBuilder {
+    /// Creates a builder with the default settings, a request queue of
+    /// one and no limit on concurrent transfers.
+    pub fn new() -> Self {
+        Self {
+            capacity: 1,
+            max_concurrent: None,
+            default_timeout: None,
+            rate_limit: None,
+            handle_pool: None,
+            share: None,
+            workers: 1,
+            buffer_sizes: BufferSizes::default(),
+            default_headers: Vec::new(),
+            upkeep_interval: None,
+        }
+    }
+
+    /// Sets how many requests can wait in the queue of the background
+    /// worker before `send_request` starts to wait for room.
+    ///
+    /// A capacity of zero is treated as one.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Limits how many transfers the background worker drives at the
+    /// same time. Requests above the limit wait in the queue until a
+    /// running transfer finishes.
+    ///
+    /// A limit of zero is treated as one.
+    pub fn max_concurrent(mut self, max: usize) -> Self {
+        self.max_concurrent = Some(max.max(1));
+        self
+    }
+
+    /// Caps every transfer driven by the actor at `timeout` of wall-clock time.
+    /// `CurlActor::send_request_timeout` overrides it for a single request.
+    /// A transfer that runs out of time resolves with [`Error::Timeout`].
+    ///
+    /// By default there is no timeout.
+    pub fn default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = Some(timeout);
+        self
+    }
+
+    /// Limits how often the background worker starts a transfer to
+    /// `requests_per_sec`, allowing up to `burst` transfers to start at
+    /// once after a quiet period. Requests above the rate wait in the queue
+    /// for their turn, across every clone of the actor.
+    ///
+    /// Values of zero are treated as one. By default there is no rate limit.
+    pub fn rate_limit(mut self, requests_per_sec: u32, burst: u32) -> Self {
+        self.rate_limit = Some((requests_per_sec.max(1), burst.max(1)));
+        self
+    }
+
+    /// Keeps up to `size` finished Easy2 handles for reuse by later requests,
+    /// instead of allocating them anew.
+    ///
+    /// Unless a `share` is given, the transfers of the actor are also attached
+    /// to a [`CurlShare`] of DNS, TLS session and connection caches, so a
+    /// later request reuses the open connection of an earlier one. Without
+    /// it, only the TLS session cache of a pooled handle would survive, since
+    /// the connection and DNS caches go away with the Multi of each transfer.
+    ///
+    /// Handles come back to the pool through `CurlActor::recycle`, which the
+    /// `perform_*` helpers of `AsyncCurl` call once they are done with them.
+    ///
+    /// By default there is no pool.
+    pub fn handle_pool(mut self, size: usize) -> Self {
+        self.handle_pool = Some(size);
+        self
+    }
+
+    /// Attaches every transfer driven by the actor to `share`, so they use
+    /// its DNS, cookie, TLS session and connection caches. The same share
+    /// can be given to several actors.
+    ///
+    /// By default every Easy2 keeps caches of its own.
+    pub fn share(mut self, share: CurlShare) -> Self {
+        self.share = Some(share);
+        self
+    }
+
+    /// Spawns `workers` background worker threads sharing the queue, so
+    /// CPU-bound work such as TLS handshakes is spread across cores.
+    ///
+    /// The next request is started by the first worker that is free to take
+    /// it, so a worker busy with its own transfers takes fewer of them.
+    /// `max_concurrent`, `rate_limit` and `share` apply across all of them.
+    ///
+    /// A count of zero is treated as one, which is the default.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Sets the receive buffer size of every Easy2 handed out by
+    /// `CurlActor::easy_handle`, which `AsyncCurl::buffer_size` overrides
+    /// for a single request.
+    ///
+    /// By default libcurl picks the size, this corresponds to `CURLOPT_BUFFERSIZE`.
+    pub fn default_buffer_size(mut self, size: usize) -> Self {
+        self.buffer_sizes.download = Some(size);
+        self
+    }
+
+    /// Sets the upload buffer size of every Easy2 handed out by
+    /// `CurlActor::easy_handle`, which `AsyncCurl::upload_buffer_size`
+    /// overrides for a single request.
+    ///
+    /// By default libcurl picks the size, this corresponds to `CURLOPT_UPLOAD_BUFFERSIZE`.
+    pub fn default_upload_buffer_size(mut self, size: usize) -> Self {
+        self.buffer_sizes.upload = Some(size);
+        self
+    }
+
+    /// Keeps up the idle connections of the share of the actor every
+    /// `interval`, e.g. by sending an HTTP/2 PING frame on the ones idle
+    /// for that long, so intermediaries do not drop them between bursts
+    /// of traffic.
+    ///
+    /// A background worker calls `curl_easy_upkeep` on an Easy2 attached to
+    /// the share, with `interval` as its `CURLOPT_UPKEEP_INTERVAL_MS`. Unless
+    /// a `share` is given, the transfers of the actor are attached to a share
+    /// of DNS, TLS session and connection caches like with `handle_pool`.
+    ///
+    /// libcurl 8.6 and older only keep up the connections cached by the Easy2
+    /// `curl_easy_upkeep` is called on, not those of its share, so there this
+    /// leaves the connections as they are.
+    ///
+    /// By default there is no upkeep.
+    pub fn upkeep_interval(mut self, interval: Duration) -> Self {
+        self.upkeep_interval = Some(interval.max(Duration::from_millis(1)));
+        self
+    }
+
+    /// Sends `headers` with every request of the actor, such as an
+    /// `Authorization` or `User-Agent` header. A header of the same name
+    /// set on the request with `AsyncCurl::header` and similar methods
+    /// replaces the default one.
+    ///
+    /// The headers are set on every Easy2 handed out by
+    /// `CurlActor::easy_handle`, so an Easy2 created by hand and given to
+    /// `send_request` goes without them. Values that are not valid UTF-8
+    /// are sent with the invalid sequences replaced.
+    ///
+    /// By default no headers are added.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers = headers
+            .iter()
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes());
+                format!("{}: {}", name.as_str(), value)
+            })
+            .collect();
+        self
+    }
+
+    /// Spawns the background worker and returns the configured [`CurlActor`].
+    pub fn build<H>(self) -> CurlActor<H>
+    where
+        H: Handler + Debug + Send + 'static,
+    {
+        CurlActor::spawn(self)
+    }
+}
+
+/// The handler of the Easy2 keeping up the connections of a share,
+/// which never performs a transfer.
+#[derive(Debug)]
+struct Upkeep;
+
+impl Handler for Upkeep {}
+
+/// Calls `curl_easy_upkeep` every `interval` on an Easy2 attached to
+/// `share`, for as long as the worker is running.
+async fn upkeep_share(share: CurlShare, interval: Duration) {
+    let mut easy2 = Easy2::new(Upkeep);
+    let millis = interval.as_millis().min(std::ffi::c_long::MAX as u128) as std::ffi::c_long;
+    if let Err(err) = setopt_long(&mut easy2, CURLOPT_UPKEEP_INTERVAL_MS, millis) {
+        trace!("{err}");
+    }
+    if let Err(err) = share.attach(&mut easy2) {
+        trace!("The connections of the share cannot be kept up. {err}");
+        return std::future::pending().await;
+    }
+    let mut ticks = tokio::time::interval_at(Instant::now() + interval, interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        if let Err(err) = easy2.upkeep() {
+            trace!("{err}");
+        }
+    }
+}
+
+/// The buffer sizes every Easy2 of the actor starts with.
+#[derive(Debug, Clone, Copy, Default)]
+struct BufferSizes {
+    download: Option<usize>,
+    upload: Option<usize>,
+}
+
+impl BufferSizes {
+    fn apply<H>(&self, easy2: &mut Easy2<H>) {
+        if let Some(Err(err)) = self.download.map(|size| easy2.buffer_size(size)) {
+            trace!("{err}");
+        }
+        if let Some(Err(err)) = self.upload.map(|size| easy2.upload_buffer_size(size)) {
+            trace!("{err}");
+        }
+    }
+}
+
+/// Finished Easy2 handles kept for reuse, shared with every clone of the actor.
+struct HandlePool<H: Handler> {
+    size: usize,
+    handles: std::sync::Mutex<Vec<Easy2<H>>>,
+}
+
+/// Counters of the background worker shared with every clone of the actor.
+#[derive(Debug, Default)]
+struct Metrics {
+    waiting: AtomicUsize,
+    in_flight: AtomicUsize,
+}
+
+/// Counts a transfer as in flight until it is dropped,
+/// whichever way the transfer ends.
+struct InFlight(Arc<Metrics>);
+
+impl InFlight {
+    fn start(metrics: Arc<Metrics>) -> Self {
+        metrics.in_flight.fetch_add(1, Ordering::SeqCst);
+        Self(metrics)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The request queue the background workers take turns to start requests
+/// from, with the rate limiter that paces them.
+struct Queue<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    requests: mpsc::Receiver<Request<H>>,
+    rate_limiter: Option<TokenBucket>,
+    /// The requests taken from `requests` that wait for a token or a slot,
+    /// up to `capacity` of them, so the most urgent one is started first.
+    pending: BinaryHeap<Pending<H>>,
+    capacity: usize,
+    sequence: u64,
+}
+
+impl<H> Queue<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    /// Waits for a request, a rate limit token and a free slot, and returns
+    /// the request of the highest priority together with its slot. Returns
+    /// None once every sender is gone and no request is left.
+    async fn admit(
+        &mut self,
+        limiter: Option<&Arc<Semaphore>>,
+        metrics: &Metrics,
+    ) -> Option<(Request<H>, Option<OwnedSemaphorePermit>)> {
+        let Self {
+            requests,
+            rate_limiter,
+            pending,
+            capacity,
+            sequence,
+        } = self;
+        if pending.is_empty() {
+            let request = requests.recv().await?;
+            Self::push(pending, sequence, request, metrics);
+        }
+        // Wait for a token and a free slot before the transfer is added to a Multi,
+        // while the requests queued in the meantime compete for them.
+        let slot = async {
+            if let Some(rate_limiter) = rate_limiter {
+                rate_limiter.acquire().await;
+            }
+            match limiter {
+                Some(limiter) => limiter.clone().acquire_owned().await.map(Some),
+                None => Ok(None),
+            }
+        };
+        tokio::pin!(slot);
+        let permit = loop {
+            let room = pending.len() < *capacity;
+            tokio::select! {
+                biased;
+                permit = &mut slot => break permit.ok()?,
+                Some(request) = requests.recv(), if room => {
+                    Self::push(pending, sequence, request, metrics);
+                }
+            }
+        };
+        let Pending { request, .. } = pending.pop()?;
+        metrics.waiting.fetch_sub(1, Ordering::SeqCst);
+        Some((request, permit))
+    }
+
+    fn push(
+        pending: &mut BinaryHeap<Pending<H>>,
+        sequence: &mut u64,
+        request: Request<H>,
+        metrics: &Metrics,
+    ) {
+        metrics.waiting.fetch_add(1, Ordering::SeqCst);
+        pending.push(Pending {
+            request,
+            sequence: *sequence,
+        });
+        *sequence += 1;
+    }
+}
+
+/// A request waiting to be started, ordered by its priority, then by
+/// the order it was queued in.
+struct Pending<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    request: Request<H>,
+    sequence: u64,
+}
+
+impl<H> Pending<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn key(&self) -> (Priority, std::cmp::Reverse<u64>) {
+        (
+            self.request.options.priority,
+            std::cmp::Reverse(self.sequence),
+        )
+    }
+}
+
+impl<H> PartialEq for Pending<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<H> Eq for Pending<H> where H: Handler + Debug + Send + 'static {}
+
+impl<H> PartialOrd for Pending<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<H> Ord for Pending<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// A token bucket refilled at a steady rate, owned by the background worker.
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(requests_per_sec: u32, burst: u32) -> Self {
+        Self {
+            rate: requests_per_sec as f64,
+            burst: burst as f64,
+            tokens: burst as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Waits until a token is available and takes it.
+    async fn acquire(&mut self) {
+        loop {
+            let now = Instant::now();
+            let elapsed = now.duration_since(self.updated).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+            self.updated = now;
+            if self.tokens >= 1.0 {
+                self.tokens -= 1.0;
+                return;
+            }
+            sleep(Duration::from_secs_f64((1.0 - self.tokens) / self.rate)).await;
+        }
+    }
+}
+
+/// This queues the request on the background worker and awaits its response.
+async fn dispatch<H>(
+    request_sender: Sender<Request<H>>,
+    easy2: Easy2<H>,
+    options: RequestOptions,
+) -> Result<Easy2<H>, Error<H>>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    let (request, response) = Request::new(easy2, options);
+    request_sender.send(request).await?;
+    response.await?
+}
+
+/// This queues the request right away in the queue slot held by `permit`,
+/// and returns the future awaiting its response.
+#[cfg(feature = "tower")]
+pub(crate) fn dispatch_reserved<H>(
+    permit: OwnedPermit<Request<H>>,
     easy2: Easy2<H>,
+) -> impl Future<Output = Result<Easy2<H>, Error<H>>> + Send + 'static
+where
+    H: Handler + Debug + Send + 'static,
+{
+    let (request, response) = Request::new(easy2, RequestOptions::default());
+    permit.send(request);
+    async move { response.await? }
+}
+
+/// The stream returned by `CurlActor::stream`.
+#[cfg(feature = "stream")]
+struct Completions<H, S>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    request_sender: Sender<Request<H>>,
+    /// None once the stream of requests ended.
+    requests: Option<Pin<Box<S>>>,
+    transfers: JoinSet<Result<Easy2<H>, Error<H>>>,
+}
+
+#[cfg(feature = "stream")]
+impl<H, S> Stream for Completions<H, S>
+where
+    H: Handler + Debug + Send + 'static,
+    S: Stream<Item = Easy2<H>> + Send + 'static,
+{
+    type Item = Result<Easy2<H>, Error<H>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        while let Some(requests) = this.requests.as_mut() {
+            match requests.as_mut().poll_next(cx) {
+                Poll::Ready(Some(easy2)) => {
+                    let response = dispatch(
+                        this.request_sender.clone(),
+                        easy2,
+                        RequestOptions::default(),
+                    );
+                    this.transfers.spawn(response);
+                }
+                Poll::Ready(None) => this.requests = None,
+                Poll::Pending => break,
+            }
+        }
+        match this.transfers.poll_join_next(cx) {
+            Poll::Ready(Some(response)) => Poll::Ready(Some(
+                response.unwrap_or_else(|err| Err(Error::Io(err.into()))),
+            )),
+            // More requests may still come.
+            Poll::Ready(None) if this.requests.is_some() => Poll::Pending,
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Logs the outcome of the transfer at the debug level, reading the
+/// transfer info only when the level is enabled.
+fn log_transfer<H>(response: &Result<Easy2<H>, Error<H>>)
+where
+    H: Handler + Debug + Send + 'static,
+{
+    if !log::log_enabled!(log::Level::Debug) {
+        return;
+    }
+    match response {
+        Ok(easy2) => log::debug!(
+            "Transfer finished: url={} status={} time={:?} downloaded={} uploaded={}",
+            easy2.effective_url().ok().flatten().unwrap_or("-"),
+            easy2.response_code().unwrap_or(0),
+            easy2.total_time().unwrap_or_default(),
+            easy2.download_size().unwrap_or(0.0) as u64,
+            easy2.upload_size().unwrap_or(0.0) as u64,
+        ),
+        Err(err) => log::debug!(
+            "Transfer failed: code={} detail={} error={err}",
+            err.curl_code()
+                .map_or_else(|| "-".to_string(), |code| code.to_string()),
+            err.detail().unwrap_or("-"),
+        ),
+    }
+}
+
+/// Records the outcome of the transfer on the span of its request.
+#[cfg(feature = "tracing")]
+fn record_transfer<H>(span: &tracing::Span, response: &Result<Easy2<H>, Error<H>>)
+where
+    H: Handler + Debug + Send + 'static,
+{
+    match response {
+        Ok(easy2) => {
+            if let Ok(Some(url)) = easy2.effective_url() {
+                span.record("url", url);
+            }
+            if let Some(method) = crate::info::effective_method(easy2) {
+                span.record("method", method.as_str());
+            }
+            if let Ok(status) = easy2.response_code() {
+                span.record("status", status);
+            }
+            if let Ok(duration) = easy2.total_time() {
+                span.record("duration", tracing::field::debug(duration));
+            }
+            if let Ok(bytes) = easy2.download_size() {
+                span.record("bytes", bytes as u64);
+            }
+        }
+        Err(err) => {
+            span.record("error", tracing::field::display(err));
+            tracing::debug!(error = %err, "transfer failed");
+        }
+    }
+}
+
+/// Resolves once the `deadline` of the transfer has passed, never if there is none.
+async fn wait_for_deadline(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending::<()>().await,
+    }
+}
+
+/// Resolves once an abort is requested through the [`TransferHandle`],
+/// never if the transfer has no handle or the handle was dropped.
+/// Pause and resume requests are passed on to the perform loop meanwhile.
+async fn wait_for_abort(control: Option<UnboundedReceiver<Control>>, pause: &PauseState) {
+    if let Some(mut control) = control {
+        while let Some(control) = control.recv().await {
+            match control {
+                Control::Abort => return,
+                Control::Pause => pause.set(true),
+                Control::Resume => pause.set(false),
+            }
+        }
+    }
+    std::future::pending::<()>().await
+}
+
+/// Whether the transfer has been paused through its [`TransferHandle`].
+#[derive(Debug, Default)]
+struct PauseState {
+    paused: AtomicBool,
+    changed: Notify,
+}
+
+impl PauseState {
+    fn set(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+        self.changed.notify_one();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+}
+
+async fn perform_curl_multi<H: Handler + Debug + Send + 'static>(
+    mut easy2: Easy2<H>,
+    share: Option<&CurlShare>,
+    pause: &PauseState,
+    connection: Option<ConnectionSession>,
 ) -> Result<Easy2<H>, Error<H>> {
+    if let Some(share) = share {
+        share.attach(&mut easy2)?;
+    }
     let multi = Multi::new();
     let handle = multi.add2(easy2).map_err(|e| Error::Multi(e))?;
+    let wake = Arc::new(Notify::new());
+    let waker = Waker::from(Arc::new(TransferWaker(wake.clone())));
+    let mut panic = None;
 
-    while multi.perform().map_err(|e| Error::Multi(e))? != 0 {
+    loop {
+        let running = with_transfer_waker(&waker, &mut panic, || multi.perform())
+            .map_err(|e| Error::Multi(e))?;
+        if running == 0 {
+            break;
+        }
         let timeout_result = multi
             .get_timeout()
             .map(|d| d.unwrap_or_else(|| Duration::from_secs(2)));
@@ -236,28 +1451,253 @@ async fn perform_curl_multi<H: Handler + Debug + Send + 'static>(
         };
 
         if !timeout.is_zero() {
-            sleep(Duration::from_millis(200)).await;
+            tokio::select! {
+                _ = sleep(Duration::from_millis(200)) => {}
+                _ = wake.notified() => {}
+                _ = pause.changed.notified() => {}
+            }
+        }
+
+        if pause.is_paused() {
+            pause_transfer(&handle)?;
+        } else {
+            // Resume a handler that paused the transfer by returning `WriteError::Pause`
+            // or `ReadError::Pause`, a handler that is still not ready simply pauses again.
+            with_transfer_waker(&waker, &mut panic, || handle.unpause_write())
+                .map_err(|e| Error::Curl(e))?;
         }
     }
 
     let mut error: Option<Error<H>> = None;
+    // result_for2 attaches the CURLOPT_ERRORBUFFER contents of the Easy2 to the error.
     multi.messages(|msg| {
-        if let Some(Err(e)) = msg.result() {
-            error = Some(Error::Curl(e));
+        if let Some(Err(e)) = msg.result_for2(&handle) {
+            error = Some(if e.is_write_error() {
+                Error::HandlerAborted(e)
+            } else {
+                Error::Curl(e)
+            });
         }
     });
 
-    if let Some(e) = error {
+    // The callback that panicked made libcurl fail the transfer.
+    if let Some(message) = panic {
+        trace!("The handler panicked: {message}");
+        Err(Error::HandlerPanic(message))
+    } else if let Some(e) = error {
         Err(e)
     } else {
-        multi.remove2(handle).map_err(|e| Error::Multi(e))
+        // libcurl closes a connect-only connection once its Easy2 leaves the Multi.
+        if let Some(connection) = connection {
+            connection::serve(&multi, &handle, connection).await;
+        }
+        let mut easy2 = multi.remove2(handle).map_err(|e| Error::Multi(e))?;
+        // The caller may keep the Easy2 for longer than the actor keeps the share.
+        if let Some(share) = share {
+            share.detach(&mut easy2)?;
+        }
+        Ok(easy2)
+    }
+}
+
+/// Stops sending and receiving until the transfer is unpaused.
+fn pause_transfer<H: Handler + Debug + Send + 'static>(
+    handle: &Easy2Handle<H>,
+) -> Result<(), Error<H>> {
+    let rc = unsafe {
+        curl_sys::curl_easy_pause(
+            handle.raw(),
+            curl_sys::CURLPAUSE_RECV | curl_sys::CURLPAUSE_SEND,
+        )
+    };
+    if rc != curl_sys::CURLE_OK {
+        let err = curl::Error::new(rc);
+        trace!("{err}");
+        return Err(Error::Curl(err));
     }
+    Ok(())
 }
 
+thread_local! {
+    static TRANSFER_WAKER: RefCell<Option<Waker>> = const { RefCell::new(None) };
+    static HANDLER_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Wakes up the perform loop of a transfer, so a paused handler
+/// is resumed as soon as it is ready instead of on the next tick.
+struct TransferWaker(Arc<Notify>);
+
+impl Wake for TransferWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.notify_one();
+    }
+}
+
+/// Makes the waker of the transfer available to its handler
+/// callbacks while libcurl is driving them, and takes the panic
+/// one of them recorded into `panic`.
+fn with_transfer_waker<T>(waker: &Waker, panic: &mut Option<String>, f: impl FnOnce() -> T) -> T {
+    TRANSFER_WAKER.with(|current| *current.borrow_mut() = Some(waker.clone()));
+    let result = f();
+    TRANSFER_WAKER.with(|current| *current.borrow_mut() = None);
+    if let Some(message) = HANDLER_PANIC.with(|current| current.borrow_mut().take()) {
+        panic.get_or_insert(message);
+    }
+    result
+}
+
+/// Records the panic a `CatchUnwindHandler` caught, for the transfer
+/// whose callback is running to fail with `Error::HandlerPanic`.
+/// Outside of the actor there is no transfer to fail, so nothing is recorded.
+pub(crate) fn record_handler_panic(message: String) {
+    if TRANSFER_WAKER.with(|current| current.borrow().is_none()) {
+        return;
+    }
+    HANDLER_PANIC.with(|current| {
+        current.borrow_mut().get_or_insert(message);
+    });
+}
+
+/// Returns the waker of the transfer whose handler callback is running,
+/// for handlers that poll async sources from within a callback.
+pub(crate) fn transfer_waker() -> Waker {
+    TRANSFER_WAKER
+        .with(|current| current.borrow().clone())
+        .unwrap_or_else(|| Waker::noop().clone())
+}
+
+/// Where the response of a queued request arrives.
+type ResponseReceiver<H> = oneshot::Receiver<Result<Easy2<H>, Error<H>>>;
+
 /// This contains the Easy2 object and a oneshot sender channel when passing into the
 /// background task to perform Curl asynchronously.
 #[derive(Debug)]
-pub struct Request<H: Handler + Debug + Send + 'static>(
-    Easy2<H>,
-    oneshot::Sender<Result<Easy2<H>, Error<H>>>,
-);
+pub struct Request<H: Handler + Debug + Send + 'static> {
+    easy2: Easy2<H>,
+    sender: oneshot::Sender<Result<Easy2<H>, Error<H>>>,
+    options: RequestOptions,
+}
+
+impl<H> Request<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    /// Bundles the Easy2 with its options, together with the receiver
+    /// its response is sent to.
+    fn new(easy2: Easy2<H>, options: RequestOptions) -> (Self, ResponseReceiver<H>) {
+        #[cfg(feature = "tracing")]
+        let options = RequestOptions {
+            span: Some(tracing::info_span!(
+                "send_request",
+                url = tracing::field::Empty,
+                method = tracing::field::Empty,
+                status = tracing::field::Empty,
+                duration = tracing::field::Empty,
+                bytes = tracing::field::Empty,
+                error = tracing::field::Empty,
+            )),
+            ..options
+        };
+        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<Result<Easy2<H>, Error<H>>>();
+        let request = Request {
+            easy2,
+            sender: oneshot_sender,
+            options,
+        };
+        (request, oneshot_receiver)
+    }
+}
+
+/// The response of a request queued by `CurlActor::try_send_request`.
+#[derive(Debug)]
+pub struct PendingResponse<H>(ResponseReceiver<H>)
+where
+    H: Handler + Debug + Send + 'static;
+
+impl<H> Future for PendingResponse<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    type Output = Result<Easy2<H>, Error<H>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|response| response?)
+    }
+}
+
+/// Per-request settings that travel with the Easy2 to the background worker.
+#[derive(Debug, Default)]
+struct RequestOptions {
+    control: Option<UnboundedReceiver<Control>>,
+    timeout: Option<Duration>,
+    /// Overrides `timeout` with a point in time the transfer must be done by.
+    deadline: Option<Instant>,
+    /// Keeps the connection open once connected, see `AsyncCurl::connect`.
+    connection: Option<ConnectionSession>,
+    priority: Priority,
+    /// The span the transfer is performed in.
+    #[cfg(feature = "tracing")]
+    span: Option<tracing::Span>,
+}
+
+/// Priority decides which of the requests waiting for a free slot of
+/// `CurlActorBuilder::max_concurrent` or a rate limit token is started
+/// next, see `CurlActor::send_request_prioritized`.
+///
+/// Requests of the same priority are started in the order they were sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Bulk background work that can wait for the other requests.
+    Low,
+    /// The priority of `send_request`.
+    #[default]
+    Normal,
+    /// Latency-critical requests started before any other waiting one.
+    High,
+}
+
+/// Commands sent from a [`TransferHandle`] to the transfer it refers to.
+#[derive(Debug)]
+enum Control {
+    Abort,
+    Pause,
+    Resume,
+}
+
+/// TransferHandle refers to exactly one transfer queued through
+/// `CurlActor::send_request_with_handle` and can pause, resume or cancel
+/// it from anywhere.
+#[derive(Debug, Clone)]
+pub struct TransferHandle {
+    control: UnboundedSender<Control>,
+}
+
+impl TransferHandle {
+    /// Tells the background worker to remove this transfer from its Multi.
+    /// Aborting a transfer that already finished has no effect.
+    pub fn abort(&self) {
+        if self.control.send(Control::Abort).is_err() {
+            trace!("The transfer has already finished.");
+        }
+    }
+
+    /// Stops the transfer from sending or receiving any data, e.g. while the
+    /// consumer of the body cannot keep up. The connection is kept open.
+    pub fn pause(&self) {
+        if self.control.send(Control::Pause).is_err() {
+            trace!("The transfer has already finished.");
+        }
+    }
+
+    /// Continues a transfer stopped by `pause`.
+    pub fn resume(&self) {
+        if self.control.send(Control::Resume).is_err() {
+            trace!("The transfer has already finished.");
+        }
+    }
+
+    /// Returns true once the transfer has finished or has been aborted.
+    pub fn is_finished(&self) -> bool {
+        self.control.is_closed()
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::response::Response;
+
+/// A response kept by the cache, with the validators to revalidate it.
+#[derive(Debug, Clone)]
+pub(crate) struct CachedResponse {
+    /// The `ETag` of the response, sent back in `If-None-Match`.
+    pub(crate) etag: Option<String>,
+    /// The `Last-Modified` time of the response as seconds since the epoch,
+    /// sent back in `If-Modified-Since`.
+    pub(crate) last_modified: Option<i64>,
+    pub(crate) response: Response,
+}
+
+/// HttpCache keeps responses in memory by URL, so the requests performed
+/// through `AsyncCurl::perform_cached` only download a resource again when
+/// it changed.
+///
+/// A successful response with an `ETag` or a `Last-Modified` header is kept,
+/// unless it has `Cache-Control: no-store`. The next request for the URL
+/// asks the server whether it changed through `If-None-Match` and
+/// `If-Modified-Since`, and the kept response is returned when it did not.
+/// Clones share the same responses.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::cache::HttpCache;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::CollectingHandler;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let actor = CurlActor::new();
+/// let cache = HttpCache::new();
+///
+/// for _ in 0..2 {
+///     // The second request gets a 304 and the body kept by the first one.
+///     let response = AsyncCurl::new(actor.clone(), CollectingHandler::new())
+///         .url("https://example.com/config.json")?
+///         .finalize()
+///         .perform_cached(&cache)
+///         .await?;
+///     println!("{} bytes of config", response.body.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HttpCache {
+    entries: Arc<Mutex<HashMap<String, CachedResponse>>>,
+}
+
+impl HttpCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the response kept for `url`.
+    pub fn get(&self, url: &str) -> Option<Response> {
+        self.lock().get(url).map(|entry| entry.response.clone())
+    }
+
+    /// Forgets the response kept for `url`, so the next request for it
+    /// downloads the resource unconditionally.
+    pub fn remove(&self, url: &str) {
+        self.lock().remove(url);
+    }
+
+    /// Forgets every response.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Returns how many responses are kept.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns whether no response is kept.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    pub(crate) fn entry(&self, url: &str) -> Option<CachedResponse> {
+        self.lock().get(url).cloned()
+    }
+
+    /// Keeps a successful response for `url` if it can be revalidated, or
+    /// forgets the one kept before if it cannot. Other responses leave the
+    /// cache alone, as only a 304 gives back the kept response.
+    pub(crate) fn store(&self, url: &str, response: &Response, last_modified: Option<i64>) {
+        if response.status != 200 {
+            return;
+        }
+        let etag = response
+            .headers
+            .get(http::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let no_store = response
+            .headers
+            .get_all(http::header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|directive| directive.trim().eq_ignore_ascii_case("no-store"));
+        let mut entries = self.lock();
+        if no_store || (etag.is_none() && last_modified.is_none()) {
+            entries.remove(url);
+            return;
+        }
+        entries.insert(
+            url.to_string(),
+            CachedResponse {
+                etag,
+                last_modified,
+                response: response.clone(),
+            },
+        );
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, CachedResponse>> {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
@@ -0,0 +1,292 @@
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use curl::easy::{Easy2, Handler};
+use tokio::time::Instant;
+
+use crate::actor::{Actor, CurlActor};
+use crate::error::Error;
+
+/// Decides whether the outcome of a request counts as a failure.
+type Classify<H> = Arc<dyn Fn(Result<&Easy2<H>, &Error<H>>) -> bool + Send + Sync>;
+
+/// The state of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests go through, while the failures in a row are counted.
+    Closed,
+    /// Requests fail with `Error::CircuitOpen` until the cooldown is over.
+    Open,
+    /// The cooldown is over and one request at a time is let through to
+    /// probe the upstream, closing the circuit if it succeeds.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    /// The failures in a row, and when the first of them happened.
+    failures: u32,
+    first_failure: Option<Instant>,
+    /// When the open circuit may be probed.
+    reopen_at: Option<Instant>,
+    /// A probe of the half-open circuit is in flight.
+    probing: bool,
+}
+
+/// CircuitBreaker is an [`Actor`] that stops sending requests to an upstream
+/// that keeps failing, so the failures don't pile up on both sides.
+///
+/// After `failure_threshold` failures in a row within `window`, the circuit
+/// opens and every request fails right away with `Error::CircuitOpen` for
+/// `cooldown`. Then one request is let through: the circuit closes again if
+/// it succeeds and opens for another cooldown if it fails. By default every
+/// error and every response with a 5xx status counts as a failure.
+///
+/// Clones share the same circuit.
+/// ```
+/// use std::time::Duration;
+///
+/// use async_curl::actor::CurlActor;
+/// use async_curl::circuit::CircuitBreaker;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::CollectingHandler;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let breaker = CircuitBreaker::new(CurlActor::<CollectingHandler>::new())
+///     .failure_threshold(5)
+///     .window(Duration::from_secs(30))
+///     .cooldown(Duration::from_secs(10))
+///     .is_failure(|outcome| outcome.is_err());
+/// let client = AsyncCurl::new(breaker.clone(), CollectingHandler::new())
+///     .url("https://example.com/flaky")?
+///     .finalize();
+/// # Ok(())
+/// # }
+/// ```
+pub struct CircuitBreaker<H, A = CurlActor<H>>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    actor: A,
+    failure_threshold: u32,
+    window: Duration,
+    cooldown: Duration,
+    is_failure: Classify<H>,
+    circuit: Arc<Mutex<Circuit>>,
+    _handler: PhantomData<fn() -> H>,
+}
+
+impl<H, A> Clone for CircuitBreaker<H, A>
+where
+    H: Handler + Debug + Send + 'static,
+    A: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            actor: self.actor.clone(),
+            failure_threshold: self.failure_threshold,
+            window: self.window,
+            cooldown: self.cooldown,
+            is_failure: self.is_failure.clone(),
+            circuit: self.circuit.clone(),
+            _handler: PhantomData,
+        }
+    }
+}
+
+impl<H, A> Debug for CircuitBreaker<H, A>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("failure_threshold", &self.failure_threshold)
+            .field("window", &self.window)
+            .field("cooldown", &self.cooldown)
+            .field("circuit", &self.circuit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H, A> CircuitBreaker<H, A>
+where
+    H: Handler + Debug + Send + 'static,
+    A: Actor<H>,
+{
+    /// Wraps `actor` in a closed circuit that opens after five failures in
+    /// a row within a minute, for a cooldown of 30 seconds.
+    pub fn new(actor: A) -> Self {
+        Self {
+            actor,
+            failure_threshold: 5,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(30),
+            is_failure: Arc::new(|outcome| match outcome {
+                Ok(easy2) => easy2.response_code().is_ok_and(|code| code >= 500),
+                Err(_) => true,
+            }),
+            circuit: Arc::new(Mutex::new(Circuit {
+                state: CircuitState::Closed,
+                failures: 0,
+                first_failure: None,
+                reopen_at: None,
+                probing: false,
+            })),
+            _handler: PhantomData,
+        }
+    }
+
+    /// Sets how many failures in a row open the circuit.
+    ///
+    /// A value of zero is treated as one.
+    pub fn failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    /// Sets how close together the failures must be: the count starts over
+    /// with a failure that comes later than this after the first one.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets how long the circuit stays open before it is probed.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Sets which outcomes count as failures, given the finished Easy2 or
+    /// the error of the request.
+    pub fn is_failure<F>(mut self, is_failure: F) -> Self
+    where
+        F: Fn(Result<&Easy2<H>, &Error<H>>) -> bool + Send + Sync + 'static,
+    {
+        self.is_failure = Arc::new(is_failure);
+        self
+    }
+
+    /// Returns the state of the circuit, which turns half-open once the
+    /// cooldown of an open circuit is over.
+    pub fn state(&self) -> CircuitState {
+        let mut circuit = self.lock();
+        if circuit.state == CircuitState::Open
+            && circuit.reopen_at.is_some_and(|at| Instant::now() >= at)
+        {
+            circuit.state = CircuitState::HalfOpen;
+        }
+        circuit.state
+    }
+
+    /// Returns the wrapped actor.
+    pub fn get_ref(&self) -> &A {
+        &self.actor
+    }
+
+    /// Decides whether a request may go through, and whether it is the
+    /// probe of a half-open circuit.
+    fn admit(&self) -> Option<bool> {
+        let state = self.state();
+        let mut circuit = self.lock();
+        match state {
+            CircuitState::Closed => Some(false),
+            CircuitState::Open => None,
+            CircuitState::HalfOpen if circuit.probing => None,
+            CircuitState::HalfOpen => {
+                circuit.probing = true;
+                Some(true)
+            }
+        }
+    }
+
+    fn record(&self, failed: bool, probe: bool) {
+        let mut circuit = self.lock();
+        let now = Instant::now();
+        if probe {
+            circuit.probing = false;
+        }
+        if !failed {
+            if circuit.state != CircuitState::Closed {
+                log::debug!("Circuit closed");
+            }
+            circuit.state = CircuitState::Closed;
+            circuit.failures = 0;
+            circuit.first_failure = None;
+            return;
+        }
+        if circuit.state == CircuitState::Closed {
+            match circuit.first_failure {
+                Some(first) if now.duration_since(first) <= self.window => circuit.failures += 1,
+                _ => {
+                    circuit.failures = 1;
+                    circuit.first_failure = Some(now);
+                }
+            }
+            if circuit.failures < self.failure_threshold {
+                return;
+            }
+        } else if !probe {
+            // A request let through before the circuit opened.
+            return;
+        }
+        log::debug!("Circuit opened for {:?}", self.cooldown);
+        circuit.state = CircuitState::Open;
+        circuit.failures = 0;
+        circuit.first_failure = None;
+        circuit.reopen_at = Some(now + self.cooldown);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Circuit> {
+        self.circuit.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Lets the next request probe the circuit again when a probe is dropped
+/// before it finished.
+struct Probe<'a> {
+    circuit: &'a Mutex<Circuit>,
+    armed: bool,
+}
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            let mut circuit = self.circuit.lock().unwrap_or_else(|err| err.into_inner());
+            circuit.probing = false;
+        }
+    }
+}
+
+#[async_trait]
+impl<H, A> Actor<H> for CircuitBreaker<H, A>
+where
+    H: Handler + Debug + Send + 'static,
+    A: Actor<H>,
+{
+    async fn send_request(&self, easy2: Easy2<H>) -> Result<Easy2<H>, Error<H>> {
+        let Some(probe) = self.admit() else {
+            return Err(Error::CircuitOpen);
+        };
+        let mut guard = Probe {
+            circuit: &self.circuit,
+            armed: probe,
+        };
+        let response = self.actor.send_request(easy2).await;
+        guard.armed = false;
+        self.record((self.is_failure)(response.as_ref()), probe);
+        response
+    }
+
+    fn easy_handle(&self, handler: H) -> Easy2<H> {
+        self.actor.easy_handle(handler)
+    }
+
+    fn recycle(&self, easy2: Easy2<H>) {
+        self.actor.recycle(easy2);
+    }
+}
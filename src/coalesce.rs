@@ -0,0 +1,116 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bytes::Bytes;
+use curl::easy::Easy2;
+use tokio::sync::watch;
+
+use crate::actor::{Actor, CurlActor};
+use crate::error::CurlError;
+use crate::handler::BytesHandler;
+
+/// The body of a coalesced transfer, or why it failed, once it finished.
+type Outcome = Option<Result<Bytes, CurlError>>;
+
+/// Coalescer keeps track of the coalesced transfers in flight, so a
+/// request for a key that is already being fetched waits for that transfer.
+#[derive(Default)]
+pub(crate) struct Coalescer {
+    transfers: Mutex<HashMap<Box<dyn Key>, watch::Receiver<Outcome>>>,
+}
+
+impl Coalescer {
+    /// Returns the body of the transfer for `key`, starting it with the
+    /// Easy2 built by `make_easy` unless one is already in flight.
+    pub(crate) async fn send<K, F>(
+        self: &Arc<Self>,
+        actor: &CurlActor<BytesHandler>,
+        key: K,
+        make_easy: F,
+    ) -> Result<Bytes, CurlError>
+    where
+        K: Hash + Eq + Clone + Send + 'static,
+        F: FnOnce() -> Easy2<BytesHandler>,
+    {
+        let mut outcome = {
+            let mut transfers = self.lock();
+            match transfers.get(&key as &dyn Key) {
+                Some(outcome) => outcome.clone(),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    transfers.insert(Box::new(key.clone()), receiver.clone());
+                    let easy2 = make_easy();
+                    let actor = actor.clone();
+                    let coalescer = self.clone();
+                    // The transfer runs on its own, so the other callers still
+                    // get its body when the one that started it goes away.
+                    tokio::spawn(async move {
+                        let response = actor.send_request(easy2).await;
+                        coalescer.lock().remove(&key as &dyn Key);
+                        let response = response
+                            .map(|mut easy2| easy2.get_mut().take_bytes())
+                            .map_err(CurlError::from);
+                        sender.send_replace(Some(response));
+                    });
+                    receiver
+                }
+            }
+        };
+        let outcome = outcome
+            .wait_for(Option::is_some)
+            .await
+            .map(|outcome| outcome.clone());
+        match outcome {
+            Ok(Some(response)) => response,
+            // The transfer was dropped before it finished.
+            _ => Err(CurlError::Cancelled),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Box<dyn Key>, watch::Receiver<Outcome>>> {
+        self.transfers.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// A key of any `Hash + Eq` type, so the keys of every caller fit one map.
+trait Key: Send {
+    fn as_any(&self) -> &dyn Any;
+
+    fn eq_key(&self, other: &dyn Key) -> bool;
+
+    fn hash_key(&self, state: &mut dyn Hasher);
+}
+
+impl<K> Key for K
+where
+    K: Hash + Eq + Send + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_key(&self, other: &dyn Key) -> bool {
+        other.as_any().downcast_ref::<K>() == Some(self)
+    }
+
+    fn hash_key(&self, mut state: &mut dyn Hasher) {
+        TypeId::of::<K>().hash(&mut state);
+        self.hash(&mut state);
+    }
+}
+
+impl PartialEq for dyn Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_key(other)
+    }
+}
+
+impl Eq for dyn Key {}
+
+impl Hash for dyn Key {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        self.hash_key(state);
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt::Debug;
+use std::future::Future;
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::time::Duration;
+
+use curl::easy::{Easy2, Handler};
+use curl::multi::{Easy2Handle, Multi, WaitFd};
+#[cfg(unix)]
+use tokio::io::unix::AsyncFd;
+#[cfg(unix)]
+use tokio::io::Interest;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::time::sleep;
+
+use crate::error::Error;
+#[cfg(feature = "websocket")]
+use crate::websocket::{self, Frame};
+
+const CURLINFO_SOCKET: curl_sys::CURLINFO = 0x500000;
+/// `CURLINFO_ACTIVESOCKET`, which curl-sys does not define.
+const CURLINFO_ACTIVESOCKET: curl_sys::CURLINFO = CURLINFO_SOCKET + 44;
+
+type PerformFuture<H> = Pin<Box<dyn Future<Output = Result<Easy2<H>, Error<H>>> + Send>>;
+
+/// Connection is the connection left open by `AsyncCurl::connect`, after
+/// libcurl connected to the host and did the TLS handshake, if any.
+///
+/// The Easy2 stays in the Multi of the actor's background worker, since
+/// libcurl closes the connection once it is removed from there, so `send`
+/// and `recv` are carried out by the worker. Dropping the Connection
+/// closes the connection.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::CollectingHandler;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut connection = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+///     .url("https://example.com")?
+///     .finalize()
+///     .connect()
+///     .await?;
+///
+/// connection.send_all(b"PING\r\n").await?;
+/// let mut buf = [0; 1024];
+/// let len = connection.recv(&mut buf).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Connection<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    io: UnboundedSender<ConnectionIo>,
+    response: PerformFuture<H>,
+}
+
+impl<H> Debug for Connection<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("closed", &self.io.is_closed())
+            .finish()
+    }
+}
+
+impl<H> Connection<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    /// Waits until the worker has connected the transfer queued by `response`.
+    pub(crate) async fn open(
+        io: UnboundedSender<ConnectionIo>,
+        connected: oneshot::Receiver<()>,
+        response: impl Future<Output = Result<Easy2<H>, Error<H>>> + Send + 'static,
+    ) -> Result<Self, Error<H>> {
+        let mut response: PerformFuture<H> = Box::pin(response);
+        let finished = tokio::select! {
+            result = connected => match result {
+                Ok(()) => None,
+                // The worker gave up on the connection, the transfer tells why.
+                Err(_) => Some((&mut response).await),
+            },
+            result = &mut response => Some(result),
+        };
+        match finished {
+            None => Ok(Self { io, response }),
+            Some(result) => {
+                result?;
+                let err = curl::Error::new(curl_sys::CURLE_COULDNT_CONNECT);
+                log::trace!("{err}");
+                Err(Error::Curl(err))
+            }
+        }
+    }
+
+    /// Sends bytes from `data`, returning how many were sent once the
+    /// connection accepted at least one.
+    ///
+    /// This corresponds to `curl_easy_send`.
+    pub async fn send(&mut self, data: &[u8]) -> Result<usize, Error<H>> {
+        let (reply, result) = oneshot::channel();
+        self.request(ConnectionIo::Send(data.to_vec(), reply))?;
+        result.await?.map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })
+    }
+
+    /// Sends the whole of `data`.
+    pub async fn send_all(&mut self, mut data: &[u8]) -> Result<(), Error<H>> {
+        while !data.is_empty() {
+            let len = self.send(data).await?;
+            data = &data[len..];
+        }
+        Ok(())
+    }
+
+    /// Receives bytes into `buf`, returning how many were received once at
+    /// least one arrived, and 0 once the host closed the connection.
+    ///
+    /// This corresponds to `curl_easy_recv`.
+    pub async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, Error<H>> {
+        let (reply, result) = oneshot::channel();
+        self.request(ConnectionIo::Recv(buf.len(), reply))?;
+        let data = result.await?.map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        buf[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+
+    /// Closes the connection and returns the Easy2 once the worker let go of it.
+    pub async fn close(self) -> Result<Easy2<H>, Error<H>> {
+        drop(self.io);
+        self.response.await
+    }
+
+    pub(crate) fn request(&self, io: ConnectionIo) -> Result<(), Error<H>> {
+        self.io.send(io).map_err(|_| {
+            let err = curl::Error::new(curl_sys::CURLE_SEND_ERROR);
+            log::trace!("The connection has already been closed.");
+            Error::Curl(err)
+        })
+    }
+}
+
+/// An operation on the socket, carried out by the worker holding the Easy2.
+#[derive(Debug)]
+pub(crate) enum ConnectionIo {
+    Send(Vec<u8>, oneshot::Sender<Result<usize, curl::Error>>),
+    Recv(usize, oneshot::Sender<Result<Vec<u8>, curl::Error>>),
+    #[cfg(feature = "websocket")]
+    SendFrame(Frame, oneshot::Sender<Result<(), curl::Error>>),
+    #[cfg(feature = "websocket")]
+    RecvFrame(usize, oneshot::Sender<Result<Frame, curl::Error>>),
+}
+
+/// What the worker needs to keep the connection of a transfer open.
+#[derive(Debug)]
+pub(crate) struct ConnectionSession {
+    pub(crate) io: UnboundedReceiver<ConnectionIo>,
+    pub(crate) connected: oneshot::Sender<()>,
+}
+
+/// Carries out the socket operations of a connected transfer until its
+/// [`Connection`] is dropped.
+pub(crate) async fn serve<H>(multi: &Multi, handle: &Easy2Handle<H>, session: ConnectionSession)
+where
+    H: Handler + Debug + Send + 'static,
+{
+    let ConnectionSession { mut io, connected } = session;
+    if connected.send(()).is_err() {
+        return;
+    }
+    let mut socket: curl_sys::curl_socket_t = curl_sys::CURL_SOCKET_BAD;
+    unsafe { curl_sys::curl_easy_getinfo(handle.raw(), CURLINFO_ACTIVESOCKET, &mut socket) };
+    let readiness = Readiness::new(socket);
+    while let Some(request) = io.recv().await {
+        match request {
+            ConnectionIo::Send(data, reply) => {
+                let result = loop {
+                    match send(handle, &data) {
+                        Err(err) if err.is_again() && !reply.is_closed() => {
+                            readiness.wait_for(multi, socket, false).await;
+                        }
+                        result => break result,
+                    }
+                };
+                let _ = reply.send(result);
+            }
+            ConnectionIo::Recv(len, reply) => {
+                let mut buf = vec![0; len];
+                let result = loop {
+                    match recv(handle, &mut buf) {
+                        Err(err) if err.is_again() && !reply.is_closed() => {
+                            readiness.wait_for(multi, socket, true).await;
+                        }
+                        result => break result,
+                    }
+                };
+                let _ = reply.send(result.map(|len| {
+                    buf.truncate(len);
+                    buf
+                }));
+            }
+            #[cfg(feature = "websocket")]
+            ConnectionIo::SendFrame(frame, reply) => {
+                let mut data = frame.payload.as_slice();
+                let result = loop {
+                    match websocket::send(handle, data, &frame) {
+                        Ok(len) if len < data.len() => data = &data[len..],
+                        Err(err) if err.is_again() && !reply.is_closed() => {
+                            readiness.wait_for(multi, socket, false).await;
+                        }
+                        result => break result.map(drop),
+                    }
+                };
+                let _ = reply.send(result);
+            }
+            #[cfg(feature = "websocket")]
+            ConnectionIo::RecvFrame(max_frame_size, reply) => {
+                let mut partial = None;
+                let result = loop {
+                    match websocket::recv(handle, &mut partial, max_frame_size) {
+                        Ok(Some(frame)) => break Ok(frame),
+                        Ok(None) => {}
+                        Err(err) if err.is_again() && !reply.is_closed() => {
+                            readiness.wait_for(multi, socket, true).await;
+                        }
+                        Err(err) => break Err(err),
+                    }
+                };
+                let _ = reply.send(result);
+            }
+        }
+    }
+}
+
+/// The socket of a connected transfer, which libcurl keeps owning.
+#[cfg(unix)]
+struct Socket(RawFd);
+
+#[cfg(unix)]
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// Readiness waits for the socket of a connected transfer to be readable or
+/// writable, registered with the I/O driver of the worker's runtime so it
+/// wakes up the moment it is, without holding up the other transfers.
+///
+/// On platforms other than Unix, or if the socket cannot be registered, the
+/// socket is checked through `multi.wait` every few milliseconds instead.
+struct Readiness {
+    #[cfg(unix)]
+    socket: Option<AsyncFd<Socket>>,
+}
+
+impl Readiness {
+    fn new(socket: curl_sys::curl_socket_t) -> Self {
+        #[cfg(unix)]
+        {
+            let interest = Interest::READABLE | Interest::WRITABLE;
+            let socket = AsyncFd::with_interest(Socket(socket), interest)
+                .map_err(|err| log::trace!("{err}"))
+                .ok();
+            Self { socket }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = socket;
+            Self {}
+        }
+    }
+
+    /// Waits until the socket can be read from, or written to, after
+    /// libcurl answered `CURLE_AGAIN`.
+    async fn wait_for(&self, multi: &Multi, socket: curl_sys::curl_socket_t, read: bool) {
+        #[cfg(unix)]
+        if let Some(fd) = &self.socket {
+            let guard = if read {
+                fd.readable().await
+            } else {
+                fd.writable().await
+            };
+            // The operation is retried right after, so clearing the readiness
+            // first misses none of the data that arrives in between.
+            match guard {
+                Ok(mut guard) => guard.clear_ready(),
+                Err(err) => log::trace!("{err}"),
+            }
+            return;
+        }
+        poll_for(multi, socket, read).await;
+    }
+}
+
+/// Waits until the socket can be read from, or written to, checking it
+/// through `multi.wait` without blocking the other transfers of the worker.
+async fn poll_for(multi: &Multi, socket: curl_sys::curl_socket_t, read: bool) {
+    loop {
+        let mut wait_fd = WaitFd::new();
+        wait_fd.set_fd(socket);
+        wait_fd.poll_on_read(read).poll_on_write(!read);
+        let mut wait_fds = [wait_fd];
+        match multi.wait(&mut wait_fds, Duration::ZERO) {
+            Ok(_) if wait_fds[0].received_read() || wait_fds[0].received_write() => return,
+            Ok(_) => sleep(Duration::from_millis(5)).await,
+            Err(err) => {
+                log::trace!("{err}");
+                return;
+            }
+        }
+    }
+}
+
+fn send<H>(handle: &Easy2Handle<H>, data: &[u8]) -> Result<usize, curl::Error> {
+    let mut len = 0;
+    let rc = unsafe {
+        curl_sys::curl_easy_send(
+            handle.raw(),
+            data.as_ptr() as *const _,
+            data.len(),
+            &mut len,
+        )
+    };
+    if rc != curl_sys::CURLE_OK {
+        return Err(curl::Error::new(rc));
+    }
+    Ok(len)
+}
+
+fn recv<H>(handle: &Easy2Handle<H>, buf: &mut [u8]) -> Result<usize, curl::Error> {
+    let mut len = 0;
+    let rc = unsafe {
+        curl_sys::curl_easy_recv(
+            handle.raw(),
+            buf.as_mut_ptr() as *mut _,
+            buf.len(),
+            &mut len,
+        )
+    };
+    if rc != curl_sys::CURLE_OK {
+        return Err(curl::Error::new(rc));
+    }
+    Ok(len)
+}
@@ -0,0 +1,109 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use curl::easy::{Easy2, Handler};
+
+use crate::error::Error;
+use crate::info::TransferInfo;
+
+/// CookieJar keeps cookies in memory, so they flow between the requests it
+/// is attached to through `AsyncCurl::cookie_store` without a cookie file.
+///
+/// The cookies are loaded into the handle when the request is performed,
+/// and replaced by the ones the handle holds afterwards. Requests sharing
+/// a jar should therefore run one after another, as a login flow does.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::cookie::CookieJar;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::CollectingHandler;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let actor = CurlActor::new();
+/// let jar = CookieJar::new();
+///
+/// AsyncCurl::new(actor.clone(), CollectingHandler::new())
+///     .url("https://example.com/login")?
+///     .cookie_store(&jar)
+///     .finalize()
+///     .perform()
+///     .await?;
+/// // Sends the session cookie set by the login.
+/// AsyncCurl::new(actor, CollectingHandler::new())
+///     .url("https://example.com/profile")?
+///     .cookie_store(&jar)
+///     .finalize()
+///     .perform()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Arc<Mutex<Vec<String>>>,
+}
+
+impl CookieJar {
+    /// Creates an empty jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a jar holding the given Netscape cookie file lines,
+    /// e.g. the ones saved from `cookies` earlier.
+    pub fn from_lines<I>(lines: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let cookies = lines.into_iter().map(Into::into).collect();
+        Self {
+            cookies: Arc::new(Mutex::new(cookies)),
+        }
+    }
+
+    /// Returns the cookies in the jar, one Netscape cookie file line each.
+    pub fn cookies(&self) -> Vec<String> {
+        self.lock().clone()
+    }
+
+    /// Removes every cookie from the jar.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Replaces the cookies of the handle by the ones in the jar.
+    pub(crate) fn load<H>(&self, easy: &mut Easy2<H>) -> Result<(), Error<H>>
+    where
+        H: Handler + Debug + Send + 'static,
+    {
+        let load = |easy: &mut Easy2<H>| -> Result<(), curl::Error> {
+            // An empty file name enables the cookie engine without reading a file.
+            easy.cookie_file("")?;
+            easy.cookie_list("ALL")?;
+            for cookie in self.lock().iter() {
+                easy.cookie_list(cookie)?;
+            }
+            Ok(())
+        };
+        load(easy).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })
+    }
+
+    /// Replaces the cookies in the jar by the ones the handle holds.
+    pub(crate) fn store<H>(&self, easy: &Easy2<H>) -> Result<(), Error<H>>
+    where
+        H: Handler + Debug + Send + 'static,
+    {
+        let cookies = easy.cookies()?;
+        *self.lock() = cookies;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<String>> {
+        self.cookies.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
@@ -1,47 +1,274 @@
-use std::{path::Path, time::Duration};
+use std::{
+    ffi::CString,
+    fs::OpenOptions,
+    future::Future,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+    time::Duration,
+};
 
 use curl::easy::{
     Auth, Easy2, Form, Handler, HttpVersion, IpResolve, List, NetRc, ProxyType, SslOpt, SslVersion,
     TimeCondition,
 };
 
+use http::HeaderMap;
+use tokio::sync::watch;
+
+#[cfg(feature = "websocket")]
+use crate::websocket::{self, WebSocket};
 use crate::{
     actor::{Actor, CurlActor},
+    cache::HttpCache,
+    connection::Connection,
+    cookie::CookieJar,
     error::Error,
+    handler::{
+        CallbackHandler, CollectingHandler, DecodingHandler, FileHandler, LinesHandler,
+        ProgressInfo, ReadSource, StreamHandler,
+    },
+    info,
+    multipart::Multipart,
+    plan::RequestPlan,
+    response::Response,
+    version::version_info,
 };
 
+/// The result of performing a transfer with the handler `C`.
+type PerformResult<C> = Result<Easy2<C>, Error<C>>;
+
+/// `CURLE_NOT_BUILT_IN`, which curl-sys does not define.
+pub(crate) const CURLE_NOT_BUILT_IN: curl_sys::CURLcode = 4;
+/// `CURL_HTTP_VERSION_3ONLY`, which curl-sys does not define.
+const CURL_HTTP_VERSION_3ONLY: std::ffi::c_long = 31;
+/// `CURLOPT_HAPPY_EYEBALLS_TIMEOUT_MS`, which curl-sys does not define.
+const CURLOPT_HAPPY_EYEBALLS_TIMEOUT_MS: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 271;
+/// `CURLOPT_DNS_INTERFACE`, which curl-sys does not define.
+const CURLOPT_DNS_INTERFACE: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 221;
+/// `CURLOPT_DNS_LOCAL_IP4`, which curl-sys does not define.
+const CURLOPT_DNS_LOCAL_IP4: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 222;
+/// `CURLOPT_DNS_LOCAL_IP6`, which curl-sys does not define.
+const CURLOPT_DNS_LOCAL_IP6: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 223;
+/// `CURLOPT_DNS_SHUFFLE_ADDRESSES`, which curl-sys does not define.
+const CURLOPT_DNS_SHUFFLE_ADDRESSES: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 275;
+/// `CURLOPT_ALTSVC_CTRL`, which curl-sys does not define.
+const CURLOPT_ALTSVC_CTRL: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 286;
+/// `CURLOPT_ALTSVC`, which curl-sys does not define.
+const CURLOPT_ALTSVC: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 287;
+/// `CURLOPT_HSTS_CTRL`, which curl-sys does not define.
+const CURLOPT_HSTS_CTRL: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 299;
+/// `CURLOPT_HSTS`, which curl-sys does not define.
+const CURLOPT_HSTS: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 300;
+/// `CURLALTSVC_H1 | CURLALTSVC_H2 | CURLALTSVC_H3`.
+const CURLALTSVC_ALL: std::ffi::c_long = (1 << 3) | (1 << 4) | (1 << 5);
+/// `CURLHSTS_ENABLE`.
+const CURLHSTS_ENABLE: std::ffi::c_long = 1;
+/// `CURLOPT_SSL_VERIFYSTATUS`, which curl-sys does not define.
+const CURLOPT_SSL_VERIFYSTATUS: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 232;
+/// `CURLOPT_XOAUTH2_BEARER`, which curl-sys does not define.
+const CURLOPT_XOAUTH2_BEARER: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 220;
+/// `CURLOPT_LOGIN_OPTIONS`, which curl-sys does not define.
+const CURLOPT_LOGIN_OPTIONS: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 224;
+/// `CURLOPT_PROXY_SERVICE_NAME`, which curl-sys does not define.
+const CURLOPT_PROXY_SERVICE_NAME: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 235;
+/// `CURLOPT_SERVICE_NAME`, which curl-sys does not define.
+const CURLOPT_SERVICE_NAME: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 236;
+/// `CURLOPT_PROTOCOLS_STR`, which curl-sys does not define.
+const CURLOPT_PROTOCOLS_STR: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 318;
+/// `CURLOPT_REDIR_PROTOCOLS_STR`, which curl-sys does not define.
+const CURLOPT_REDIR_PROTOCOLS_STR: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 319;
+
+/// The HTTP method of a request, set through `AsyncCurl::method`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    /// Any other method, sent as given.
+    Custom(String),
+}
+
+impl Method {
+    /// Returns the name of the method as it appears in the request line.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Custom(method) => method,
+        }
+    }
+}
+
+/// A protocol a transfer may use, see `AsyncCurl::allowed_protocols`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Http,
+    Https,
+    Ftp,
+    Ftps,
+    Sftp,
+    Scp,
+    File,
+    Smtp,
+    Smtps,
+    Imap,
+    Imaps,
+    Pop3,
+    Pop3s,
+    Ws,
+    Wss,
+    Mqtt,
+    Ldap,
+    Ldaps,
+    Dict,
+    Gopher,
+    Gophers,
+    Telnet,
+    Tftp,
+}
+
+impl Protocol {
+    /// Returns the scheme name of the protocol as libcurl knows it.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::Http => "http",
+            Protocol::Https => "https",
+            Protocol::Ftp => "ftp",
+            Protocol::Ftps => "ftps",
+            Protocol::Sftp => "sftp",
+            Protocol::Scp => "scp",
+            Protocol::File => "file",
+            Protocol::Smtp => "smtp",
+            Protocol::Smtps => "smtps",
+            Protocol::Imap => "imap",
+            Protocol::Imaps => "imaps",
+            Protocol::Pop3 => "pop3",
+            Protocol::Pop3s => "pop3s",
+            Protocol::Ws => "ws",
+            Protocol::Wss => "wss",
+            Protocol::Mqtt => "mqtt",
+            Protocol::Ldap => "ldap",
+            Protocol::Ldaps => "ldaps",
+            Protocol::Dict => "dict",
+            Protocol::Gopher => "gopher",
+            Protocol::Gophers => "gophers",
+            Protocol::Telnet => "telnet",
+            Protocol::Tftp => "tftp",
+        }
+    }
+}
+
+/// The value of an option set through `AsyncCurl::setopt_raw`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawOption<'a> {
+    /// For options of the `CURLOPTTYPE_LONG` kind, such as `CURLOPT_TCP_FASTOPEN`.
+    Long(std::ffi::c_long),
+    /// For options of the `CURLOPTTYPE_OFF_T` kind, such as `CURLOPT_MAX_RECV_SPEED_LARGE`.
+    OffT(curl_sys::curl_off_t),
+    /// For options taking a string, which libcurl copies.
+    Str(&'a str),
+}
+
 /// A type-state struct in building the HttpClient.
 pub struct Build;
+/// A type-state struct in building the HttpClient, once the URL is set.
+pub struct BuildWithUrl;
 /// A type-state struct in building the HttpClient.
 pub struct Perform;
 
+/// The type-states in which the options of the HttpClient can be set,
+/// i.e. [`Build`] and [`BuildWithUrl`].
+pub trait BuildState: private::Sealed {}
+
+impl BuildState for Build {}
+impl BuildState for BuildWithUrl {}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for super::Build {}
+    impl Sealed for super::BuildWithUrl {}
+}
+
 /// The HTTP Client struct that wraps curl Easy2.
-pub struct AsyncCurl<C, S>
+///
+/// The request is performed through the [`Actor`] `A`, a [`CurlActor`]
+/// unless another one is given to `new`.
+pub struct AsyncCurl<C, S, A = CurlActor<C>>
 where
     C: Handler + std::fmt::Debug + Send + 'static,
 {
-    curl: CurlActor<C>,
+    curl: A,
     easy: Easy2<C>,
+    /// Declared after `easy`, so the lists outlive the Easy2 pointing to them.
+    lists: SlistOptions,
+    url: Option<String>,
+    headers: Vec<String>,
+    /// The default headers of the actor, sent unless `headers` has one of the same name.
+    default_headers: Vec<String>,
+    resume_from: Option<u64>,
+    cookie_jar: Option<CookieJar>,
+    verify_content_length: bool,
+    plan: RequestPlan,
     _state: S,
 }
 
-impl<C> AsyncCurl<C, Build>
+impl<C, A> AsyncCurl<C, Build, A>
 where
     C: Handler + std::fmt::Debug + Send + 'static,
+    A: Actor<C>,
 {
     /// Creates a new HTTP Client.
     ///
     /// The [`CurlActor`](https://docs.rs/async-curl/latest/async_curl/actor/struct.CurlActor.html) is the actor handler that can be cloned to be able to handle multiple request sender
     /// and a single consumer that is spawned in the background upon creation of this object to be able to achieve
     /// non-blocking I/O during curl perform.
-    pub fn new(curl: CurlActor<C>, collector: C) -> Self {
+    ///
+    /// Redirects are restricted to HTTP and HTTPS, see `allowed_redirect_protocols`.
+    pub fn new(curl: A, collector: C) -> Self {
+        let mut easy = curl.easy_handle(collector);
+        // libcurl before 7.85 only knows the deprecated bitmask form.
+        let restricted =
+            setopt_str(&mut easy, CURLOPT_REDIR_PROTOCOLS_STR, "http,https").or_else(|err| {
+                log::trace!("{err}");
+                setopt_long(
+                    &mut easy,
+                    curl_sys::CURLOPT_REDIR_PROTOCOLS,
+                    (curl_sys::CURLPROTO_HTTP | curl_sys::CURLPROTO_HTTPS).into(),
+                )
+            });
+        if let Err(err) = restricted {
+            log::warn!("Redirects could not be restricted to HTTP and HTTPS: {err}");
+        }
+        let default_headers = curl.default_headers();
         Self {
             curl,
-            easy: Easy2::new(collector),
+            easy,
+            lists: SlistOptions::default(),
+            url: None,
+            headers: Vec::new(),
+            default_headers,
+            resume_from: None,
+            cookie_jar: None,
+            verify_content_length: false,
+            plan: RequestPlan::default(),
             _state: Build,
         }
     }
+}
 
+impl<C, S, A> AsyncCurl<C, S, A>
+where
+    C: Handler + std::fmt::Debug + Send + 'static,
+    S: BuildState,
+{
     // =========================================================================
     // Behavior options
 
@@ -190,6 +417,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("fail_on_error", fail.to_string());
         Ok(self)
     }
 
@@ -209,12 +437,20 @@ where
     ///
     /// By default this option is not set and `perform` will not work until it
     /// is set. This option corresponds to `CURLOPT_URL`.
-    pub fn url(mut self, url: &str) -> Result<Self, Error<C>> {
+    ///
+    /// Setting the URL allows the build to be finalized.
+    pub fn url(mut self, url: &str) -> Result<AsyncCurl<C, BuildWithUrl, A>, Error<C>> {
+        self.set_url(url)?;
+        Ok(self.into_state(BuildWithUrl))
+    }
+
+    fn set_url(&mut self, url: &str) -> Result<(), Error<C>> {
         self.easy.url(url).map_err(|err| {
             log::trace!("{err}");
             Error::Curl(err)
         })?;
-        Ok(self)
+        self.url = Some(url.to_string());
+        Ok(())
     }
 
     /// Configures the port number to connect to, instead of the one specified
@@ -267,6 +503,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("proxy", url.to_string());
         Ok(self)
     }
 
@@ -291,6 +528,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("proxy_cainfo", cainfo.to_string());
         Ok(self)
     }
 
@@ -304,10 +542,12 @@ where
     /// By default this value is not set and corresponds to
     /// `CURLOPT_PROXY_CAPATH`.
     pub fn proxy_capath<P: AsRef<Path>>(mut self, path: P) -> Result<Self, Error<C>> {
+        let path = path.as_ref();
         self.easy.proxy_capath(path).map_err(|err| {
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("proxy_capath", path.display().to_string());
         Ok(self)
     }
 
@@ -444,6 +684,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("noproxy", skip.to_string());
         Ok(self)
     }
 
@@ -517,6 +758,34 @@ where
         Ok(self)
     }
 
+    /// Sets the network interface, by name, that DNS queries are sent from.
+    ///
+    /// By default this option is not set and corresponds to
+    /// `CURLOPT_DNS_INTERFACE`. Like `dns_servers`, it needs libcurl to be
+    /// linked against c-ares and fails with an error saying so otherwise.
+    pub fn dns_interface(mut self, interface: &str) -> Result<Self, Error<C>> {
+        set_dns_option(&mut self.easy, CURLOPT_DNS_INTERFACE, interface)?;
+        Ok(self)
+    }
+
+    /// Sets the local IPv4 address that DNS queries are sent from.
+    ///
+    /// By default this option is not set and corresponds to
+    /// `CURLOPT_DNS_LOCAL_IP4`. It needs libcurl to be linked against c-ares.
+    pub fn dns_local_ip4(mut self, ip: Ipv4Addr) -> Result<Self, Error<C>> {
+        set_dns_option(&mut self.easy, CURLOPT_DNS_LOCAL_IP4, &ip.to_string())?;
+        Ok(self)
+    }
+
+    /// Sets the local IPv6 address that DNS queries are sent from.
+    ///
+    /// By default this option is not set and corresponds to
+    /// `CURLOPT_DNS_LOCAL_IP6`. It needs libcurl to be linked against c-ares.
+    pub fn dns_local_ip6(mut self, ip: Ipv6Addr) -> Result<Self, Error<C>> {
+        set_dns_option(&mut self.easy, CURLOPT_DNS_LOCAL_IP6, &ip.to_string())?;
+        Ok(self)
+    }
+
     /// Sets the timeout of how long name resolves will be kept in memory.
     ///
     /// This is distinct from DNS TTL options and is entirely speculative.
@@ -531,6 +800,21 @@ where
         Ok(self)
     }
 
+    /// Shuffles the addresses a host name resolves to before they are tried,
+    /// spreading the connections of many clients over every address.
+    ///
+    /// By default this option is `false` and corresponds to
+    /// `CURLOPT_DNS_SHUFFLE_ADDRESSES`.
+    pub fn dns_shuffle_addresses(mut self, enable: bool) -> Result<Self, Error<C>> {
+        setopt_long(&mut self.easy, CURLOPT_DNS_SHUFFLE_ADDRESSES, enable.into()).map_err(
+            |err| {
+                log::trace!("{err}");
+                Error::Curl(err)
+            },
+        )?;
+        Ok(self)
+    }
+
     /// Provide the DNS-over-HTTPS URL.
     ///
     /// The parameter must be URL-encoded in the following format:
@@ -598,6 +882,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("doh_ssl_verify_peer", verify.to_string());
         Ok(self)
     }
 
@@ -631,6 +916,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("doh_ssl_verify_host", verify.to_string());
         Ok(self)
     }
 
@@ -653,6 +939,8 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan
+            .record("doh_ssl_verify_status", verify.to_string());
         Ok(self)
     }
 
@@ -776,6 +1064,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("username", user.to_string());
         Ok(self)
     }
 
@@ -806,6 +1095,70 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("http_auth", format!("{auth:?}"));
+        Ok(self)
+    }
+
+    /// Authenticates with HTTP basic authentication, setting the
+    /// credentials and restricting `http_auth` to basic.
+    pub fn basic_auth(mut self, user: &str, pass: &str) -> Result<Self, Error<C>> {
+        let mut auth = Auth::new();
+        auth.basic(true);
+        self = self.http_auth(&auth)?;
+        self.username(user)?.password(pass)
+    }
+
+    /// Authenticates with a bearer token by adding the
+    /// `Authorization: Bearer <token>` header.
+    pub fn bearer_auth(mut self, token: &str) -> Result<Self, Error<C>> {
+        self.append_header(format!("Authorization: Bearer {token}"))?;
+        Ok(self)
+    }
+
+    /// Authenticates with SPNEGO, e.g. against Kerberos, restricting
+    /// `http_auth` to negotiate. The credentials come from the GSS-API
+    /// library, so no username or password is needed.
+    pub fn negotiate_auth(self) -> Result<Self, Error<C>> {
+        let mut auth = Auth::new();
+        auth.gssnegotiate(true);
+        self.http_auth(&auth)
+    }
+
+    /// Sets the service name used for SPNEGO and the GSS-API and SASL
+    /// mechanisms, e.g. for a host whose principal is not `HTTP/<host>`.
+    ///
+    /// By default this is `HTTP` for HTTP and the protocol name otherwise,
+    /// this corresponds to `CURLOPT_SERVICE_NAME`.
+    pub fn service_name(mut self, name: &str) -> Result<Self, Error<C>> {
+        setopt_str(&mut self.easy, CURLOPT_SERVICE_NAME, name).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        Ok(self)
+    }
+
+    /// Sets the OAuth 2.0 access token used for the `XOAUTH2` and
+    /// `OAUTHBEARER` SASL mechanisms of IMAP, POP3 and SMTP, together with
+    /// the `username`.
+    ///
+    /// By default this value is not set and corresponds to `CURLOPT_XOAUTH2_BEARER`.
+    pub fn xoauth2_bearer(mut self, token: &str) -> Result<Self, Error<C>> {
+        setopt_str(&mut self.easy, CURLOPT_XOAUTH2_BEARER, token).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        Ok(self)
+    }
+
+    /// Sets the protocol specific login options of IMAP, POP3 and SMTP,
+    /// e.g. `"AUTH=XOAUTH2"` to pick the SASL mechanism.
+    ///
+    /// By default this value is not set and corresponds to `CURLOPT_LOGIN_OPTIONS`.
+    pub fn login_options(mut self, options: &str) -> Result<Self, Error<C>> {
+        setopt_str(&mut self.easy, CURLOPT_LOGIN_OPTIONS, options).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
         Ok(self)
     }
 
@@ -879,6 +1232,19 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("proxy_auth", format!("{auth:?}"));
+        Ok(self)
+    }
+
+    /// Sets the service name used for SPNEGO and the GSS-API
+    /// authentication with the proxy.
+    ///
+    /// By default this is `HTTP`, this corresponds to `CURLOPT_PROXY_SERVICE_NAME`.
+    pub fn proxy_service_name(mut self, name: &str) -> Result<Self, Error<C>> {
+        setopt_str(&mut self.easy, CURLOPT_PROXY_SERVICE_NAME, name).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
         Ok(self)
     }
 
@@ -923,6 +1289,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("accept_encoding", encoding.to_string());
         Ok(self)
     }
 
@@ -950,6 +1317,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("follow_location", enable.to_string());
         Ok(self)
     }
 
@@ -965,6 +1333,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("unrestricted_auth", enable.to_string());
         Ok(self)
     }
 
@@ -979,9 +1348,57 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("max_redirections", max.to_string());
         Ok(self)
     }
 
+    /// Restricts the protocols a transfer may use, given as a comma-separated
+    /// list of scheme names such as `"http,https"`. A URL of any other scheme
+    /// fails with `CURLE_UNSUPPORTED_PROTOCOL`.
+    ///
+    /// By default every protocol libcurl supports is allowed, this corresponds
+    /// to `CURLOPT_PROTOCOLS_STR`.
+    pub fn protocols(mut self, protocols: &str) -> Result<Self, Error<C>> {
+        setopt_str(&mut self.easy, CURLOPT_PROTOCOLS_STR, protocols).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.plan.record("protocols", protocols.to_string());
+        Ok(self)
+    }
+
+    /// Restricts the protocols `follow_location` may redirect to, given as a
+    /// comma-separated list of scheme names such as `"http,https"`.
+    ///
+    /// By default only HTTP and HTTPS are allowed, this corresponds
+    /// to `CURLOPT_REDIR_PROTOCOLS_STR`.
+    pub fn redir_protocols(mut self, protocols: &str) -> Result<Self, Error<C>> {
+        setopt_str(&mut self.easy, CURLOPT_REDIR_PROTOCOLS_STR, protocols).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.plan.record("redir_protocols", protocols.to_string());
+        Ok(self)
+    }
+
+    /// Restricts the protocols a transfer may use, e.g. to HTTP and HTTPS
+    /// when fetching URLs supplied by users. A URL of any other scheme fails
+    /// with `CURLE_UNSUPPORTED_PROTOCOL`.
+    ///
+    /// This is the typed form of `protocols`.
+    pub fn allowed_protocols(self, protocols: &[Protocol]) -> Result<Self, Error<C>> {
+        self.protocols(&protocol_list(protocols))
+    }
+
+    /// Restricts the protocols `follow_location` may redirect to, so a
+    /// redirect cannot reach e.g. `file://` or `gopher://` URLs.
+    ///
+    /// By default only HTTP and HTTPS are allowed. This is the typed form
+    /// of `redir_protocols`.
+    pub fn allowed_redirect_protocols(self, protocols: &[Protocol]) -> Result<Self, Error<C>> {
+        self.redir_protocols(&protocol_list(protocols))
+    }
+
     /// Make an HTTP PUT request.
     ///
     /// By default this option is `false` and corresponds to `CURLOPT_PUT`.
@@ -990,6 +1407,9 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        if enable {
+            self.plan.method = "PUT".to_string();
+        }
         Ok(self)
     }
 
@@ -1007,6 +1427,9 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        if enable {
+            self.plan.method = "POST".to_string();
+        }
         Ok(self)
     }
 
@@ -1022,6 +1445,56 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record_body(data);
+        Ok(self)
+    }
+
+    /// Serializes `value` as JSON and sends it as the body of a POST,
+    /// with the `Content-Type: application/json` header.
+    ///
+    /// A value that cannot be serialized is reported as [`Error::Encode`].
+    #[cfg(feature = "serde")]
+    pub fn json_body<T>(mut self, value: &T) -> Result<Self, Error<C>>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        let body = serde_json::to_vec(value).map_err(|err| {
+            log::trace!("{err}");
+            Error::Encode(err.to_string())
+        })?;
+        self.easy.post_fields_copy(&body).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.plan.record_body(&body);
+        self.append_header("Content-Type: application/json".to_string())?;
+        Ok(self)
+    }
+
+    /// Percent-encodes `fields` into an `application/x-www-form-urlencoded`
+    /// body and sends it as a POST, with the matching `Content-Type` header.
+    pub fn form(mut self, fields: &[(&str, &str)]) -> Result<Self, Error<C>> {
+        let body = fields
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}={}",
+                    self.easy.url_encode(name.as_bytes()),
+                    self.easy.url_encode(value.as_bytes())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        self.easy.post(true).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.easy.post_fields_copy(body.as_bytes()).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.plan.record_body(body.as_bytes());
+        self.append_header("Content-Type: application/x-www-form-urlencoded".to_string())?;
         Ok(self)
     }
 
@@ -1052,9 +1525,17 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.method = "POST".to_string();
         Ok(self)
     }
 
+    /// Sends the parts of `multipart` as a `multipart/form-data` POST request.
+    ///
+    /// This is a shortcut for `httppost` with a [`Multipart`] built form.
+    pub fn multipart(self, multipart: Multipart) -> Result<Self, Error<C>> {
+        self.httppost(multipart.into_form())
+    }
+
     /// Sets the HTTP referer header
     ///
     /// By default this option is not set and corresponds to `CURLOPT_REFERER`.
@@ -1063,6 +1544,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("referer", referer.to_string());
         Ok(self)
     }
 
@@ -1075,6 +1557,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("useragent", useragent.to_string());
         Ok(self)
     }
 
@@ -1091,10 +1574,42 @@ where
     /// By default this option is not set and corresponds to
     /// `CURLOPT_HTTPHEADER`
     pub fn http_headers(mut self, list: List) -> Result<Self, Error<C>> {
-        self.easy.http_headers(list).map_err(|err| {
-            log::trace!("{err}");
-            Error::Curl(err)
-        })?;
+        self.headers = list
+            .iter()
+            .map(|header| String::from_utf8_lossy(header).into_owned())
+            .collect();
+        self.send_headers()?;
+        Ok(self)
+    }
+
+    /// Adds a single `name: value` header to this HTTP request, keeping
+    /// the headers added before, without building a [`List`] by hand.
+    pub fn header(mut self, name: &str, value: &str) -> Result<Self, Error<C>> {
+        self.append_header(format!("{name}: {value}"))?;
+        Ok(self)
+    }
+
+    /// Adds every `(name, value)` pair as a header like `header`.
+    pub fn headers<I>(mut self, headers: I) -> Result<Self, Error<C>>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (name, value) in headers {
+            self.append_header(format!("{name}: {value}"))?;
+        }
+        Ok(self)
+    }
+
+    /// Adds the headers of an [`http::HeaderMap`] like `header`, sending
+    /// every value of a header with several values on its own line.
+    ///
+    /// Values that are not valid UTF-8 are sent with the invalid
+    /// sequences replaced.
+    pub fn headers_from_map(mut self, headers: &HeaderMap) -> Result<Self, Error<C>> {
+        for (name, value) in headers {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            self.append_header(format!("{}: {}", name.as_str(), value))?;
+        }
         Ok(self)
     }
 
@@ -1123,6 +1638,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("cookie", cookie.to_string());
         Ok(self)
     }
 
@@ -1225,6 +1741,16 @@ where
         Ok(self)
     }
 
+    /// Keeps the cookies of the request in `jar`, so they are sent by later
+    /// requests attached to the same jar, without a cookie file.
+    ///
+    /// This enables the cookie engine and replaces the cookies held by the
+    /// handle with the ones in the jar when the request is performed.
+    pub fn cookie_store(mut self, jar: &CookieJar) -> Self {
+        self.cookie_jar = Some(jar.clone());
+        self
+    }
+
     /// Ask for a HTTP GET request.
     ///
     /// By default this option is `false` and corresponds to `CURLOPT_HTTPGET`.
@@ -1233,6 +1759,9 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        if enable {
+            self.plan.method = "GET".to_string();
+        }
         Ok(self)
     }
 
@@ -1255,6 +1784,16 @@ where
         Ok(self)
     }
 
+    /// Makes `perform` fail with [`Error::TruncatedBody`] when fewer bytes
+    /// of body were received than the `Content-Length` announced, instead of
+    /// returning the short body as a success.
+    ///
+    /// By default this option is `false`.
+    pub fn verify_content_length(mut self, verify: bool) -> Self {
+        self.verify_content_length = verify;
+        self
+    }
+
     /// Enable or disable HTTP content decoding.
     ///
     /// By default this option is `true` and corresponds to
@@ -1264,6 +1803,8 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan
+            .record("http_content_decoding", enable.to_string());
         Ok(self)
     }
 
@@ -1330,6 +1871,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("range", range.to_string());
         Ok(self)
     }
 
@@ -1344,6 +1886,8 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.resume_from = Some(from);
+        self.plan.record("resume_from", from.to_string());
         Ok(self)
     }
 
@@ -1360,6 +1904,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.method = request.to_string();
         Ok(self)
     }
 
@@ -1390,6 +1935,41 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        if enable {
+            self.plan.method = "HEAD".to_string();
+        } else if self.plan.method == "HEAD" {
+            self.plan.method = "GET".to_string();
+        }
+        Ok(self)
+    }
+
+    /// Sets the HTTP method of the request, replacing whatever `get`, `post`,
+    /// `nobody` or `custom_request` set before.
+    ///
+    /// `Get` and `Head` drop any body set for the request. `Post` sends the
+    /// body set with `post_fields_copy` or from the read callback, and so do
+    /// the other methods, which are sent through `CURLOPT_CUSTOMREQUEST`.
+    pub fn method(mut self, method: Method) -> Result<Self, Error<C>> {
+        let set = |easy: &mut Easy2<C>| -> Result<(), curl::Error> {
+            match &method {
+                Method::Get => easy.get(true)?,
+                Method::Head => {
+                    easy.get(true)?;
+                    easy.nobody(true)?;
+                }
+                Method::Post => easy.post(true)?,
+                Method::Put | Method::Delete | Method::Patch | Method::Custom(_) => {
+                    easy.nobody(false)?;
+                    return easy.custom_request(method.as_str());
+                }
+            }
+            clear_custom_request(easy)
+        };
+        set(&mut self.easy).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.plan.method = method.as_str().to_string();
         Ok(self)
     }
 
@@ -1417,6 +1997,10 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        if enable {
+            self.plan.method = "PUT".to_string();
+        }
+        self.plan.record("upload", enable.to_string());
         Ok(self)
     }
 
@@ -1461,6 +2045,113 @@ where
         Ok(self)
     }
 
+    /// Sets the FTP or SFTP commands to send to the server before the
+    /// transfer, e.g. `"SITE CHMOD 644 file"`. A command prefixed with `*`
+    /// is allowed to fail.
+    ///
+    /// By default no commands are sent, this corresponds to `CURLOPT_QUOTE`.
+    pub fn quote(mut self, list: List) -> Result<Self, Error<C>> {
+        self.lists
+            .set(&mut self.easy, curl_sys::CURLOPT_QUOTE, &list)
+            .map_err(|err| {
+                log::trace!("{err}");
+                Error::Curl(err)
+            })?;
+        self.plan.record("quote", list_value(&list));
+        Ok(self)
+    }
+
+    /// Sets the FTP commands to send to the server after the transfer type
+    /// was set, right before the transfer.
+    ///
+    /// By default no commands are sent, this corresponds to `CURLOPT_PREQUOTE`.
+    pub fn prequote(mut self, list: List) -> Result<Self, Error<C>> {
+        self.lists
+            .set(&mut self.easy, curl_sys::CURLOPT_PREQUOTE, &list)
+            .map_err(|err| {
+                log::trace!("{err}");
+                Error::Curl(err)
+            })?;
+        self.plan.record("prequote", list_value(&list));
+        Ok(self)
+    }
+
+    /// Sets the FTP or SFTP commands to send to the server after the transfer.
+    ///
+    /// By default no commands are sent, this corresponds to `CURLOPT_POSTQUOTE`.
+    pub fn postquote(mut self, list: List) -> Result<Self, Error<C>> {
+        self.lists
+            .set(&mut self.easy, curl_sys::CURLOPT_POSTQUOTE, &list)
+            .map_err(|err| {
+                log::trace!("{err}");
+                Error::Curl(err)
+            })?;
+        self.plan.record("postquote", list_value(&list));
+        Ok(self)
+    }
+
+    /// Sets the envelope sender of an email sent over SMTP, whose message is
+    /// given through `upload` and the read callback.
+    ///
+    /// By default this value is not set and corresponds to `CURLOPT_MAIL_FROM`.
+    pub fn mail_from(mut self, from: &str) -> Result<Self, Error<C>> {
+        setopt_str(&mut self.easy, curl_sys::CURLOPT_MAIL_FROM, from).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.plan.record("mail_from", from.to_string());
+        Ok(self)
+    }
+
+    /// Sets the envelope recipients of an email sent over SMTP, one address
+    /// per item such as `"<alice@example.com>"`.
+    ///
+    /// By default this value is not set and corresponds to `CURLOPT_MAIL_RCPT`.
+    pub fn mail_rcpt(mut self, list: List) -> Result<Self, Error<C>> {
+        self.lists
+            .set(&mut self.easy, curl_sys::CURLOPT_MAIL_RCPT, &list)
+            .map_err(|err| {
+                log::trace!("{err}");
+                Error::Curl(err)
+            })?;
+        self.plan.record("mail_rcpt", list_value(&list));
+        Ok(self)
+    }
+
+    /// Sets the permissions of the files created on the server by an upload,
+    /// for the protocols that support them such as SFTP and SCP.
+    ///
+    /// By default this is `0o644` and corresponds to `CURLOPT_NEW_FILE_PERMS`.
+    pub fn new_file_perms(mut self, perms: u32) -> Result<Self, Error<C>> {
+        setopt_long(
+            &mut self.easy,
+            curl_sys::CURLOPT_NEW_FILE_PERMS,
+            perms as std::ffi::c_long,
+        )
+        .map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        Ok(self)
+    }
+
+    /// Sets the permissions of the directories created on the server when
+    /// `CURLOPT_FTP_CREATE_MISSING_DIRS` is set, for SFTP.
+    ///
+    /// By default this is `0o755` and corresponds to `CURLOPT_NEW_DIRECTORY_PERMS`.
+    pub fn new_directory_perms(mut self, perms: u32) -> Result<Self, Error<C>> {
+        setopt_long(
+            &mut self.easy,
+            curl_sys::CURLOPT_NEW_DIRECTORY_PERMS,
+            perms as std::ffi::c_long,
+        )
+        .map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        Ok(self)
+    }
+
     // =========================================================================
     // Connection Options
 
@@ -1490,6 +2181,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("timeout", format!("{timeout:?}"));
         Ok(self)
     }
 
@@ -1641,6 +2333,23 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("connect_timeout", format!("{timeout:?}"));
+        Ok(self)
+    }
+
+    /// Sets how long to wait for a connection over the preferred IP version,
+    /// usually IPv6, before racing a connection over the other one as well.
+    /// Lowering it cuts the connect time on networks that advertise a broken
+    /// IPv6 route.
+    ///
+    /// By default this value is 200ms and corresponds to
+    /// `CURLOPT_HAPPY_EYEBALLS_TIMEOUT_MS`.
+    pub fn happy_eyeballs_timeout(mut self, timeout: Duration) -> Result<Self, Error<C>> {
+        let millis = timeout.as_millis().min(std::ffi::c_long::MAX as u128) as std::ffi::c_long;
+        setopt_long(&mut self.easy, CURLOPT_HAPPY_EYEBALLS_TIMEOUT_MS, millis).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
         Ok(self)
     }
 
@@ -1902,6 +2611,82 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("http_version", format!("{version:?}"));
+        Ok(self)
+    }
+
+    /// Asks for HTTP/3, falling back to an older version if the server
+    /// does not support it.
+    ///
+    /// Fails with `CURLE_NOT_BUILT_IN` and a description of the linked
+    /// libcurl if it was built without HTTP/3, see `version_info`.
+    pub fn try_http3(self) -> Result<Self, Error<C>> {
+        self.set_http3(curl_sys::CURL_HTTP_VERSION_3 as std::ffi::c_long)
+    }
+
+    /// Asks for HTTP/3 like `try_http3`, but fails the transfer instead of
+    /// falling back to an older version.
+    pub fn try_http3_only(self) -> Result<Self, Error<C>> {
+        self.set_http3(CURL_HTTP_VERSION_3ONLY)
+    }
+
+    fn set_http3(self, version: std::ffi::c_long) -> Result<Self, Error<C>> {
+        let info = version_info();
+        let result = if info.http3 {
+            check_code(unsafe {
+                curl_sys::curl_easy_setopt(self.easy.raw(), curl_sys::CURLOPT_HTTP_VERSION, version)
+            })
+        } else {
+            let mut err = curl::Error::new(CURLE_NOT_BUILT_IN);
+            err.set_extra(format!(
+                "libcurl {} was built without HTTP/3 support",
+                info.version
+            ));
+            Err(err)
+        };
+        result.map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        Ok(self)
+    }
+
+    /// Enables the Alt-Svc cache and keeps it in the file at `path`, so
+    /// alternative services such as HTTP/3 endpoints announced by a host are
+    /// used by later transfers, and by later runs, without rediscovery.
+    /// Services for HTTP/1.1, HTTP/2 and HTTP/3 are accepted.
+    ///
+    /// By default this option is not set and corresponds to `CURLOPT_ALTSVC`
+    /// and `CURLOPT_ALTSVC_CTRL`.
+    pub fn alt_svc_cache<P: AsRef<Path>>(mut self, path: P) -> Result<Self, Error<C>> {
+        let result = path_str(path.as_ref())
+            .and_then(|path| {
+                setopt_long(&mut self.easy, CURLOPT_ALTSVC_CTRL, CURLALTSVC_ALL).map(|_| path)
+            })
+            .and_then(|path| setopt_str(&mut self.easy, CURLOPT_ALTSVC, path));
+        result.map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        Ok(self)
+    }
+
+    /// Enables HSTS and keeps the hosts that asked for it in the file at
+    /// `path`, so later transfers, and later runs, to those hosts are
+    /// upgraded to HTTPS before anything is sent in the clear.
+    ///
+    /// By default this option is not set and corresponds to `CURLOPT_HSTS`
+    /// and `CURLOPT_HSTS_CTRL`.
+    pub fn hsts_cache<P: AsRef<Path>>(mut self, path: P) -> Result<Self, Error<C>> {
+        let result = path_str(path.as_ref())
+            .and_then(|path| {
+                setopt_long(&mut self.easy, CURLOPT_HSTS_CTRL, CURLHSTS_ENABLE).map(|_| path)
+            })
+            .and_then(|path| setopt_str(&mut self.easy, CURLOPT_HSTS, path));
+        result.map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
         Ok(self)
     }
 
@@ -1978,6 +2763,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("ssl_verify_host", verify.to_string());
         Ok(self)
     }
 
@@ -1993,6 +2779,8 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan
+            .record("proxy_ssl_verify_host", verify.to_string());
         Ok(self)
     }
 
@@ -2008,6 +2796,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("ssl_verify_peer", verify.to_string());
         Ok(self)
     }
 
@@ -2023,20 +2812,30 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan
+            .record("proxy_ssl_verify_peer", verify.to_string());
         Ok(self)
     }
 
-    // /// Verify the certificate's status.
-    // ///
-    // /// This option determines whether libcurl verifies the status of the server
-    // /// cert using the "Certificate Status Request" TLS extension (aka. OCSP
-    // /// stapling).
-    // ///
-    // /// By default this option is set to `false` and corresponds to
-    // /// `CURLOPT_SSL_VERIFYSTATUS`.
-    // pub fn ssl_verify_status(mut self, verify: bool) -> Result<Self, Error<C>> {
-    //     self.setopt_long(curl_sys::CURLOPT_SSL_VERIFYSTATUS, verify as c_long)
-    // }
+    /// Verify the certificate's status.
+    ///
+    /// This option determines whether libcurl verifies the status of the server
+    /// cert using the "Certificate Status Request" TLS extension (aka. OCSP
+    /// stapling).
+    ///
+    /// Note that if this option is enabled but the server does not support the
+    /// TLS extension, the verification will fail.
+    ///
+    /// By default this option is set to `false` and corresponds to
+    /// `CURLOPT_SSL_VERIFYSTATUS`.
+    pub fn ssl_verify_status(mut self, verify: bool) -> Result<Self, Error<C>> {
+        setopt_long(&mut self.easy, CURLOPT_SSL_VERIFYSTATUS, verify.into()).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.plan.record("ssl_verify_status", verify.to_string());
+        Ok(self)
+    }
 
     /// Specify the path to Certificate Authority (CA) bundle
     ///
@@ -2052,10 +2851,12 @@ where
     /// By default this option is the system defaults, and corresponds to
     /// `CURLOPT_CAINFO`.
     pub fn cainfo<P: AsRef<Path>>(mut self, path: P) -> Result<Self, Error<C>> {
+        let path = path.as_ref();
         self.easy.cainfo(path).map_err(|err| {
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("cainfo", path.display().to_string());
         Ok(self)
     }
 
@@ -2146,10 +2947,12 @@ where
     ///
     /// By default this option is not set and corresponds to `CURLOPT_CAPATH`.
     pub fn capath<P: AsRef<Path>>(mut self, path: P) -> Result<Self, Error<C>> {
+        let path = path.as_ref();
         self.easy.capath(path).map_err(|err| {
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("capath", path.display().to_string());
         Ok(self)
     }
 
@@ -2242,6 +3045,7 @@ where
             log::trace!("{err}");
             Error::Curl(err)
         })?;
+        self.plan.record("pinned_public_key", pubkey.to_string());
         Ok(self)
     }
 
@@ -2476,24 +3280,1182 @@ where
         Ok(self)
     }
 
-    /// Finalizes your build to proceed in performing CURL operation.
-    pub fn finalize(self) -> AsyncCurl<C, Perform> {
-        AsyncCurl::<C, Perform> {
+    /// Gives mutable access to the underlying [`Easy2<C>`](https://docs.rs/curl/latest/curl/easy/struct.Easy2.html)
+    /// to set options that this builder does not wrap yet.
+    ///
+    /// Options set here are not seen by the builder, e.g. a URL set
+    /// through it does not allow the build to be finalized, and headers
+    /// set through it are replaced by `header` and similar methods.
+    pub fn easy_mut(&mut self) -> &mut Easy2<C> {
+        &mut self.easy
+    }
+
+    /// Sets an option this builder does not wrap through `curl_easy_setopt`,
+    /// such as `CURLOPT_TCP_FASTOPEN`.
+    ///
+    /// Options this builder keeps track of, such as `CURLOPT_URL`,
+    /// `CURLOPT_USERAGENT` or `CURLOPT_RESUME_FROM`, are handed to their
+    /// typed setter, so the request plan and `perform` see them. The URL
+    /// still has to be set with `url` to finalize the build. Other options
+    /// reach libcurl the same way the typed setters of the [`Easy2`] set them.
+    ///
+    /// A value whose kind does not match the kind encoded in `option`, or
+    /// that the typed setter of the option cannot take, fails with
+    /// `CURLE_BAD_FUNCTION_ARGUMENT` before it reaches libcurl. So does a
+    /// [`RawOption::Str`] for the pointer options libcurl does not copy a
+    /// string from, such as `CURLOPT_POSTFIELDS`, `CURLOPT_HTTPHEADER` or
+    /// `CURLOPT_WRITEDATA`, as the pointer would outlive the string or be
+    /// taken for another type.
+    ///
+    /// # Safety
+    ///
+    /// A [`RawOption::Str`] must only be given for options that take a
+    /// string, as libcurl cannot tell strings from other pointers. Pointer
+    /// options that are not strings and not refused as above, such as ones
+    /// added after libcurl 8.6, must not be given a string either.
+    pub unsafe fn setopt_raw(
+        mut self,
+        option: curl_sys::CURLoption,
+        value: RawOption<'_>,
+    ) -> Result<Self, Error<C>> {
+        let value = match (option, value) {
+            (curl_sys::CURLOPT_URL, RawOption::Str(url)) => {
+                self.set_url(url)?;
+                return Ok(self);
+            }
+            (curl_sys::CURLOPT_PROXY, RawOption::Str(url)) => return self.proxy(url),
+            (curl_sys::CURLOPT_PROXY_CAINFO, RawOption::Str(cainfo)) => {
+                return self.proxy_cainfo(cainfo)
+            }
+            (curl_sys::CURLOPT_PROXY_CAPATH, RawOption::Str(path)) => {
+                return self.proxy_capath(path)
+            }
+            (curl_sys::CURLOPT_NOPROXY, RawOption::Str(skip)) => return self.noproxy(skip),
+            (curl_sys::CURLOPT_USERNAME, RawOption::Str(user)) => return self.username(user),
+            (curl_sys::CURLOPT_ACCEPT_ENCODING, RawOption::Str(encoding)) => {
+                return self.accept_encoding(encoding)
+            }
+            (CURLOPT_PROTOCOLS_STR, RawOption::Str(protocols)) => return self.protocols(protocols),
+            (CURLOPT_REDIR_PROTOCOLS_STR, RawOption::Str(protocols)) => {
+                return self.redir_protocols(protocols)
+            }
+            (curl_sys::CURLOPT_COPYPOSTFIELDS, RawOption::Str(data)) => {
+                return self.post_fields_copy(data.as_bytes())
+            }
+            (curl_sys::CURLOPT_REFERER, RawOption::Str(referer)) => return self.referer(referer),
+            (curl_sys::CURLOPT_USERAGENT, RawOption::Str(useragent)) => {
+                return self.useragent(useragent)
+            }
+            (curl_sys::CURLOPT_COOKIE, RawOption::Str(cookie)) => return self.cookie(cookie),
+            (curl_sys::CURLOPT_RANGE, RawOption::Str(range)) => return self.range(range),
+            (curl_sys::CURLOPT_MAIL_FROM, RawOption::Str(from)) => return self.mail_from(from),
+            (curl_sys::CURLOPT_FAILONERROR, RawOption::Long(fail)) => {
+                return self.fail_on_error(fail != 0)
+            }
+            (curl_sys::CURLOPT_DOH_SSL_VERIFYPEER, RawOption::Long(verify)) => {
+                return self.doh_ssl_verify_peer(verify != 0)
+            }
+            (curl_sys::CURLOPT_DOH_SSL_VERIFYHOST, RawOption::Long(verify)) => {
+                return self.doh_ssl_verify_host(verify != 0)
+            }
+            (curl_sys::CURLOPT_HTTPAUTH, RawOption::Long(bits)) => {
+                return self.http_auth(&raw_auth(option, bits)?)
+            }
+            (curl_sys::CURLOPT_PROXYAUTH, RawOption::Long(bits)) => {
+                return self.proxy_auth(&raw_auth(option, bits)?)
+            }
+            (curl_sys::CURLOPT_FOLLOWLOCATION, RawOption::Long(enable)) => {
+                return self.follow_location(enable != 0)
+            }
+            (curl_sys::CURLOPT_UNRESTRICTED_AUTH, RawOption::Long(enable)) => {
+                return self.unrestricted_auth(enable != 0)
+            }
+            (curl_sys::CURLOPT_MAXREDIRS, RawOption::Long(max)) => {
+                return self.max_redirections(raw_value(option, max)?)
+            }
+            (curl_sys::CURLOPT_UPLOAD, RawOption::Long(enable)) => return self.upload(enable != 0),
+            (curl_sys::CURLOPT_RESUME_FROM, RawOption::Long(from)) => {
+                return self.resume_from(raw_value(option, from)?)
+            }
+            (curl_sys::CURLOPT_RESUME_FROM_LARGE, RawOption::OffT(from)) => {
+                return self.resume_from(raw_value(option, from)?)
+            }
+            (curl_sys::CURLOPT_TIMEOUT, RawOption::Long(secs)) => {
+                return self.timeout(Duration::from_secs(raw_value(option, secs)?))
+            }
+            (curl_sys::CURLOPT_TIMEOUT_MS, RawOption::Long(millis)) => {
+                return self.timeout(Duration::from_millis(raw_value(option, millis)?))
+            }
+            (curl_sys::CURLOPT_CONNECTTIMEOUT, RawOption::Long(secs)) => {
+                return self.connect_timeout(Duration::from_secs(raw_value(option, secs)?))
+            }
+            (curl_sys::CURLOPT_CONNECTTIMEOUT_MS, RawOption::Long(millis)) => {
+                return self.connect_timeout(Duration::from_millis(raw_value(option, millis)?))
+            }
+            (curl_sys::CURLOPT_HTTP_VERSION, RawOption::Long(CURL_HTTP_VERSION_3ONLY)) => {
+                return self.try_http3_only()
+            }
+            (curl_sys::CURLOPT_HTTP_VERSION, RawOption::Long(version)) => {
+                let version = match raw_value(option, version)? {
+                    curl_sys::CURL_HTTP_VERSION_NONE => HttpVersion::Any,
+                    curl_sys::CURL_HTTP_VERSION_1_0 => HttpVersion::V10,
+                    curl_sys::CURL_HTTP_VERSION_1_1 => HttpVersion::V11,
+                    curl_sys::CURL_HTTP_VERSION_2_0 => HttpVersion::V2,
+                    curl_sys::CURL_HTTP_VERSION_2TLS => HttpVersion::V2TLS,
+                    curl_sys::CURL_HTTP_VERSION_2_PRIOR_KNOWLEDGE => HttpVersion::V2PriorKnowledge,
+                    curl_sys::CURL_HTTP_VERSION_3 => return self.try_http3(),
+                    _ => return Err(Error::Curl(raw_value_error(option, version))),
+                };
+                return self.http_version(version);
+            }
+            (option, RawOption::Str(_)) if NOT_STRING_OPTIONS.contains(&option) => {
+                let mut err = curl::Error::new(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT);
+                err.set_extra(format!(
+                    "option {option} does not take a string libcurl copies"
+                ));
+                log::trace!("{err}");
+                return Err(Error::Curl(err));
+            }
+            (_, value) => value,
+        };
+        let result = match value {
+            RawOption::Long(value) if option < curl_sys::CURLOPTTYPE_OBJECTPOINT => {
+                check_code(curl_sys::curl_easy_setopt(self.easy.raw(), option, value))
+            }
+            RawOption::OffT(value)
+                if (curl_sys::CURLOPTTYPE_OFF_T..curl_sys::CURLOPTTYPE_BLOB).contains(&option) =>
+            {
+                check_code(curl_sys::curl_easy_setopt(self.easy.raw(), option, value))
+            }
+            RawOption::Str(value)
+                if (curl_sys::CURLOPTTYPE_OBJECTPOINT..curl_sys::CURLOPTTYPE_FUNCTIONPOINT)
+                    .contains(&option) =>
+            {
+                setopt_str(&mut self.easy, option, value)
+            }
+            _ => Err(curl::Error::new(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT)),
+        };
+        result.map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        Ok(self)
+    }
+
+    fn into_state<T>(self, state: T) -> AsyncCurl<C, T, A> {
+        AsyncCurl::<C, T, A> {
             curl: self.curl,
             easy: self.easy,
-            _state: Perform,
+            lists: self.lists,
+            url: self.url,
+            headers: self.headers,
+            default_headers: self.default_headers,
+            resume_from: self.resume_from,
+            cookie_jar: self.cookie_jar,
+            verify_content_length: self.verify_content_length,
+            plan: self.plan,
+            _state: state,
         }
     }
 }
 
-impl<C> AsyncCurl<C, Perform>
+impl<C, S, A> AsyncCurl<C, S, A>
+where
+    C: Handler + std::fmt::Debug + Send + 'static,
+{
+    /// Adds a header line to the headers set so far and hands
+    /// the whole set to libcurl again.
+    fn append_header(&mut self, header: String) -> Result<(), Error<C>> {
+        self.headers.push(header);
+        self.send_headers()
+    }
+
+    /// Hands the headers set so far to libcurl, after the default headers
+    /// of the actor they do not replace.
+    fn send_headers(&mut self) -> Result<(), Error<C>> {
+        let defaults = self.default_headers.iter().filter(|default| {
+            !self
+                .headers
+                .iter()
+                .any(|header| header_name(header).eq_ignore_ascii_case(header_name(default)))
+        });
+        let mut list = List::new();
+        for header in defaults.chain(&self.headers) {
+            list.append(header).map_err(|err| {
+                log::trace!("{err}");
+                Error::Curl(err)
+            })?;
+        }
+        self.easy.http_headers(list).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })
+    }
+}
+
+impl<C, A> AsyncCurl<C, BuildWithUrl, A>
+where
+    C: Handler + std::fmt::Debug + Send + 'static,
+{
+    /// Appends query parameters to the URL set by `url`, percent-encoding
+    /// every key and value. Parameters already in the URL are kept and a
+    /// fragment stays at the end.
+    pub fn query(mut self, params: &[(&str, &str)]) -> Result<Self, Error<C>> {
+        let url = self.url.take().unwrap_or_default();
+        let (base, fragment) = match url.find('#') {
+            Some(index) => url.split_at(index),
+            None => (url.as_str(), ""),
+        };
+        let mut query = base.to_string();
+        for (key, value) in params {
+            if !query.contains('?') {
+                query.push('?');
+            } else if !query.ends_with('?') && !query.ends_with('&') {
+                query.push('&');
+            }
+            query.push_str(&self.easy.url_encode(key.as_bytes()));
+            query.push('=');
+            query.push_str(&self.easy.url_encode(value.as_bytes()));
+        }
+        query.push_str(fragment);
+        self.url(&query)
+    }
+
+    /// Applies the proxy that the standard environment variables choose for
+    /// the scheme of the URL: `https_proxy` for HTTPS, `http_proxy` for HTTP
+    /// and so on, falling back to `all_proxy`, with the hosts of `no_proxy`
+    /// going direct. Upper-case names are read as well, except for
+    /// `HTTP_PROXY`, which libcurl ignores too as CGI programs cannot trust it.
+    ///
+    /// Only variables that are set override `proxy` and `noproxy` calls made
+    /// before, while calls made afterwards override the environment.
+    pub fn proxy_from_env(self) -> Result<Self, Error<C>> {
+        self.proxy_from(|name| std::env::var(name).ok())
+    }
+
+    /// Applies the proxy settings as `proxy_from_env` does, reading the
+    /// variables through `lookup`.
+    pub(crate) fn proxy_from<F>(mut self, lookup: F) -> Result<Self, Error<C>>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let scheme = self
+            .url
+            .as_deref()
+            .and_then(|url| url.split_once("://"))
+            .map_or_else(
+                || "http".to_string(),
+                |(scheme, _)| scheme.to_ascii_lowercase(),
+            );
+        let var = |name: &str| {
+            let upper = name.to_ascii_uppercase();
+            lookup(name)
+                .or_else(|| (upper != "HTTP_PROXY").then(|| lookup(&upper)).flatten())
+                .filter(|value| !value.is_empty())
+        };
+        if let Some(proxy) = var(&format!("{scheme}_proxy")).or_else(|| var("all_proxy")) {
+            self = self.proxy(&proxy)?;
+        }
+        if let Some(noproxy) = var("no_proxy") {
+            self = self.noproxy(&noproxy)?;
+        }
+        Ok(self)
+    }
+
+    /// Finalizes your build to proceed in performing CURL operation.
+    ///
+    /// This is only available once the URL has been set with `url`.
+    pub fn finalize(self) -> AsyncCurl<C, Perform, A> {
+        self.into_state(Perform)
+    }
+}
+
+impl<C, A> AsyncCurl<C, Perform, A>
 where
     C: Handler + std::fmt::Debug + Send,
+    A: Actor<C>,
 {
+    /// Wraps an `Easy2` that was configured directly, skipping the build
+    /// stage, so existing code can perform it through the [`Actor`].
+    pub fn from_easy(curl: A, easy: Easy2<C>) -> Self {
+        Self {
+            curl,
+            easy,
+            lists: SlistOptions::default(),
+            url: None,
+            headers: Vec::new(),
+            default_headers: Vec::new(),
+            resume_from: None,
+            cookie_jar: None,
+            verify_content_length: false,
+            plan: RequestPlan::default(),
+            _state: Perform,
+        }
+    }
+
     /// This will send the request asynchronously,
     /// and return the underlying [`Easy2<C>`](https://docs.rs/curl/latest/curl/easy/struct.Easy2.html) useful if you
     /// want to decide how to transform the response yourself.
     pub async fn perform(self) -> Result<Easy2<C>, Error<C>> {
-        self.curl.send_request(self.easy).await
+        let Self {
+            curl,
+            mut easy,
+            lists,
+            cookie_jar,
+            verify_content_length,
+            ..
+        } = self;
+        if let Some(jar) = &cookie_jar {
+            jar.load(&mut easy)?;
+        }
+        // A cancelled transfer may still be using the lists on the worker,
+        // so they are leaked rather than freed when this future is dropped.
+        let lists = std::mem::ManuallyDrop::new(lists);
+        let result = curl.send_request(easy).await;
+        let mut lists = std::mem::ManuallyDrop::into_inner(lists);
+        let mut easy = result?;
+        lists.unset(&mut easy);
+        if let Some(jar) = &cookie_jar {
+            jar.store(&easy)?;
+        }
+        if verify_content_length {
+            info::verify_content_length(&easy)?;
+        }
+        Ok(easy)
+    }
+
+    /// Returns the [`RequestPlan`] recorded by the setters instead of
+    /// performing the request, e.g. to test the code building it.
+    pub fn dry_run(self) -> RequestPlan {
+        RequestPlan {
+            url: self.url,
+            headers: self.headers,
+            ..self.plan
+        }
     }
 }
+
+impl<C> AsyncCurl<C, Perform>
+where
+    C: Handler + std::fmt::Debug + Send,
+{
+    /// This will only connect to the host, doing the TLS handshake if any,
+    /// and return the [`Connection`] to send and receive raw bytes over.
+    ///
+    /// This sets `CURLOPT_CONNECT_ONLY`.
+    pub async fn connect(mut self) -> Result<Connection<C>, Error<C>> {
+        // The lists set through `quote` or `mail_rcpt` only apply to a transfer.
+        self.lists.unset(&mut self.easy);
+        self.easy.connect_only(true).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.curl.connect(self.easy).await
+    }
+
+    /// This will connect to the `ws://` or `wss://` URL, upgrade the
+    /// connection to a WebSocket and return the [`WebSocket`] to send and
+    /// receive frames over.
+    ///
+    /// Fails with `CURLE_NOT_BUILT_IN` and a description of the linked
+    /// libcurl if it does not support WebSocket, see `websocket::supported`.
+    ///
+    /// This sets `CURLOPT_CONNECT_ONLY` to 2.
+    #[cfg(feature = "websocket")]
+    pub async fn websocket(mut self) -> Result<WebSocket<C>, Error<C>> {
+        let result = if websocket::supported() {
+            setopt_long(&mut self.easy, curl_sys::CURLOPT_CONNECT_ONLY, 2)
+        } else {
+            let mut err = curl::Error::new(CURLE_NOT_BUILT_IN);
+            err.set_extra(format!(
+                "libcurl {} does not support WebSocket",
+                version_info().version
+            ));
+            Err(err)
+        };
+        result.map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        self.lists.unset(&mut self.easy);
+        let connection = self.curl.connect(self.easy).await?;
+        Ok(WebSocket::new(connection))
+    }
+}
+
+impl<A> AsyncCurl<CollectingHandler, Perform, A>
+where
+    A: Actor<CollectingHandler> + Clone,
+{
+    /// This will send the request asynchronously, and bundle the status code,
+    /// headers and body into a [`Response`], together with the URL the
+    /// transfer ended up at.
+    pub async fn perform_response(self) -> Result<Response, Error<CollectingHandler>> {
+        let curl = self.curl.clone();
+        let mut easy = self.perform().await?;
+        let response = take_response(&mut easy)?;
+        curl.recycle(easy);
+        Ok(response)
+    }
+
+    /// This will send the request asynchronously like `perform_response`,
+    /// revalidating the response `cache` kept for the URL, if any, through
+    /// `If-None-Match` and `If-Modified-Since`. The kept response is returned
+    /// when the server answers that it did not change, and a new one that
+    /// can be revalidated is kept for the next request.
+    ///
+    /// This sets `CURLOPT_FILETIME` to learn the `Last-Modified` time, and
+    /// `CURLOPT_TIMECONDITION` and `CURLOPT_TIMEVALUE` to send it back.
+    pub async fn perform_cached(
+        mut self,
+        cache: &HttpCache,
+    ) -> Result<Response, Error<CollectingHandler>> {
+        let Some(url) = self.url.clone() else {
+            return self.perform_response().await;
+        };
+        let cached = cache.entry(&url);
+        if let Some(etag) = cached.as_ref().and_then(|entry| entry.etag.as_ref()) {
+            self.append_header(format!("If-None-Match: {etag}"))?;
+        }
+        let last_modified = cached.as_ref().and_then(|entry| entry.last_modified);
+        let mut conditional = || -> Result<(), curl::Error> {
+            self.easy.fetch_filetime(true)?;
+            if let Some(time) = last_modified {
+                self.easy.time_condition(TimeCondition::IfModifiedSince)?;
+                self.easy.time_value(time)?;
+            }
+            Ok(())
+        };
+        conditional().map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        let curl = self.curl.clone();
+        let mut easy = self.perform().await?;
+        let info = || -> Result<_, curl::Error> {
+            Ok((
+                easy.response_code()?,
+                easy.time_condition_unmet()?,
+                easy.filetime()?,
+            ))
+        };
+        let (status, unmet, filetime) = info().map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        // libcurl also drops a body that is not newer than `If-Modified-Since`
+        // when the server ignored the condition.
+        if let Some(entry) = cached.filter(|_| status == 304 || unmet) {
+            curl.recycle(easy);
+            return Ok(entry.response);
+        }
+        let response = take_response(&mut easy)?;
+        curl.recycle(easy);
+        cache.store(&url, &response, filetime);
+        Ok(response)
+    }
+
+    /// This will send the request asynchronously,
+    /// and return the response body as text decoded with the `charset`
+    /// of the `Content-Type` header, falling back to lossy UTF-8.
+    pub async fn perform_text(self) -> Result<String, Error<CollectingHandler>> {
+        let curl = self.curl.clone();
+        let easy = self.perform().await?;
+        let text = easy.get_ref().text();
+        curl.recycle(easy);
+        Ok(text)
+    }
+
+    /// This will send the request asynchronously,
+    /// and deserialize the JSON response body into `T`.
+    ///
+    /// A body that is not valid JSON for `T` is reported as [`Error::Decode`].
+    #[cfg(feature = "serde")]
+    pub async fn perform_json<T>(self) -> Result<T, Error<CollectingHandler>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let curl = self.curl.clone();
+        let easy = self.perform().await?;
+        let value = serde_json::from_slice(easy.get_ref().body()).map_err(|err| {
+            log::trace!("{err}");
+            Error::Decode(err.to_string())
+        });
+        curl.recycle(easy);
+        value
+    }
+}
+
+/// Bundles the status code, headers and body of a finished transfer into
+/// a [`Response`], leaving an empty handler behind.
+fn take_response(
+    easy: &mut Easy2<CollectingHandler>,
+) -> Result<Response, Error<CollectingHandler>> {
+    let info = || -> Result<_, curl::Error> {
+        Ok((
+            easy.response_code()? as u16,
+            easy.effective_url()?.map(str::to_string),
+            easy.redirect_count()?,
+        ))
+    };
+    let (status, effective_url, redirect_count) = info().map_err(|err| {
+        log::trace!("{err}");
+        Error::Curl(err)
+    })?;
+    let mut response = std::mem::take(easy.get_mut()).into_response(status);
+    response.effective_url = effective_url;
+    response.redirect_count = redirect_count;
+    Ok(response)
+}
+
+impl<A> AsyncCurl<StreamHandler, Perform, A>
+where
+    A: Actor<StreamHandler>,
+{
+    /// This will send the request asynchronously like `perform`, and close the
+    /// body stream of the [`StreamHandler`] once the transfer has finished, so
+    /// its [`BodyReader`](crate::handler::BodyReader) reaches the end of the body.
+    ///
+    /// The returned future must run concurrently with the reader.
+    pub async fn perform_stream(self) -> Result<Easy2<StreamHandler>, Error<StreamHandler>> {
+        let mut easy = self.perform().await?;
+        easy.get_mut().close().await;
+        Ok(easy)
+    }
+}
+
+impl<H, A> AsyncCurl<DecodingHandler<H>, Perform, A>
+where
+    H: Handler + std::fmt::Debug + Send + 'static,
+    A: Actor<DecodingHandler<H>>,
+{
+    /// This will send the request asynchronously like `perform`, but fail with
+    /// [`Error::Decode`] when the body reached the handler still encoded,
+    /// because its `Content-Encoding` is one libcurl was not built to decode,
+    /// or because decoding was not enabled through `accept_encoding`.
+    ///
+    /// Whether libcurl decoded the body is told from the transfer itself, by
+    /// comparing the bytes the handler was given with the bytes received, so
+    /// decoding enabled through `easy_mut` or on the `Easy2` given to
+    /// `from_easy` counts too.
+    ///
+    /// The body is also checked against its `Content-Length` like with
+    /// `verify_content_length`, which counts the bytes before decoding. The
+    /// length of the decoded body cannot be verified, as HTTP does not tell
+    /// it, so `DecodingHandler::decoded_len` only reports it.
+    pub async fn perform_decoded(
+        self,
+    ) -> Result<Easy2<DecodingHandler<H>>, Error<DecodingHandler<H>>> {
+        let easy = self.perform().await.map_err(|err| {
+            if err.curl_code() == Some(curl_sys::CURLE_BAD_CONTENT_ENCODING) {
+                Error::Decode(format!("libcurl could not decode the body: {err}"))
+            } else {
+                err
+            }
+        })?;
+        info::verify_content_length(&easy)?;
+        let received = info::TransferInfo::sizes(&easy)?.download;
+        // libcurl hands the handler other bytes than it received only when it decoded them.
+        if received == 0 || easy.get_ref().decoded_len() != received {
+            return Ok(easy);
+        }
+        let codings = easy.get_ref().content_encoding().unwrap_or_default();
+        for coding in codings.split(',').map(str::trim) {
+            if coding.is_empty() || coding == "identity" {
+                continue;
+            }
+            let err = if can_decode(coding) {
+                format!("the body is {coding} encoded, but decoding was not enabled with accept_encoding")
+            } else {
+                format!("libcurl was not built to decode {coding}")
+            };
+            log::trace!("{err}");
+            return Err(Error::Decode(err));
+        }
+        Ok(easy)
+    }
+}
+
+/// Returns the name of a header line, which ends at the `:` of a
+/// `name: value` line or the `;` of a header sent without content.
+fn header_name(line: &str) -> &str {
+    line.split([':', ';']).next().unwrap_or_default().trim()
+}
+
+/// Returns whether libcurl was built to decode the content `coding`.
+fn can_decode(coding: &str) -> bool {
+    let info = version_info();
+    match coding {
+        "gzip" | "x-gzip" | "deflate" => info.libz,
+        "br" => info.brotli,
+        "zstd" => info.zstd,
+        _ => false,
+    }
+}
+
+impl<A> AsyncCurl<LinesHandler, Perform, A>
+where
+    A: Actor<LinesHandler>,
+{
+    /// This will send the request asynchronously like `perform`, and close the
+    /// line stream of the [`LinesHandler`] once the transfer has finished, so
+    /// the last line is sent even without a final `\n` and the receiver ends.
+    ///
+    /// The returned future must run concurrently with the receiver.
+    pub async fn perform_lines(self) -> Result<Easy2<LinesHandler>, Error<LinesHandler>> {
+        let mut easy = self.perform().await?;
+        easy.get_mut().close().await;
+        Ok(easy)
+    }
+}
+
+impl<H, S, A> AsyncCurl<CallbackHandler<H>, S, A>
+where
+    H: Handler + std::fmt::Debug + Send + 'static,
+    S: BuildState,
+{
+    /// Uploads the request body from `reader` as the transfer goes, instead of
+    /// copying the whole body into the handle like `post_fields_copy`.
+    ///
+    /// Pass the length of the body as `len` when it is known, so it is sent
+    /// as `Content-Length` rather than with chunked transfer encoding.
+    ///
+    /// This sets `CURLOPT_UPLOAD` and `CURLOPT_INFILESIZE_LARGE`.
+    pub fn upload_stream<R>(
+        mut self,
+        reader: R,
+        len: Option<u64>,
+    ) -> Result<Self, Error<CallbackHandler<H>>>
+    where
+        R: tokio::io::AsyncRead + Send + 'static,
+    {
+        self.easy.get_mut().set_reader(reader);
+        self.easy.upload(true).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        if let Some(len) = len {
+            self.easy.in_filesize(len).map_err(|err| {
+                log::trace!("{err}");
+                Error::Curl(err)
+            })?;
+        }
+        Ok(self)
+    }
+
+    /// Feeds the request body from `source`, which libcurl can also rewind
+    /// to send the body again after a redirect.
+    ///
+    /// This only installs the source, combine it with `upload` and
+    /// `in_filesize`, or with `post` and `post_field_size`.
+    ///
+    /// This corresponds to `CURLOPT_READFUNCTION` and `CURLOPT_SEEKFUNCTION`.
+    pub fn upload_reader<R>(mut self, source: R) -> Self
+    where
+        R: ReadSource + 'static,
+    {
+        self.easy.get_mut().set_source(source);
+        self
+    }
+
+    /// Calls `progress` with the download and upload progress while the
+    /// transfer is running, e.g. to drive a progress bar.
+    ///
+    /// The callback runs on the background thread of the [`CurlActor`] that
+    /// performs the transfer, hence it must be `Send`, and it should return
+    /// quickly since it holds up every transfer of that actor while it runs.
+    ///
+    /// This enables the progress meter, i.e. `CURLOPT_NOPROGRESS` is set to 0.
+    pub fn on_progress<F>(mut self, progress: F) -> Result<Self, Error<CallbackHandler<H>>>
+    where
+        F: FnMut(ProgressInfo) + Send + 'static,
+    {
+        self.easy.get_mut().set_progress(progress);
+        self.easy.progress(true).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        Ok(self)
+    }
+
+    /// Calls `header` with every header line received, including the ones of
+    /// informational `1xx` responses, redirects and trailers, as they arrive.
+    /// Returning false from it aborts the transfer.
+    ///
+    /// Like `on_progress`, the callback runs on the background thread of the
+    /// [`CurlActor`] and must be `Send`.
+    ///
+    /// This corresponds to `CURLOPT_HEADERFUNCTION`.
+    pub fn on_header<F>(mut self, header: F) -> Self
+    where
+        F: FnMut(&[u8]) -> bool + Send + 'static,
+    {
+        self.easy.get_mut().set_header(header);
+        self
+    }
+
+    /// Records the debug output of the transfer, such as the TLS handshake
+    /// and the headers sent and received, instead of printing it to stderr.
+    /// It is read back through `CallbackHandler::debug_log` of the returned
+    /// Easy2.
+    ///
+    /// This turns on `verbose`, i.e. `CURLOPT_VERBOSE` is set to 1.
+    pub fn capture_debug(mut self) -> Result<Self, Error<CallbackHandler<H>>> {
+        self.easy.get_mut().capture_debug();
+        self.easy.verbose(true).map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        Ok(self)
+    }
+
+    /// Appends the TLS session secrets of the transfer to the file at `path`,
+    /// in the key log format Wireshark reads to decrypt captured traffic.
+    ///
+    /// **Debug only.** Anyone holding the file can read everything sent over
+    /// the connections it covers, so never enable this in production.
+    /// It needs the `ssl-keylog` feature and a libcurl built with OpenSSL,
+    /// and fails with `CURLE_NOT_BUILT_IN` on any other TLS backend.
+    #[cfg(all(feature = "ssl-keylog", unix, not(target_os = "macos")))]
+    pub fn ssl_keylog<P: AsRef<Path>>(
+        mut self,
+        path: P,
+    ) -> Result<Self, Error<CallbackHandler<H>>> {
+        let info = version_info();
+        if !info
+            .ssl_version
+            .as_deref()
+            .is_some_and(|ssl| ssl.contains("OpenSSL"))
+        {
+            let mut err = curl::Error::new(CURLE_NOT_BUILT_IN);
+            err.set_extra(format!(
+                "libcurl {} does not use OpenSSL, which the TLS key log needs",
+                info.version
+            ));
+            log::trace!("{err}");
+            return Err(Error::Curl(err));
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())
+            .map_err(|err| {
+                log::trace!("{err}");
+                Error::Io(err)
+            })?;
+        log::warn!(
+            "TLS session secrets are written to {}, do not use this in production",
+            path.as_ref().display()
+        );
+        self.easy
+            .get_mut()
+            .set_keylog(std::sync::Arc::new(std::sync::Mutex::new(file)));
+        Ok(self)
+    }
+}
+
+impl<H, A> AsyncCurl<CallbackHandler<H>, Perform, A>
+where
+    H: Handler + std::fmt::Debug + Send + 'static,
+    A: Actor<CallbackHandler<H>>,
+{
+    /// This will send the request like `perform`, and also return a receiver
+    /// of the latest [`ProgressInfo`] of the transfer that can be awaited from
+    /// any task, as an alternative to `on_progress`.
+    ///
+    /// The returned future resolves once the transfer has finished, after
+    /// which the receiver sees no more changes.
+    pub fn perform_with_progress(
+        mut self,
+    ) -> (
+        impl Future<Output = PerformResult<CallbackHandler<H>>> + Send,
+        watch::Receiver<ProgressInfo>,
+    ) {
+        let (sender, receiver) = watch::channel(ProgressInfo::default());
+        self.easy.get_mut().set_progress(move |info| {
+            sender.send_if_modified(|current| {
+                let changed = *current != info;
+                *current = info;
+                changed
+            });
+        });
+        let enabled = self.easy.progress(true);
+        let response = async move {
+            enabled.map_err(|err| {
+                log::trace!("{err}");
+                Error::Curl(err)
+            })?;
+            self.perform().await
+        };
+        (response, receiver)
+    }
+}
+
+impl<A> AsyncCurl<FileHandler, BuildWithUrl, A>
+where
+    A: Actor<FileHandler> + Clone,
+{
+    /// This will send the request asynchronously and write the response body
+    /// straight into the file at `path`, returning the number of bytes written.
+    ///
+    /// The file is created, or truncated if it already exists, and removed
+    /// again if the transfer fails. When `resume_from` was set, the body is
+    /// appended to the existing file instead and a failed transfer leaves
+    /// the partial file in place so it can be resumed once more.
+    ///
+    /// An HTTP response that is not a success fails with
+    /// `CURLE_HTTP_RETURNED_ERROR`, without its body being written.
+    pub async fn download_to<P: AsRef<Path>>(self, path: P) -> Result<u64, Error<FileHandler>> {
+        let curl = self.curl.clone();
+        let mut easy = self.download(path.as_ref()).await?;
+        let written = easy.get_ref().written();
+        // Replacing the handler closes the file before the handle is pooled.
+        *easy.get_mut() = FileHandler::new();
+        curl.recycle(easy);
+        Ok(written)
+    }
+
+    /// This will continue downloading into the file at `path` from where a
+    /// previous attempt stopped, setting `resume_from` to the size of the
+    /// partial file, and returns the size of the completed file.
+    ///
+    /// Once the transfer is done, the size of the file is checked against
+    /// the `Content-Range` or `Content-Length` of the response, and a short
+    /// file fails with an `io::ErrorKind::UnexpectedEof` error. The partial
+    /// file is kept on failure, so the download can be resumed again.
+    ///
+    /// A `416 Range Not Satisfiable` response whose `Content-Range` gives
+    /// the size of the file means it was already complete.
+    pub async fn resume_download<P: AsRef<Path>>(self, path: P) -> Result<u64, Error<FileHandler>> {
+        let path = path.as_ref();
+        let existing = match std::fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(err) => {
+                log::trace!("{err}");
+                return Err(Error::Io(err));
+            }
+        };
+        let curl = self.curl.clone();
+        let mut easy = self.resume_from(existing)?.download(path).await?;
+        let size = existing + easy.get_ref().written();
+        let expected = match easy.get_ref().total_size() {
+            Some(total) => Some(total),
+            None => easy
+                .content_length_download()
+                .ok()
+                .filter(|length| *length >= 0.0)
+                .map(|length| existing + length as u64),
+        };
+        *easy.get_mut() = FileHandler::new();
+        curl.recycle(easy);
+
+        match expected {
+            Some(expected) if expected != size => {
+                let err = std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("Downloaded {size} of {expected} bytes"),
+                );
+                log::trace!("{err}");
+                Err(Error::Io(err))
+            }
+            _ => Ok(size),
+        }
+    }
+
+    /// Performs the transfer into the file at `path` as described by `download_to`.
+    async fn download(mut self, path: &Path) -> Result<Easy2<FileHandler>, Error<FileHandler>> {
+        let resume_from = self.resume_from.unwrap_or(0);
+        let resuming = resume_from > 0;
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resuming)
+            .truncate(!resuming)
+            .open(path)
+            .map_err(|err| {
+                log::trace!("{err}");
+                Error::Io(err)
+            })?;
+        *self.easy.get_mut() = FileHandler::with_file(file);
+
+        let response = self
+            .finalize()
+            .perform()
+            .await
+            .and_then(|easy| check_download_status(easy, resume_from));
+        if response.is_err() && !resuming {
+            if let Err(err) = std::fs::remove_file(path) {
+                log::trace!("{err}");
+            }
+        }
+        response
+    }
+}
+
+/// Fails a download whose HTTP response is not a success, except for the
+/// `416` telling that the file resumed from `resume_from` is already complete.
+fn check_download_status(
+    easy: Easy2<FileHandler>,
+    resume_from: u64,
+) -> Result<Easy2<FileHandler>, Error<FileHandler>> {
+    let status = easy.response_code().unwrap_or(0);
+    let complete =
+        status == 416 && resume_from > 0 && easy.get_ref().total_size() == Some(resume_from);
+    if status == 0 || (200..300).contains(&status) || complete {
+        return Ok(easy);
+    }
+    let mut err = curl::Error::new(curl_sys::CURLE_HTTP_RETURNED_ERROR);
+    err.set_extra(format!("The server answered with status {status}"));
+    log::trace!("{err}");
+    Err(Error::Curl(err))
+}
+
+/// Joins protocols into the comma-separated list of `CURLOPT_PROTOCOLS_STR`.
+fn protocol_list(protocols: &[Protocol]) -> String {
+    protocols
+        .iter()
+        .map(Protocol::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Returns the path as the string libcurl expects.
+fn path_str(path: &Path) -> Result<&str, curl::Error> {
+    path.to_str()
+        .ok_or_else(|| curl::Error::new(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT))
+}
+
+/// Sets a DNS option that only libcurl linked against c-ares supports,
+/// explaining the failure when it is not.
+fn set_dns_option<C>(
+    easy: &mut Easy2<C>,
+    option: curl_sys::CURLoption,
+    value: &str,
+) -> Result<(), Error<C>>
+where
+    C: Handler + std::fmt::Debug + Send + 'static,
+{
+    setopt_str(easy, option, value).map_err(|mut err| {
+        // Depending on the version, libcurl reports the option as not built in or unknown.
+        if err.code() == CURLE_NOT_BUILT_IN || err.is_unknown_option() {
+            err.set_extra(format!(
+                "libcurl {} was built without c-ares",
+                version_info().version
+            ));
+        }
+        log::trace!("{err}");
+        Error::Curl(err)
+    })
+}
+
+/// The `curl_slist`s of the list options that the [`Easy2`] does not keep
+/// alive itself, such as `CURLOPT_QUOTE` and `CURLOPT_MAIL_RCPT`.
+#[derive(Debug, Default)]
+struct SlistOptions(Vec<(curl_sys::CURLoption, *mut curl_sys::curl_slist)>);
+
+// SAFETY: the lists are only read by libcurl, through the Easy2 they are set on.
+unsafe impl Send for SlistOptions {}
+
+impl SlistOptions {
+    /// Sets `option` to a copy of `list`, replacing the list set before.
+    fn set<C: Handler>(
+        &mut self,
+        easy: &mut Easy2<C>,
+        option: curl_sys::CURLoption,
+        list: &List,
+    ) -> Result<(), curl::Error> {
+        let mut raw: *mut curl_sys::curl_slist = std::ptr::null_mut();
+        for item in list.iter() {
+            let appended = CString::new(item)
+                .ok()
+                .map(|item| unsafe { curl_sys::curl_slist_append(raw, item.as_ptr()) })
+                .filter(|appended| !appended.is_null());
+            let Some(appended) = appended else {
+                unsafe { curl_sys::curl_slist_free_all(raw) };
+                return Err(curl::Error::new(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT));
+            };
+            raw = appended;
+        }
+        if let Err(err) = check_code(unsafe { curl_sys::curl_easy_setopt(easy.raw(), option, raw) })
+        {
+            unsafe { curl_sys::curl_slist_free_all(raw) };
+            return Err(err);
+        }
+        if let Some(index) = self.0.iter().position(|(set, _)| *set == option) {
+            let (_, old) = self.0.swap_remove(index);
+            unsafe { curl_sys::curl_slist_free_all(old) };
+        }
+        if !raw.is_null() {
+            self.0.push((option, raw));
+        }
+        Ok(())
+    }
+
+    /// Unsets the options and frees their lists, so the Easy2 can outlive them.
+    fn unset<C: Handler>(&mut self, easy: &mut Easy2<C>) {
+        for (option, raw) in self.0.drain(..) {
+            unsafe {
+                curl_sys::curl_easy_setopt(
+                    easy.raw(),
+                    option,
+                    std::ptr::null_mut::<curl_sys::curl_slist>(),
+                );
+                curl_sys::curl_slist_free_all(raw);
+            }
+        }
+    }
+}
+
+impl Drop for SlistOptions {
+    fn drop(&mut self) {
+        for (_, raw) in self.0.drain(..) {
+            unsafe { curl_sys::curl_slist_free_all(raw) };
+        }
+    }
+}
+
+/// Sets an option of the `CURLOPTTYPE_LONG` kind.
+pub(crate) fn setopt_long<C: Handler>(
+    easy: &mut Easy2<C>,
+    option: curl_sys::CURLoption,
+    value: std::ffi::c_long,
+) -> Result<(), curl::Error> {
+    check_code(unsafe { curl_sys::curl_easy_setopt(easy.raw(), option, value) })
+}
+
+/// Joins the entries of `list` for the [`RequestPlan`].
+fn list_value(list: &List) -> String {
+    list.iter()
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The pointer options of libcurl 8.6 that take something other than a
+/// string libcurl copies, so `setopt_raw` refuses a [`RawOption::Str`] for them.
+const NOT_STRING_OPTIONS: &[curl_sys::CURLoption] = &[
+    curl_sys::CURLOPT_WRITEDATA,
+    curl_sys::CURLOPT_READDATA,
+    curl_sys::CURLOPT_ERRORBUFFER,
+    curl_sys::CURLOPT_POSTFIELDS,
+    curl_sys::CURLOPT_HTTPHEADER,
+    curl_sys::CURLOPT_HTTPPOST,
+    curl_sys::CURLOPT_QUOTE,
+    curl_sys::CURLOPT_HEADERDATA,
+    curl_sys::CURLOPT_STDERR,
+    curl_sys::CURLOPT_POSTQUOTE,
+    // CURLOPT_OBSOLETE40
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 40,
+    curl_sys::CURLOPT_PROGRESSDATA,
+    curl_sys::CURLOPT_TELNETOPTIONS,
+    curl_sys::CURLOPT_PREQUOTE,
+    curl_sys::CURLOPT_DEBUGDATA,
+    curl_sys::CURLOPT_SHARE,
+    curl_sys::CURLOPT_PRIVATE,
+    curl_sys::CURLOPT_HTTP200ALIASES,
+    curl_sys::CURLOPT_SSL_CTX_DATA,
+    curl_sys::CURLOPT_IOCTLDATA,
+    curl_sys::CURLOPT_SOCKOPTDATA,
+    curl_sys::CURLOPT_OPENSOCKETDATA,
+    curl_sys::CURLOPT_SEEKDATA,
+    curl_sys::CURLOPT_SSH_KEYDATA,
+    curl_sys::CURLOPT_MAIL_RCPT,
+    curl_sys::CURLOPT_INTERLEAVEDATA,
+    curl_sys::CURLOPT_CHUNK_DATA,
+    curl_sys::CURLOPT_FNMATCH_DATA,
+    curl_sys::CURLOPT_RESOLVE,
+    curl_sys::CURLOPT_CLOSESOCKETDATA,
+    // CURLOPT_PROXYHEADER
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 228,
+    // CURLOPT_STREAM_DEPENDS and CURLOPT_STREAM_DEPENDS_E
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 240,
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 241,
+    curl_sys::CURLOPT_CONNECT_TO,
+    // CURLOPT_MIMEPOST
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 269,
+    // CURLOPT_RESOLVER_START_DATA
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 273,
+    // CURLOPT_CURLU
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 282,
+    // CURLOPT_TRAILERDATA
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 284,
+    // CURLOPT_HSTSREADDATA and CURLOPT_HSTSWRITEDATA
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 302,
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 304,
+    // CURLOPT_PREREQDATA
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 313,
+    // CURLOPT_SSH_HOSTKEYDATA
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 317,
+];
+
+/// Converts a value given to `setopt_raw` to the type of the typed setter
+/// of `option`, failing for values the setter cannot take.
+fn raw_value<T, V>(option: curl_sys::CURLoption, value: V) -> Result<T, curl::Error>
+where
+    T: TryFrom<V>,
+    V: Copy + std::fmt::Display,
+{
+    T::try_from(value).map_err(|_| raw_value_error(option, value))
+}
+
+fn raw_value_error(option: curl_sys::CURLoption, value: impl std::fmt::Display) -> curl::Error {
+    let mut err = curl::Error::new(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT);
+    err.set_extra(format!(
+        "{value} is not a value option {option} can be set to here"
+    ));
+    log::trace!("{err}");
+    err
+}
+
+/// Converts the `CURLAUTH_*` bits given to `setopt_raw` to an [`Auth`],
+/// failing for bits it cannot express.
+fn raw_auth(option: curl_sys::CURLoption, bits: std::ffi::c_long) -> Result<Auth, curl::Error> {
+    let known = curl_sys::CURLAUTH_BASIC
+        | curl_sys::CURLAUTH_DIGEST
+        | curl_sys::CURLAUTH_DIGEST_IE
+        | curl_sys::CURLAUTH_GSSNEGOTIATE
+        | curl_sys::CURLAUTH_NTLM
+        | curl_sys::CURLAUTH_NTLM_WB;
+    let bits: std::ffi::c_ulong = raw_value(option, bits)?;
+    if bits & !known != 0 {
+        return Err(raw_value_error(option, bits));
+    }
+    let mut auth = Auth::new();
+    auth.basic(bits & curl_sys::CURLAUTH_BASIC != 0)
+        .digest(bits & curl_sys::CURLAUTH_DIGEST != 0)
+        .digest_ie(bits & curl_sys::CURLAUTH_DIGEST_IE != 0)
+        .gssnegotiate(bits & curl_sys::CURLAUTH_GSSNEGOTIATE != 0)
+        .ntlm(bits & curl_sys::CURLAUTH_NTLM != 0)
+        .ntlm_wb(bits & curl_sys::CURLAUTH_NTLM_WB != 0);
+    Ok(auth)
+}
+
+/// Sets a string option, which libcurl copies.
+fn setopt_str<C: Handler>(
+    easy: &mut Easy2<C>,
+    option: curl_sys::CURLoption,
+    value: &str,
+) -> Result<(), curl::Error> {
+    let value =
+        CString::new(value).map_err(|_| curl::Error::new(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT))?;
+    check_code(unsafe { curl_sys::curl_easy_setopt(easy.raw(), option, value.as_ptr()) })
+}
+
+fn check_code(rc: curl_sys::CURLcode) -> Result<(), curl::Error> {
+    if rc != curl_sys::CURLE_OK {
+        return Err(curl::Error::new(rc));
+    }
+    Ok(())
+}
+
+/// Resets `CURLOPT_CUSTOMREQUEST` so the method follows from the other options again.
+fn clear_custom_request<C: Handler>(easy: &mut Easy2<C>) -> Result<(), curl::Error> {
+    check_code(unsafe {
+        curl_sys::curl_easy_setopt(
+            easy.raw(),
+            curl_sys::CURLOPT_CUSTOMREQUEST,
+            std::ptr::null::<std::ffi::c_char>(),
+        )
+    })
+}
@@ -7,14 +7,43 @@ use crate::actor;
 
 /// This the enum of Errors for this crate.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error<H>
 where
     H: Handler + Debug + Send + 'static,
 {
     Curl(curl::Error),
+    /// The [`Handler`] stopped the transfer on purpose by consuming fewer
+    /// bytes than it was given in `write` (libcurl's `CURLE_WRITE_ERROR`).
+    HandlerAborted(curl::Error),
     Multi(curl::MultiError),
     TokioRecv(RecvError),
     TokioSend(SendError<actor::Request<H>>),
+    /// The transfer ran longer than the timeout given to
+    /// `CurlActor::send_request_timeout` or `CurlActorBuilder::default_timeout`,
+    /// or past the deadline given to `CurlActor::send_request_deadline`.
+    Timeout,
+    /// The transfer was aborted through its `TransferHandle` before it finished.
+    Cancelled,
+    /// The response body could not be decoded, e.g. by `AsyncCurl::perform_json`.
+    Decode(String),
+    /// The request body could not be encoded, e.g. by `AsyncCurl::json_body`.
+    Encode(String),
+    /// A local file operation failed, e.g. in `AsyncCurl::download_to`.
+    Io(std::io::Error),
+    /// The transfer succeeded with fewer bytes of body than its
+    /// `Content-Length` announced, see `AsyncCurl::verify_content_length`.
+    TruncatedBody {
+        expected: u64,
+        received: u64,
+    },
+    /// A callback of the [`Handler`], wrapped in a `CatchUnwindHandler`,
+    /// panicked with this message, which failed the transfer without taking
+    /// down the background worker.
+    HandlerPanic(String),
+    /// The request was not sent because the `CircuitBreaker` it went through
+    /// is open after too many failures in a row.
+    CircuitOpen,
 }
 
 /// This convert RecvError to our customized
@@ -53,6 +82,110 @@ where
     }
 }
 
+impl<H> Error<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    /// Returns the raw libcurl `CURLcode` when this error came from a transfer,
+    /// so it can be matched against constants such as
+    /// `curl_sys::CURLE_OPERATION_TIMEDOUT` without parsing the message.
+    pub fn curl_code(&self) -> Option<curl_sys::CURLcode> {
+        self.curl_error().map(|err| err.code())
+    }
+
+    /// Returns the detailed reason libcurl wrote to its error buffer
+    /// (`CURLOPT_ERRORBUFFER`) for a failed transfer, which is usually more
+    /// specific than the generic message of the `CURLcode`.
+    pub fn detail(&self) -> Option<&str> {
+        self.curl_error().and_then(|err| err.extra_description())
+    }
+
+    /// Returns true if the transfer timed out, either inside libcurl or
+    /// through the actor's own timeout.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Error::Timeout)
+            || self
+                .curl_error()
+                .is_some_and(|err| err.is_operation_timedout())
+    }
+
+    /// Returns true if libcurl failed to connect to the host or proxy.
+    pub fn is_couldnt_connect(&self) -> bool {
+        self.curl_error()
+            .is_some_and(|err| err.is_couldnt_connect())
+    }
+
+    /// Returns true if libcurl failed to resolve the host name.
+    pub fn is_couldnt_resolve_host(&self) -> bool {
+        self.curl_error()
+            .is_some_and(|err| err.is_couldnt_resolve_host())
+    }
+
+    /// Returns true if no connection could be established at all, because
+    /// the host or proxy could not be resolved or refused to connect.
+    pub fn is_connection_error(&self) -> bool {
+        self.curl_error().is_some_and(|err| {
+            err.is_couldnt_connect()
+                || err.is_couldnt_resolve_host()
+                || err.is_couldnt_resolve_proxy()
+        })
+    }
+
+    /// Returns true if the TLS handshake or the certificate verification failed.
+    pub fn is_ssl_error(&self) -> bool {
+        self.curl_error().is_some_and(|err| {
+            err.is_ssl_connect_error()
+                || err.is_peer_failed_verification()
+                || err.is_ssl_certproblem()
+                || err.is_ssl_cipher()
+                || err.is_ssl_cacert()
+                || err.is_ssl_cacert_badfile()
+                || err.is_ssl_crl_badfile()
+                || err.is_ssl_shutdown_failed()
+                || err.is_ssl_issuer_error()
+                || err.is_ssl_engine_notfound()
+                || err.is_ssl_engine_setfailed()
+                || err.is_ssl_engine_initfailed()
+                || err.is_use_ssl_failed()
+        })
+    }
+
+    fn curl_error(&self) -> Option<&curl::Error> {
+        match self {
+            Error::Curl(err) | Error::HandlerAborted(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// This convert our customized Error enum into std::io::Error
+/// for networking layers that deal in io::Result, keeping the
+/// original error as the inner error.
+impl<H> From<Error<H>> for std::io::Error
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn from(err: Error<H>) -> Self {
+        if let Error::Io(err) = err {
+            return err;
+        }
+        let kind = if err.is_timeout() {
+            std::io::ErrorKind::TimedOut
+        } else if err.is_connection_error() {
+            std::io::ErrorKind::ConnectionRefused
+        } else if matches!(err, Error::TruncatedBody { .. }) {
+            std::io::ErrorKind::UnexpectedEof
+        } else if matches!(err, Error::Decode(_)) {
+            std::io::ErrorKind::InvalidData
+        } else if matches!(err, Error::Encode(_)) {
+            std::io::ErrorKind::InvalidInput
+        } else {
+            std::io::ErrorKind::Other
+        };
+        std::io::Error::new(kind, CurlError::from(err))
+    }
+}
+
 impl<H> std::fmt::Display for Error<H>
 where
     H: Handler + Debug + Send + 'static,
@@ -60,11 +193,165 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Error::Curl(err) => write!(f, "{}", err),
+            Error::HandlerAborted(err) => write!(f, "Transfer aborted by the handler: {}", err),
             Error::Multi(err) => write!(f, "{}", err),
             Error::TokioRecv(err) => write!(f, "{}", err),
             Error::TokioSend(err) => write!(f, "{}", err),
+            Error::Timeout => write!(f, "The transfer timed out"),
+            Error::Cancelled => write!(f, "The transfer was cancelled"),
+            Error::Decode(err) => write!(f, "Failed to decode the response body: {}", err),
+            Error::Encode(err) => write!(f, "Failed to encode the request body: {}", err),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::TruncatedBody { expected, received } => write!(
+                f,
+                "The response body was truncated: received {} of {} bytes",
+                received, expected
+            ),
+            Error::HandlerPanic(err) => write!(f, "The handler panicked: {}", err),
+            Error::CircuitOpen => write!(f, "The circuit breaker is open"),
         }
     }
 }
 
-impl<H> std::error::Error for Error<H> where H: Handler + Debug + Send + 'static {}
+impl<H> std::error::Error for Error<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Curl(err) | Error::HandlerAborted(err) => Some(err),
+            Error::Multi(err) => Some(err),
+            Error::TokioRecv(err) => Some(err),
+            Error::TokioSend(err) => Some(err),
+            Error::Io(err) => Some(err),
+            Error::Timeout
+            | Error::Cancelled
+            | Error::Decode(_)
+            | Error::Encode(_)
+            | Error::TruncatedBody { .. }
+            | Error::HandlerPanic(_)
+            | Error::CircuitOpen => None,
+        }
+    }
+}
+
+/// This is the same set of errors as [`Error`], but without the `Handler`
+/// type parameter, so errors coming from different handler types can be
+/// stored or returned together.
+///
+/// The `TokioSend` variant is the only one that depends on the handler,
+/// so it keeps the `Debug` output of the original error instead.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CurlError {
+    Curl(curl::Error),
+    HandlerAborted(curl::Error),
+    Multi(curl::MultiError),
+    TokioRecv(RecvError),
+    TokioSend(String),
+    Timeout,
+    Cancelled,
+    Decode(String),
+    Encode(String),
+    Io(std::io::Error),
+    TruncatedBody { expected: u64, received: u64 },
+    HandlerPanic(String),
+    CircuitOpen,
+}
+
+/// This convert our generic Error into the
+/// handler-independent CurlError.
+impl<H> From<Error<H>> for CurlError
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn from(err: Error<H>) -> Self {
+        match err {
+            Error::Curl(err) => CurlError::Curl(err),
+            Error::HandlerAborted(err) => CurlError::HandlerAborted(err),
+            Error::Multi(err) => CurlError::Multi(err),
+            Error::TokioRecv(err) => CurlError::TokioRecv(err),
+            Error::TokioSend(err) => CurlError::TokioSend(format!("{:?}", err)),
+            Error::Timeout => CurlError::Timeout,
+            Error::Cancelled => CurlError::Cancelled,
+            Error::Decode(err) => CurlError::Decode(err),
+            Error::Encode(err) => CurlError::Encode(err),
+            Error::Io(err) => CurlError::Io(err),
+            Error::TruncatedBody { expected, received } => {
+                CurlError::TruncatedBody { expected, received }
+            }
+            Error::HandlerPanic(err) => CurlError::HandlerPanic(err),
+            Error::CircuitOpen => CurlError::CircuitOpen,
+        }
+    }
+}
+
+/// The `Io` variant is cloned as a new error of the same kind and message,
+/// since `std::io::Error` is not `Clone`.
+impl Clone for CurlError {
+    fn clone(&self) -> Self {
+        match self {
+            CurlError::Curl(err) => CurlError::Curl(err.clone()),
+            CurlError::HandlerAborted(err) => CurlError::HandlerAborted(err.clone()),
+            CurlError::Multi(err) => CurlError::Multi(err.clone()),
+            CurlError::TokioRecv(err) => CurlError::TokioRecv(err.clone()),
+            CurlError::TokioSend(err) => CurlError::TokioSend(err.clone()),
+            CurlError::Timeout => CurlError::Timeout,
+            CurlError::Cancelled => CurlError::Cancelled,
+            CurlError::Decode(err) => CurlError::Decode(err.clone()),
+            CurlError::Encode(err) => CurlError::Encode(err.clone()),
+            CurlError::Io(err) => CurlError::Io(std::io::Error::new(err.kind(), err.to_string())),
+            CurlError::TruncatedBody { expected, received } => CurlError::TruncatedBody {
+                expected: *expected,
+                received: *received,
+            },
+            CurlError::HandlerPanic(err) => CurlError::HandlerPanic(err.clone()),
+            CurlError::CircuitOpen => CurlError::CircuitOpen,
+        }
+    }
+}
+
+impl std::fmt::Display for CurlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CurlError::Curl(err) => write!(f, "{}", err),
+            CurlError::HandlerAborted(err) => {
+                write!(f, "Transfer aborted by the handler: {}", err)
+            }
+            CurlError::Multi(err) => write!(f, "{}", err),
+            CurlError::TokioRecv(err) => write!(f, "{}", err),
+            CurlError::TokioSend(err) => write!(f, "{}", err),
+            CurlError::Timeout => write!(f, "The transfer timed out"),
+            CurlError::Cancelled => write!(f, "The transfer was cancelled"),
+            CurlError::Decode(err) => write!(f, "Failed to decode the response body: {}", err),
+            CurlError::Encode(err) => write!(f, "Failed to encode the request body: {}", err),
+            CurlError::Io(err) => write!(f, "{}", err),
+            CurlError::TruncatedBody { expected, received } => write!(
+                f,
+                "The response body was truncated: received {} of {} bytes",
+                received, expected
+            ),
+            CurlError::HandlerPanic(err) => write!(f, "The handler panicked: {}", err),
+            CurlError::CircuitOpen => write!(f, "The circuit breaker is open"),
+        }
+    }
+}
+
+impl std::error::Error for CurlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CurlError::Curl(err) | CurlError::HandlerAborted(err) => Some(err),
+            CurlError::Multi(err) => Some(err),
+            CurlError::TokioRecv(err) => Some(err),
+            CurlError::Io(err) => Some(err),
+            CurlError::TokioSend(_)
+            | CurlError::Timeout
+            | CurlError::Cancelled
+            | CurlError::Decode(_)
+            | CurlError::Encode(_)
+            | CurlError::TruncatedBody { .. }
+            | CurlError::HandlerPanic(_)
+            | CurlError::CircuitOpen => None,
+        }
+    }
+}
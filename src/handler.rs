@@ -0,0 +1,1555 @@
+use std::collections::VecDeque;
+use std::ffi::{c_int, c_void};
+use std::fmt::Debug;
+use std::fs::File;
+use std::future::Future;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use curl::easy::{Handler, InfoType, ReadError, SeekResult, WriteError};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::{self, OwnedPermit, Receiver, Sender};
+
+use crate::actor;
+use crate::response::{RedirectHop, Response};
+
+/// CollectingHandler is a ready-made [`Handler`] that stores the response
+/// headers, in the order they were received, together with the body.
+///
+/// When redirects are followed, only the headers of the last response are
+/// kept, while the status and `Location` of every redirect are recorded as
+/// a [`RedirectHop`].
+/// ```
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::CollectingHandler;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+///     .url("https://www.rust-lang.org/")?
+///     .finalize();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CollectingHandler {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    /// The status code of the response the headers belong to.
+    status: Option<u16>,
+    redirects: Vec<RedirectHop>,
+}
+
+impl Handler for CollectingHandler {
+    /// This will store the response from the server
+    /// to the body vector.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.body.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    /// This will store every header line as a name and value pair,
+    /// reserving room for the body announced by `Content-Length`.
+    fn header(&mut self, data: &[u8]) -> bool {
+        // A new status line starts the headers of the next response,
+        // e.g. when following redirects, so only the last set is kept.
+        if data.starts_with(b"HTTP/") {
+            let location = self.header_value("Location").map(str::to_string);
+            if let (Some(status @ 300..=399), Some(location)) = (self.status, location) {
+                self.redirects.push(RedirectHop { status, location });
+            }
+            self.status = parse_status_line(data);
+            self.headers.clear();
+            return true;
+        }
+        if let Some(header) = parse_header_line(data) {
+            if let Some(len) = content_length(&header) {
+                self.body.reserve(len.saturating_sub(self.body.len()));
+            }
+            self.headers.push(header);
+        }
+        true
+    }
+}
+
+impl CollectingHandler {
+    /// Instantiation of the CollectingHandler
+    /// with empty headers and body.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the response headers in the order they were received.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the value of the first header with the given name,
+    /// compared case-insensitively.
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the response body received so far.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the redirects that were followed so far, in order.
+    pub fn redirects(&self) -> &[RedirectHop] {
+        &self.redirects
+    }
+
+    /// Returns the body as text, decoded with the `charset` of the
+    /// `Content-Type` header. UTF-8 is assumed when the charset is missing
+    /// or unknown, replacing invalid sequences.
+    pub fn text(&self) -> String {
+        let charset = self.header_value("Content-Type").and_then(charset);
+        decode_text(&self.body, charset)
+    }
+
+    /// This will give the body to the receiving variable
+    /// and leave an empty body behind.
+    pub fn take_body(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.body)
+    }
+
+    /// This will consume the handler and bundle its
+    /// headers, body and redirects with the given status code.
+    pub fn into_response(self, status: u16) -> Response {
+        let mut response = Response::from_parts(status, &self.headers, self.body);
+        response.redirects = self.redirects;
+        response
+    }
+}
+
+/// BoundedResponseHandler stores the response body like a plain response
+/// handler, but aborts the transfer with `Error::HandlerAborted` once the
+/// body grows past `max_bytes`, so an untrusted server cannot exhaust memory.
+///
+/// Keep the flag returned by [`BoundedResponseHandler::exceeded_flag`] to tell
+/// whether the limit was the reason a transfer failed, since the handler
+/// itself is not returned with the error.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::BoundedResponseHandler;
+/// use std::sync::atomic::Ordering;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let handler = BoundedResponseHandler::new(1024 * 1024);
+/// let exceeded = handler.exceeded_flag();
+///
+/// let result = AsyncCurl::new(CurlActor::new(), handler)
+///     .url("https://www.rust-lang.org/")?
+///     .finalize()
+///     .perform()
+///     .await;
+/// if result.is_err() && exceeded.load(Ordering::SeqCst) {
+///     eprintln!("The response is larger than 1 MiB");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct BoundedResponseHandler {
+    data: Vec<u8>,
+    max_bytes: usize,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl Handler for BoundedResponseHandler {
+    /// This will store the response from the server to the data
+    /// vector, aborting the transfer once the limit is exceeded.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        if self.data.len() + data.len() > self.max_bytes {
+            self.exceeded.store(true, Ordering::SeqCst);
+            return Ok(0);
+        }
+        self.data.extend_from_slice(data);
+        Ok(data.len())
+    }
+}
+
+impl BoundedResponseHandler {
+    /// Instantiation of the BoundedResponseHandler that
+    /// accepts a body of at most `max_bytes`.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether the body went past the limit.
+    pub fn limit_exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::SeqCst)
+    }
+
+    /// Returns a flag that is set once the body goes past the limit,
+    /// which stays readable after a failed transfer.
+    pub fn exceeded_flag(&self) -> Arc<AtomicBool> {
+        self.exceeded.clone()
+    }
+
+    /// Returns the response body received so far.
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// This will give the body to the receiving variable
+    /// and leave an empty body behind.
+    pub fn take_data(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.data)
+    }
+}
+
+/// BytesHandler stores the response body in a `BytesMut`, reserving room
+/// for the whole body once the `Content-Length` header arrives, and hands it
+/// out as a `Bytes` that is cheap to clone and share across tasks.
+/// ```
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::BytesHandler;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AsyncCurl::new(CurlActor::new(), BytesHandler::new())
+///     .url("https://www.rust-lang.org/")?
+///     .finalize();
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "bytes")]
+#[derive(Debug, Clone, Default)]
+pub struct BytesHandler {
+    body: bytes::BytesMut,
+}
+
+#[cfg(feature = "bytes")]
+impl Handler for BytesHandler {
+    /// This will append the chunk to the body.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.body.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    /// This will reserve room for the body announced by `Content-Length`.
+    fn header(&mut self, data: &[u8]) -> bool {
+        if let Some(len) = parse_header_line(data).as_ref().and_then(content_length) {
+            self.body.reserve(len.saturating_sub(self.body.len()));
+        }
+        true
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl BytesHandler {
+    /// Instantiation of the BytesHandler with an empty body.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the response body received so far.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// This will give the body to the receiving variable
+    /// and leave an empty body behind.
+    pub fn take_bytes(&mut self) -> bytes::Bytes {
+        self.body.split().freeze()
+    }
+
+    /// This will consume the handler and return the body.
+    pub fn into_bytes(self) -> bytes::Bytes {
+        self.body.freeze()
+    }
+}
+
+/// The most room reserved up front for a body, however large the
+/// `Content-Length` a server announces.
+const MAX_RESERVED_BODY: usize = 64 * 1024 * 1024;
+
+/// Returns the length announced by a `Content-Length` header,
+/// capped at [`MAX_RESERVED_BODY`].
+fn content_length((name, value): &(String, String)) -> Option<usize> {
+    if !name.eq_ignore_ascii_case("Content-Length") {
+        return None;
+    }
+    let len = value.parse::<usize>().ok()?;
+    Some(len.min(MAX_RESERVED_BODY))
+}
+
+/// Extracts the status code of a `HTTP/1.1 302 Found` status line.
+fn parse_status_line(data: &[u8]) -> Option<u16> {
+    String::from_utf8_lossy(data)
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+/// Splits a raw `Name: value\r\n` header line, skipping lines that are
+/// not headers such as the blank line ending the header block.
+fn parse_header_line(data: &[u8]) -> Option<(String, String)> {
+    let colon = data.iter().position(|b| *b == b':')?;
+    let name = String::from_utf8_lossy(data[..colon].trim_ascii()).into_owned();
+    let value = String::from_utf8_lossy(data[colon + 1..].trim_ascii()).into_owned();
+    if name.is_empty() {
+        return None;
+    }
+    Some((name, value))
+}
+
+/// StreamHandler is a [`Handler`] that forwards every chunk of the body to
+/// a [`BodyReader`] instead of buffering it, for constant-memory downloads.
+///
+/// At most `capacity` chunks are held in between. When the reader falls
+/// behind, the handler pauses the transfer until there is room again, and
+/// dropping the reader aborts the transfer with `Error::HandlerAborted`.
+///
+/// Use `AsyncCurl::perform_stream` so the reader reaches the end of the
+/// body as soon as the transfer finishes.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::StreamHandler;
+/// use tokio::io::AsyncReadExt;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let (handler, mut reader) = StreamHandler::new(16);
+/// let transfer = AsyncCurl::new(CurlActor::new(), handler)
+///     .url("https://www.rust-lang.org/")?
+///     .finalize()
+///     .perform_stream();
+///
+/// let consumer = async move {
+///     let mut chunk = [0u8; 4096];
+///     while reader.read(&mut chunk).await? > 0 {
+///         // Process the chunk while the transfer keeps going.
+///     }
+///     Ok::<_, std::io::Error>(())
+/// };
+/// let (_transfer, _consumer) = tokio::join!(transfer, consumer);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct StreamHandler {
+    sink: ChannelSink<Vec<u8>>,
+}
+
+impl Handler for StreamHandler {
+    /// This will forward the chunk to the reader, or pause the transfer
+    /// until the reader took the last chunk if it has not caught up yet.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        match self.sink.flush() {
+            Flush::Done => {}
+            Flush::Full => return Err(WriteError::Pause),
+            // The reader is gone, nobody is interested in the rest of the body.
+            Flush::Closed => return Ok(0),
+        }
+        self.sink.push(data.to_vec());
+        // A chunk that doesn't fit is sent before the next one is taken.
+        match self.sink.flush() {
+            Flush::Done | Flush::Full => Ok(data.len()),
+            Flush::Closed => Ok(0),
+        }
+    }
+}
+
+impl StreamHandler {
+    /// Creates the handler together with the reader of the body,
+    /// holding at most `capacity` chunks in between.
+    pub fn new(capacity: usize) -> (Self, BodyReader) {
+        let (sink, receiver) = ChannelSink::new(capacity);
+        (
+            Self { sink },
+            BodyReader {
+                receiver,
+                chunk: Vec::new(),
+                position: 0,
+            },
+        )
+    }
+}
+
+impl StreamHandler {
+    /// Ends the body stream once the last chunk was sent, waiting for room
+    /// in the channel, so the [`BodyReader`] reaches its end after it.
+    pub async fn close(&mut self) {
+        self.sink.close().await;
+    }
+}
+
+/// BodyReader is the [`AsyncRead`] side of a [`StreamHandler`]. It reaches
+/// the end of the stream once the transfer has finished.
+#[derive(Debug)]
+pub struct BodyReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl AsyncRead for BodyReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        while self.position == self.chunk.len() {
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(chunk)) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                // The handler has been closed or dropped.
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let len = buf.remaining().min(self.chunk.len() - self.position);
+        let position = self.position;
+        buf.put_slice(&self.chunk[position..position + len]);
+        self.position += len;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// LinesHandler is a [`Handler`] that sends every `\n`-terminated line of
+/// the body to a channel as soon as it is complete, for NDJSON and other
+/// line-delimited streams such as log tails.
+///
+/// The line ending, `\r\n` included, is removed and the line is decoded as
+/// lossy UTF-8. A line longer than `max_line_len` bytes aborts the transfer
+/// with `Error::HandlerAborted`, as does dropping the receiver. When the
+/// receiver falls behind, the handler pauses the transfer until there is
+/// room again.
+///
+/// Use `AsyncCurl::perform_lines` so the last line is sent even without a
+/// final `\n`, and the receiver ends once the transfer has finished.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::LinesHandler;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let (handler, mut lines) = LinesHandler::new(16, 64 * 1024);
+/// let transfer = AsyncCurl::new(CurlActor::new(), handler)
+///     .url("https://example.com/logs.ndjson")?
+///     .finalize()
+///     .perform_lines();
+///
+/// let consumer = async move {
+///     while let Some(line) = lines.recv().await {
+///         println!("{line}");
+///     }
+/// };
+/// let (_transfer, _consumer) = tokio::join!(transfer, consumer);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LinesHandler {
+    sink: ChannelSink<String>,
+    max_line_len: usize,
+    /// The bytes of the line that is not complete yet.
+    line: Vec<u8>,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl Handler for LinesHandler {
+    /// This will split the chunk into lines, or pause the transfer if the
+    /// receiver has not caught up with the lines of the last chunk yet.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        match self.sink.flush() {
+            Flush::Done => {}
+            Flush::Full => return Err(WriteError::Pause),
+            Flush::Closed => return Ok(0),
+        }
+        for &byte in data {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.line);
+                self.sink.push(decode_line(&line));
+            } else if self.line.len() < self.max_line_len {
+                self.line.push(byte);
+            } else {
+                self.exceeded.store(true, Ordering::SeqCst);
+                return Ok(0);
+            }
+        }
+        // The lines that don't fit are sent before the next chunk is taken.
+        match self.sink.flush() {
+            Flush::Done | Flush::Full => Ok(data.len()),
+            Flush::Closed => Ok(0),
+        }
+    }
+}
+
+impl LinesHandler {
+    /// Creates the handler together with the receiver of the lines, holding
+    /// at most `capacity` lines in between and accepting lines of at most
+    /// `max_line_len` bytes.
+    pub fn new(capacity: usize, max_line_len: usize) -> (Self, Receiver<String>) {
+        let (sink, receiver) = ChannelSink::new(capacity);
+        (
+            Self {
+                sink,
+                max_line_len,
+                line: Vec::new(),
+                exceeded: Arc::default(),
+            },
+            receiver,
+        )
+    }
+
+    /// Returns whether a line went past `max_line_len`.
+    pub fn limit_exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::SeqCst)
+    }
+
+    /// Returns a flag that is set once a line goes past `max_line_len`,
+    /// which stays readable after a failed transfer.
+    pub fn exceeded_flag(&self) -> Arc<AtomicBool> {
+        self.exceeded.clone()
+    }
+
+    /// Ends the line stream once the lines of the last chunk were sent,
+    /// waiting for room in the channel, so the receiver returns `None` after
+    /// them. The last line is sent even if it did not end with `\n`.
+    pub async fn close(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.sink.push(decode_line(&line));
+        }
+        self.sink.close().await;
+    }
+}
+
+/// Decodes a line without its `\r\n` or `\n` ending.
+fn decode_line(line: &[u8]) -> String {
+    String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned()
+}
+
+/// How far `ChannelSink::flush` got.
+pub(crate) enum Flush {
+    /// Every item was sent.
+    Done,
+    /// The channel is full, the transfer is woken up once there is room.
+    Full,
+    /// The receiver is gone.
+    Closed,
+}
+
+type ReserveFuture<T> = Pin<Box<dyn Future<Output = Result<OwnedPermit<T>, SendError<()>>> + Send>>;
+
+/// ChannelSink sends the items a handler parsed out of the body to a channel,
+/// keeping the ones that don't fit until there is room, which wakes up the
+/// transfer paused in the meantime.
+pub(crate) struct ChannelSink<T> {
+    sender: Option<Sender<T>>,
+    pending: VecDeque<T>,
+    reserve: Option<ReserveFuture<T>>,
+}
+
+impl<T> ChannelSink<T>
+where
+    T: Send + 'static,
+{
+    pub(crate) fn new(capacity: usize) -> (Self, Receiver<T>) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        (
+            Self {
+                sender: Some(sender),
+                pending: VecDeque::new(),
+                reserve: None,
+            },
+            receiver,
+        )
+    }
+
+    /// Queues an item to be sent by the next `flush`.
+    pub(crate) fn push(&mut self, item: T) {
+        self.pending.push_back(item);
+    }
+
+    /// Sends the queued items while the channel has room.
+    pub(crate) fn flush(&mut self) -> Flush {
+        let Some(sender) = &self.sender else {
+            return Flush::Closed;
+        };
+        while !self.pending.is_empty() {
+            let reserve = self
+                .reserve
+                .get_or_insert_with(|| Box::pin(sender.clone().reserve_owned()));
+            let waker = actor::transfer_waker();
+            match reserve.as_mut().poll(&mut Context::from_waker(&waker)) {
+                Poll::Ready(Ok(permit)) => {
+                    self.reserve = None;
+                    if let Some(item) = self.pending.pop_front() {
+                        permit.send(item);
+                    }
+                }
+                Poll::Ready(Err(_)) => return Flush::Closed,
+                Poll::Pending => return Flush::Full,
+            }
+        }
+        Flush::Done
+    }
+
+    /// Sends the queued items, waiting for room in the channel,
+    /// and ends the channel.
+    pub(crate) async fn close(&mut self) {
+        self.reserve = None;
+        if let Some(sender) = self.sender.take() {
+            for item in self.pending.drain(..) {
+                if sender.send(item).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl<T> Debug for ChannelSink<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChannelSink")
+            .field("closed", &self.sender.is_none())
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+/// FileHandler writes the response body straight into a file as it
+/// arrives, without keeping the body in memory.
+///
+/// It is normally created for you by `AsyncCurl::download_to`.
+#[derive(Debug, Default)]
+pub struct FileHandler {
+    file: Option<File>,
+    written: u64,
+    total_size: Option<u64>,
+    /// The status code of the HTTP response the body belongs to.
+    status: Option<u16>,
+    /// Set when the handler only takes `206 Partial Content`, to tell that
+    /// it aborted the transfer because the server answered otherwise.
+    range_refused: Option<Arc<AtomicBool>>,
+}
+
+impl Handler for FileHandler {
+    /// This will append the chunk to the file, aborting the transfer
+    /// with `Error::HandlerAborted` if the file cannot be written.
+    ///
+    /// The body of an HTTP response that is not a success, such as an error
+    /// page or a redirect, is skipped so it never ends up in the file.
+    /// A handler made by `partial_only` aborts on any response but
+    /// `206 Partial Content` instead.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        if let Some(refused) = &self.range_refused {
+            if self.status != Some(206) {
+                refused.store(true, Ordering::SeqCst);
+                return Ok(0);
+            }
+        }
+        if self
+            .status
+            .is_some_and(|status| !(200..300).contains(&status))
+        {
+            return Ok(data.len());
+        }
+        let Some(file) = &mut self.file else {
+            return Ok(0);
+        };
+        match file.write_all(data) {
+            Ok(()) => {
+                self.written += data.len() as u64;
+                Ok(data.len())
+            }
+            Err(err) => {
+                log::trace!("{err}");
+                Ok(0)
+            }
+        }
+    }
+
+    /// This will remember the full size of the resource given by the
+    /// `Content-Range` header of the final response.
+    fn header(&mut self, data: &[u8]) -> bool {
+        let line = String::from_utf8_lossy(data);
+        if line.starts_with("HTTP/") {
+            self.total_size = None;
+            self.status = parse_status_line(data);
+        } else if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-range") {
+                self.total_size = value
+                    .rsplit_once('/')
+                    .and_then(|(_, total)| total.trim().parse().ok());
+            }
+        }
+        true
+    }
+}
+
+impl FileHandler {
+    /// Creates a handler that has no file yet, the file is
+    /// opened by `AsyncCurl::download_to`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a handler that writes the body into the given file.
+    pub fn with_file(file: File) -> Self {
+        Self {
+            file: Some(file),
+            ..Default::default()
+        }
+    }
+
+    /// Makes the handler abort the transfer on the first body bytes of any
+    /// response but `206 Partial Content`, setting `refused` to tell so.
+    pub(crate) fn partial_only(mut self, refused: Arc<AtomicBool>) -> Self {
+        self.range_refused = Some(refused);
+        self
+    }
+
+    /// Returns the number of body bytes written to the file so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Returns the full size of the resource as given by the `Content-Range`
+    /// header of a partial response, `None` without the header.
+    pub fn total_size(&self) -> Option<u64> {
+        self.total_size
+    }
+}
+
+/// CallbackHandler wraps another [`Handler`] and lets the builder of
+/// `AsyncCurl` install extra callbacks, such as the upload source of
+/// `AsyncCurl::upload_stream`, on top of it. Every other callback is
+/// forwarded to the wrapped handler.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::{CallbackHandler, CollectingHandler};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let file = tokio::fs::File::open("large.bin").await?;
+/// let len = file.metadata().await?.len();
+///
+/// let handler = CallbackHandler::new(CollectingHandler::new());
+/// let easy = AsyncCurl::new(CurlActor::new(), handler)
+///     .url("https://example.com/upload")?
+///     .upload_stream(file, Some(len))?
+///     .finalize()
+///     .perform()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CallbackHandler<H> {
+    inner: H,
+    upload: Option<Upload>,
+    progress: Option<Box<dyn FnMut(ProgressInfo) + Send>>,
+    header: Option<HeaderCallback>,
+    debug_log: Option<Vec<(InfoType, Vec<u8>)>>,
+    #[cfg(all(feature = "ssl-keylog", unix, not(target_os = "macos")))]
+    keylog: Option<crate::keylog::KeyLog>,
+}
+
+/// Where the body uploaded through a [`CallbackHandler`] comes from.
+enum Upload {
+    /// Installed by `AsyncCurl::upload_stream`.
+    Stream(Pin<Box<dyn AsyncRead + Send>>),
+    /// Installed by `AsyncCurl::upload_reader`.
+    Source(Box<dyn ReadSource>),
+}
+
+/// ReadSource feeds the body of an upload installed with
+/// `AsyncCurl::upload_reader` from the background thread of the actor.
+///
+/// It is implemented for every `Read + Seek` type, such as a `File`
+/// or a `Cursor` over a buffer.
+pub trait ReadSource: Send {
+    /// Fills `buf` with the next bytes of the body, returning how many
+    /// were written, and 0 once the body is complete.
+    ///
+    /// This corresponds to `CURLOPT_READFUNCTION`.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ReadError>;
+
+    /// Moves to another position in the body, so libcurl can send it again
+    /// when it follows a redirect or retries on a fresh connection.
+    ///
+    /// By default the body cannot be rewound, and corresponds to
+    /// `CURLOPT_SEEKFUNCTION`.
+    fn seek(&mut self, whence: SeekFrom) -> SeekResult {
+        let _ = whence;
+        SeekResult::CantSeek
+    }
+}
+
+impl<R> ReadSource for R
+where
+    R: Read + Seek + Send,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ReadError> {
+        Read::read(self, buf).map_err(|err| {
+            log::trace!("{err}");
+            ReadError::Abort
+        })
+    }
+
+    fn seek(&mut self, whence: SeekFrom) -> SeekResult {
+        match Seek::seek(self, whence) {
+            Ok(_) => SeekResult::Ok,
+            Err(err) => {
+                log::trace!("{err}");
+                SeekResult::Fail
+            }
+        }
+    }
+}
+
+/// The callback installed by `AsyncCurl::on_header`.
+type HeaderCallback = Box<dyn FnMut(&[u8]) -> bool + Send>;
+
+/// The progress of a transfer as reported by libcurl, in bytes.
+/// The totals are 0 while they are not known yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressInfo {
+    pub download_total: u64,
+    pub downloaded: u64,
+    pub upload_total: u64,
+    pub uploaded: u64,
+}
+
+impl<H> Handler for CallbackHandler<H>
+where
+    H: Handler,
+{
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.inner.write(data)
+    }
+
+    /// This will feed the upload from the installed reader or source, pausing
+    /// the transfer until a reader is ready, or fall back to the wrapped handler.
+    fn read(&mut self, data: &mut [u8]) -> Result<usize, ReadError> {
+        let reader = match &mut self.upload {
+            Some(Upload::Stream(reader)) => reader,
+            Some(Upload::Source(source)) => return source.read(data),
+            None => return self.inner.read(data),
+        };
+        let waker = actor::transfer_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = ReadBuf::new(data);
+        match reader.as_mut().poll_read(&mut cx, &mut buf) {
+            Poll::Ready(Ok(())) => Ok(buf.filled().len()),
+            Poll::Ready(Err(err)) => {
+                log::trace!("{err}");
+                Err(ReadError::Abort)
+            }
+            Poll::Pending => Err(ReadError::Pause),
+        }
+    }
+
+    fn seek(&mut self, whence: SeekFrom) -> SeekResult {
+        match &mut self.upload {
+            // A stream cannot be rewound, libcurl falls back to reading ahead.
+            Some(Upload::Stream(_)) => SeekResult::CantSeek,
+            Some(Upload::Source(source)) => source.seek(whence),
+            None => self.inner.seek(whence),
+        }
+    }
+
+    /// This will record the debug output when it is captured,
+    /// instead of handing it to the wrapped handler.
+    fn debug(&mut self, kind: InfoType, data: &[u8]) {
+        match &mut self.debug_log {
+            Some(debug_log) => debug_log.push((kind, data.to_vec())),
+            None => self.inner.debug(kind, data),
+        }
+    }
+
+    /// This will hand the header line to the installed callback, stopping
+    /// the transfer when it returns false, before the wrapped handler.
+    fn header(&mut self, data: &[u8]) -> bool {
+        if let Some(header) = &mut self.header {
+            if !header(data) {
+                return false;
+            }
+        }
+        self.inner.header(data)
+    }
+
+    /// This will report the progress to the installed callback
+    /// before handing it to the wrapped handler.
+    fn progress(&mut self, dltotal: f64, dlnow: f64, ultotal: f64, ulnow: f64) -> bool {
+        if let Some(progress) = &mut self.progress {
+            progress(ProgressInfo {
+                download_total: dltotal as u64,
+                downloaded: dlnow as u64,
+                upload_total: ultotal as u64,
+                uploaded: ulnow as u64,
+            });
+        }
+        self.inner.progress(dltotal, dlnow, ultotal, ulnow)
+    }
+
+    fn ssl_ctx(&mut self, cx: *mut c_void) -> Result<(), curl::Error> {
+        self.inner.ssl_ctx(cx)?;
+        #[cfg(all(feature = "ssl-keylog", unix, not(target_os = "macos")))]
+        if let Some(keylog) = &self.keylog {
+            crate::keylog::install(cx, keylog)?;
+        }
+        Ok(())
+    }
+
+    fn open_socket(
+        &mut self,
+        family: c_int,
+        socktype: c_int,
+        protocol: c_int,
+    ) -> Option<curl_sys::curl_socket_t> {
+        self.inner.open_socket(family, socktype, protocol)
+    }
+}
+
+impl<H> CallbackHandler<H> {
+    /// Wraps the handler without any extra callback installed yet.
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            upload: None,
+            progress: None,
+            header: None,
+            debug_log: None,
+            #[cfg(all(feature = "ssl-keylog", unix, not(target_os = "macos")))]
+            keylog: None,
+        }
+    }
+
+    /// Returns the wrapped handler.
+    pub fn get_ref(&self) -> &H {
+        &self.inner
+    }
+
+    /// Returns the wrapped handler mutably.
+    pub fn get_mut(&mut self) -> &mut H {
+        &mut self.inner
+    }
+
+    /// Consumes the wrapper and returns the wrapped handler.
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+
+    /// Returns the debug output recorded since `AsyncCurl::capture_debug`,
+    /// in the order libcurl produced it.
+    pub fn debug_log(&self) -> &[(InfoType, Vec<u8>)] {
+        self.debug_log.as_deref().unwrap_or_default()
+    }
+
+    /// Takes the recorded debug output, leaving the capture running.
+    pub fn take_debug_log(&mut self) -> Vec<(InfoType, Vec<u8>)> {
+        self.debug_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_reader<R>(&mut self, reader: R)
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        self.upload = Some(Upload::Stream(Box::pin(reader)));
+    }
+
+    pub(crate) fn set_source<R>(&mut self, source: R)
+    where
+        R: ReadSource + 'static,
+    {
+        self.upload = Some(Upload::Source(Box::new(source)));
+    }
+
+    pub(crate) fn set_progress<F>(&mut self, progress: F)
+    where
+        F: FnMut(ProgressInfo) + Send + 'static,
+    {
+        self.progress = Some(Box::new(progress));
+    }
+
+    pub(crate) fn set_header<F>(&mut self, header: F)
+    where
+        F: FnMut(&[u8]) -> bool + Send + 'static,
+    {
+        self.header = Some(Box::new(header));
+    }
+
+    pub(crate) fn capture_debug(&mut self) {
+        self.debug_log.get_or_insert_with(Vec::new);
+    }
+
+    #[cfg(all(feature = "ssl-keylog", unix, not(target_os = "macos")))]
+    pub(crate) fn set_keylog(&mut self, keylog: crate::keylog::KeyLog) {
+        self.keylog = Some(keylog);
+    }
+}
+
+impl<H> Debug for CallbackHandler<H>
+where
+    H: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallbackHandler")
+            .field("inner", &self.inner)
+            .field("upload", &self.upload.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("header", &self.header.is_some())
+            .field("debug_log", &self.debug_log.as_ref().map(Vec::len))
+            .finish()
+    }
+}
+
+/// DecodingHandler wraps another [`Handler`] and records the
+/// `Content-Encoding` of the response and how many bytes of body reached
+/// the wrapped handler once libcurl decompressed it.
+///
+/// Use `AsyncCurl::perform_decoded` together with `accept_encoding` to
+/// have a body libcurl could not decode reported as an error.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::{CollectingHandler, DecodingHandler};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let handler = DecodingHandler::new(CollectingHandler::new());
+/// let easy = AsyncCurl::new(CurlActor::new(), handler)
+///     .url("https://www.rust-lang.org/")?
+///     .accept_encoding("")?
+///     .finalize()
+///     .perform_decoded()
+///     .await?;
+/// println!(
+///     "{} bytes decoded from {:?}",
+///     easy.get_ref().decoded_len(),
+///     easy.get_ref().content_encoding()
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DecodingHandler<H> {
+    inner: H,
+    content_encoding: Option<String>,
+    decoded_len: u64,
+}
+
+impl<H> Handler for DecodingHandler<H>
+where
+    H: Handler,
+{
+    /// This will count the decoded bytes the wrapped handler accepted.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        let written = self.inner.write(data)?;
+        self.decoded_len += written as u64;
+        Ok(written)
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> Result<usize, ReadError> {
+        self.inner.read(data)
+    }
+
+    fn seek(&mut self, whence: SeekFrom) -> SeekResult {
+        self.inner.seek(whence)
+    }
+
+    fn debug(&mut self, kind: InfoType, data: &[u8]) {
+        self.inner.debug(kind, data)
+    }
+
+    /// This will record the `Content-Encoding` of the last response,
+    /// before handing the header line to the wrapped handler.
+    fn header(&mut self, data: &[u8]) -> bool {
+        if data.starts_with(b"HTTP/") {
+            // A redirect or an interim response came before this one.
+            self.content_encoding = None;
+            self.decoded_len = 0;
+        } else if let Some((name, value)) = parse_header_line(data) {
+            if name.eq_ignore_ascii_case("content-encoding") {
+                self.content_encoding = Some(value.to_ascii_lowercase());
+            }
+        }
+        self.inner.header(data)
+    }
+
+    fn progress(&mut self, dltotal: f64, dlnow: f64, ultotal: f64, ulnow: f64) -> bool {
+        self.inner.progress(dltotal, dlnow, ultotal, ulnow)
+    }
+
+    fn ssl_ctx(&mut self, cx: *mut c_void) -> Result<(), curl::Error> {
+        self.inner.ssl_ctx(cx)
+    }
+
+    fn open_socket(
+        &mut self,
+        family: c_int,
+        socktype: c_int,
+        protocol: c_int,
+    ) -> Option<curl_sys::curl_socket_t> {
+        self.inner.open_socket(family, socktype, protocol)
+    }
+}
+
+impl<H> DecodingHandler<H> {
+    /// Wraps the handler that receives the decoded body.
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            content_encoding: None,
+            decoded_len: 0,
+        }
+    }
+
+    /// Returns the `Content-Encoding` header of the response in lower case,
+    /// e.g. `gzip`, or `None` if the body was sent as is.
+    pub fn content_encoding(&self) -> Option<&str> {
+        self.content_encoding.as_deref()
+    }
+
+    /// Returns how many bytes of the decoded body the wrapped handler took.
+    pub fn decoded_len(&self) -> u64 {
+        self.decoded_len
+    }
+
+    /// Returns the wrapped handler.
+    pub fn get_ref(&self) -> &H {
+        &self.inner
+    }
+
+    /// Returns the wrapped handler mutably.
+    pub fn get_mut(&mut self) -> &mut H {
+        &mut self.inner
+    }
+
+    /// Consumes the wrapper and returns the wrapped handler.
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+}
+
+/// TeeHandler forwards the body and the headers to two handlers, e.g. a
+/// [`FileHandler`] and a handler hashing the body, so both see it in one
+/// pass without buffering it.
+///
+/// The transfer stops when either handler takes fewer bytes than it was
+/// given or rejects a header. If the second handler pauses the transfer,
+/// the first one is not given the same chunk again once it resumes.
+/// The progress and debug callbacks reach both handlers, while the upload
+/// and connection callbacks only reach the first one.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::{CollectingHandler, FileHandler, TeeHandler};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let file = std::fs::File::create("rust-lang.html")?;
+/// let handler = TeeHandler::new(FileHandler::with_file(file), CollectingHandler::new());
+/// let easy = AsyncCurl::new(CurlActor::new(), handler)
+///     .url("https://www.rust-lang.org/")?
+///     .finalize()
+///     .perform()
+///     .await?;
+/// let (file, body) = easy.get_ref().get_ref();
+/// println!("{} bytes written, {} kept", file.written(), body.body().len());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TeeHandler<A, B> {
+    first: A,
+    second: B,
+    /// The first handler already took the chunk the second one paused on.
+    first_written: bool,
+}
+
+impl<A, B> Handler for TeeHandler<A, B>
+where
+    A: Handler,
+    B: Handler,
+{
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        if !self.first_written {
+            let written = self.first.write(data)?;
+            if written != data.len() {
+                return Ok(written);
+            }
+            self.first_written = true;
+        }
+        let written = self.second.write(data)?;
+        self.first_written = false;
+        Ok(written)
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> Result<usize, ReadError> {
+        self.first.read(data)
+    }
+
+    fn seek(&mut self, whence: SeekFrom) -> SeekResult {
+        self.first.seek(whence)
+    }
+
+    fn debug(&mut self, kind: InfoType, data: &[u8]) {
+        self.first.debug(kind, data);
+        self.second.debug(kind, data);
+    }
+
+    fn header(&mut self, data: &[u8]) -> bool {
+        let first = self.first.header(data);
+        let second = self.second.header(data);
+        first && second
+    }
+
+    fn progress(&mut self, dltotal: f64, dlnow: f64, ultotal: f64, ulnow: f64) -> bool {
+        let first = self.first.progress(dltotal, dlnow, ultotal, ulnow);
+        let second = self.second.progress(dltotal, dlnow, ultotal, ulnow);
+        first && second
+    }
+
+    fn ssl_ctx(&mut self, cx: *mut c_void) -> Result<(), curl::Error> {
+        self.first.ssl_ctx(cx)
+    }
+
+    fn open_socket(
+        &mut self,
+        family: c_int,
+        socktype: c_int,
+        protocol: c_int,
+    ) -> Option<curl_sys::curl_socket_t> {
+        self.first.open_socket(family, socktype, protocol)
+    }
+}
+
+impl<A, B> TeeHandler<A, B> {
+    /// Combines the two handlers, `first` being given every chunk first.
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            first_written: false,
+        }
+    }
+
+    /// Returns both handlers.
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.first, &self.second)
+    }
+
+    /// Returns both handlers mutably.
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.first, &mut self.second)
+    }
+
+    /// Consumes the tee and returns both handlers.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+/// CatchUnwindHandler runs every callback of the wrapped handler inside
+/// `catch_unwind`, so a panic in one of them fails only its own transfer,
+/// with `Error::HandlerPanic` carrying the panic message, while the worker
+/// of the [`CurlActor`](crate::actor::CurlActor) goes on with the others.
+///
+/// Once a callback panicked, the wrapped handler is not called anymore and
+/// every callback aborts the transfer. A handler that is not wrapped must
+/// not panic: the curl crate catches the panic itself and fails every later
+/// callback on the worker thread.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::error::Error;
+/// use async_curl::handler::{CatchUnwindHandler, CollectingHandler};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let handler = CatchUnwindHandler::new(CollectingHandler::new());
+/// match AsyncCurl::new(CurlActor::new(), handler)
+///     .url("https://www.rust-lang.org/")?
+///     .finalize()
+///     .perform()
+///     .await
+/// {
+///     Ok(easy) => println!("{} bytes", easy.get_ref().get_ref().body().len()),
+///     Err(Error::HandlerPanic(message)) => eprintln!("the handler panicked: {message}"),
+///     Err(err) => return Err(err.into()),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CatchUnwindHandler<H> {
+    inner: H,
+    /// The message of the panic of a callback, after which the wrapped
+    /// handler is not called anymore.
+    panic: Option<String>,
+}
+
+impl<H> Handler for CatchUnwindHandler<H>
+where
+    H: Handler,
+{
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.catch(|inner| inner.write(data)).unwrap_or(Ok(0))
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> Result<usize, ReadError> {
+        self.catch(|inner| inner.read(data))
+            .unwrap_or(Err(ReadError::Abort))
+    }
+
+    fn seek(&mut self, whence: SeekFrom) -> SeekResult {
+        self.catch(|inner| inner.seek(whence))
+            .unwrap_or(SeekResult::Fail)
+    }
+
+    fn debug(&mut self, kind: InfoType, data: &[u8]) {
+        self.catch(|inner| inner.debug(kind, data));
+    }
+
+    fn header(&mut self, data: &[u8]) -> bool {
+        self.catch(|inner| inner.header(data)).unwrap_or(false)
+    }
+
+    fn progress(&mut self, dltotal: f64, dlnow: f64, ultotal: f64, ulnow: f64) -> bool {
+        self.catch(|inner| inner.progress(dltotal, dlnow, ultotal, ulnow))
+            .unwrap_or(false)
+    }
+
+    fn ssl_ctx(&mut self, cx: *mut c_void) -> Result<(), curl::Error> {
+        self.catch(|inner| inner.ssl_ctx(cx))
+            .unwrap_or_else(|| Err(curl::Error::new(curl_sys::CURLE_ABORTED_BY_CALLBACK)))
+    }
+
+    fn open_socket(
+        &mut self,
+        family: c_int,
+        socktype: c_int,
+        protocol: c_int,
+    ) -> Option<curl_sys::curl_socket_t> {
+        self.catch(|inner| inner.open_socket(family, socktype, protocol))
+            .flatten()
+    }
+}
+
+impl<H> CatchUnwindHandler<H> {
+    /// Wraps the handler, none of its callbacks having panicked yet.
+    pub fn new(inner: H) -> Self {
+        Self { inner, panic: None }
+    }
+
+    /// Returns the wrapped handler.
+    pub fn get_ref(&self) -> &H {
+        &self.inner
+    }
+
+    /// Returns the wrapped handler mutably.
+    pub fn get_mut(&mut self) -> &mut H {
+        &mut self.inner
+    }
+
+    /// Consumes the wrapper and returns the wrapped handler.
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+
+    /// Returns the message of the panic of a callback, if one panicked.
+    pub fn panic_message(&self) -> Option<&str> {
+        self.panic.as_deref()
+    }
+
+    /// Runs the callback unless one panicked before, returning `None`
+    /// to abort the transfer if it did or if this one panics.
+    fn catch<T>(&mut self, callback: impl FnOnce(&mut H) -> T) -> Option<T> {
+        if self.panic.is_some() {
+            return None;
+        }
+        let inner = &mut self.inner;
+        match std::panic::catch_unwind(AssertUnwindSafe(|| callback(inner))) {
+            Ok(result) => Some(result),
+            Err(payload) => {
+                let message = match payload.downcast::<String>() {
+                    Ok(message) => *message,
+                    Err(payload) => payload
+                        .downcast_ref::<&str>()
+                        .map_or_else(|| "Box<dyn Any>".to_string(), |message| message.to_string()),
+                };
+                actor::record_handler_panic(message.clone());
+                self.panic = Some(message);
+                None
+            }
+        }
+    }
+}
+
+/// HashingHandler computes a digest of the body as it arrives, SHA-256
+/// unless another [`sha2::Digest`] is given, without keeping the body.
+///
+/// Put it behind a [`TeeHandler`] to check a download against the checksum
+/// it was published with while it is written to a file.
+/// The digest starts over with each response, so only the body of the last
+/// one, e.g. after a redirect, is hashed.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::{FileHandler, HashingHandler, TeeHandler};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let file = std::fs::File::create("release.tar.gz")?;
+/// let handler = TeeHandler::new(FileHandler::with_file(file), HashingHandler::sha256());
+/// let easy = AsyncCurl::new(CurlActor::new(), handler)
+///     .url("https://example.com/release.tar.gz")?
+///     .finalize()
+///     .perform()
+///     .await?;
+/// let (_, hash) = easy.get_ref().get_ref();
+/// if !hash.matches_hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855") {
+///     return Err("the checksum of the download does not match".into());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "sha2")]
+#[derive(Debug, Clone, Default)]
+pub struct HashingHandler<D = sha2::Sha256> {
+    hasher: D,
+    len: u64,
+}
+
+#[cfg(feature = "sha2")]
+impl<D> Handler for HashingHandler<D>
+where
+    D: sha2::Digest + sha2::digest::Reset,
+{
+    /// This will add the chunk to the digest.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.hasher.update(data);
+        self.len += data.len() as u64;
+        Ok(data.len())
+    }
+
+    /// This will start the digest over at the status line of a response.
+    fn header(&mut self, data: &[u8]) -> bool {
+        if data.starts_with(b"HTTP/") {
+            sha2::Digest::reset(&mut self.hasher);
+            self.len = 0;
+        }
+        true
+    }
+}
+
+#[cfg(feature = "sha2")]
+impl HashingHandler {
+    /// Instantiation of the HashingHandler computing a SHA-256 digest.
+    pub fn sha256() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "sha2")]
+impl<D> HashingHandler<D>
+where
+    D: sha2::Digest + Clone,
+{
+    /// Instantiation of the HashingHandler with the digest `D`, e.g.
+    /// `HashingHandler::<sha2::Sha512>::new()`.
+    pub fn new() -> Self {
+        Self {
+            hasher: D::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the digest of the body received so far.
+    pub fn digest(&self) -> Vec<u8> {
+        self.hasher.clone().finalize().to_vec()
+    }
+
+    /// Returns the digest of the body received so far as lowercase hex.
+    pub fn digest_hex(&self) -> String {
+        self.digest().iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Returns whether the digest is the one given in hex, in either case.
+    pub fn matches_hex(&self, expected: &str) -> bool {
+        self.digest_hex().eq_ignore_ascii_case(expected.trim())
+    }
+
+    /// Returns how many bytes of the body were hashed.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether no byte of the body was hashed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Extracts the `charset` parameter of a `Content-Type` header value.
+fn charset(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("charset") {
+            Some(value.trim().trim_matches('"'))
+        } else {
+            None
+        }
+    })
+}
+
+/// Decodes the body with the single-byte charsets that are still common
+/// on older APIs, or as UTF-8 for everything else.
+fn decode_text(body: &[u8], charset: Option<&str>) -> String {
+    match charset
+        .map(|charset| charset.to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("iso-8859-1" | "iso8859-1" | "latin1" | "latin-1" | "l1") => {
+            body.iter().map(|b| char::from(*b)).collect()
+        }
+        Some("windows-1252" | "cp1252") => body.iter().map(|b| windows_1252(*b)).collect(),
+        _ => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+/// Windows-1252 is ISO-8859-1 except for the 0x80-0x9F range.
+fn windows_1252(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '\u{20AC}', '\u{FFFD}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}',
+        '\u{2021}', '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{FFFD}',
+        '\u{017D}', '\u{FFFD}', '\u{FFFD}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}',
+        '\u{2022}', '\u{2013}', '\u{2014}', '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}',
+        '\u{0153}', '\u{FFFD}', '\u{017E}', '\u{0178}',
+    ];
+    match byte {
+        0x80..=0x9F => HIGH[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
@@ -0,0 +1,384 @@
+use std::ffi::c_long;
+use std::fmt::Debug;
+use std::time::Duration;
+
+use curl::easy::{Easy2, Handler};
+
+use crate::error::Error;
+
+const CURLINFO_STRING: curl_sys::CURLINFO = 0x100000;
+const CURLINFO_OFF_T: curl_sys::CURLINFO = 0x600000;
+/// `CURLINFO_SIZE_UPLOAD_T`, which curl-sys does not define.
+const CURLINFO_SIZE_UPLOAD_T: curl_sys::CURLINFO = CURLINFO_OFF_T + 7;
+/// `CURLINFO_SIZE_DOWNLOAD_T`, which curl-sys does not define.
+const CURLINFO_SIZE_DOWNLOAD_T: curl_sys::CURLINFO = CURLINFO_OFF_T + 8;
+/// `CURLINFO_CONTENT_LENGTH_DOWNLOAD_T`, which curl-sys does not define.
+const CURLINFO_CONTENT_LENGTH_DOWNLOAD_T: curl_sys::CURLINFO = CURLINFO_OFF_T + 15;
+/// `CURLINFO_CONTENT_LENGTH_UPLOAD_T`, which curl-sys does not define.
+const CURLINFO_CONTENT_LENGTH_UPLOAD_T: curl_sys::CURLINFO = CURLINFO_OFF_T + 16;
+/// `CURLINFO_RETRY_AFTER`, which curl-sys does not define.
+const CURLINFO_RETRY_AFTER: curl_sys::CURLINFO = CURLINFO_OFF_T + 57;
+/// `CURLINFO_EFFECTIVE_METHOD`, which curl-sys does not define.
+const CURLINFO_EFFECTIVE_METHOD: curl_sys::CURLINFO = CURLINFO_STRING + 58;
+
+/// The time spent in each phase of a transfer, measured from its start
+/// as `CURLINFO_*_TIME` does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// Until the host name was resolved.
+    pub namelookup: Duration,
+    /// Until the connection to the host or proxy was made.
+    pub connect: Duration,
+    /// Until the TLS handshake was done, zero without TLS.
+    pub appconnect: Duration,
+    /// Until the request was about to be sent.
+    pub pretransfer: Duration,
+    /// Until the first byte of the response was received.
+    pub starttransfer: Duration,
+    /// The whole transfer, including redirects.
+    pub total: Duration,
+    /// All the redirects before the final transfer started.
+    pub redirect: Duration,
+}
+
+/// How many bytes a transfer moved, next to the sizes announced for it,
+/// e.g. to tell a response cut short by a dropped connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferSizes {
+    /// The bytes of body downloaded.
+    pub download: u64,
+    /// The bytes uploaded.
+    pub upload: u64,
+    /// The size of the download announced by the server, if known.
+    pub content_length_download: Option<u64>,
+    /// The size of the upload given to libcurl, if known.
+    pub content_length_upload: Option<u64>,
+}
+
+impl TransferSizes {
+    /// Returns true if fewer bytes were downloaded than the server announced.
+    pub fn is_truncated(&self) -> bool {
+        self.content_length_download
+            .is_some_and(|length| self.download < length)
+    }
+}
+
+/// Where a transfer was connected to and from, and whether it could
+/// reuse a connection that was already open.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// The IP address of the host or proxy that was connected to.
+    pub primary_ip: Option<String>,
+    /// The port of the host or proxy that was connected to.
+    pub primary_port: u16,
+    /// The local IP address of the connection.
+    pub local_ip: Option<String>,
+    /// The local port of the connection.
+    pub local_port: u16,
+    /// How many new connections had to be opened for the transfer.
+    pub new_connections: u32,
+}
+
+impl ConnectionInfo {
+    /// Returns true if the transfer went over a connection that was
+    /// already open, without opening a new one.
+    pub fn is_reused(&self) -> bool {
+        self.new_connections == 0
+    }
+}
+
+/// A certificate of the chain the server presented, as gathered by
+/// `AsyncCurl::certinfo`. The values are given as the TLS backend
+/// formats them, e.g. the dates as `Jan  1 00:00:00 2030 GMT` with OpenSSL.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CertInfo {
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+    pub start_date: Option<String>,
+    pub expire_date: Option<String>,
+    pub public_key_algorithm: Option<String>,
+    pub signature_algorithm: Option<String>,
+    pub signature: Option<String>,
+    /// The certificate itself, PEM encoded.
+    pub cert: Option<String>,
+    /// Every field reported by the TLS backend, including the ones above,
+    /// in the order it reported them.
+    pub fields: Vec<(String, String)>,
+}
+
+impl CertInfo {
+    /// Builds a certificate from its `name:value` entries.
+    fn parse<'a>(entries: impl Iterator<Item = &'a str>) -> Self {
+        let mut cert = CertInfo::default();
+        for entry in entries {
+            let Some((name, value)) = entry.split_once(':') else {
+                continue;
+            };
+            let field = match name {
+                "Subject" => &mut cert.subject,
+                "Issuer" => &mut cert.issuer,
+                "Start date" => &mut cert.start_date,
+                "Expire date" => &mut cert.expire_date,
+                "Public Key Algorithm" => &mut cert.public_key_algorithm,
+                "Signature Algorithm" => &mut cert.signature_algorithm,
+                "Signature" => &mut cert.signature,
+                "Cert" => &mut cert.cert,
+                _ => &mut None,
+            };
+            *field = Some(value.to_string());
+            cert.fields.push((name.to_string(), value.to_string()));
+        }
+        cert
+    }
+}
+
+/// TransferInfo reads what libcurl learned about a finished transfer
+/// from the `Easy2` returned by `perform` or `send_request`.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::CollectingHandler;
+/// use async_curl::info::TransferInfo;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let easy = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+///     .url("https://www.rust-lang.org/")?
+///     .finalize()
+///     .perform()
+///     .await?;
+///
+/// let timings = easy.timings()?;
+/// eprintln!("Time to first byte: {:?}", timings.starttransfer);
+/// # Ok(())
+/// # }
+/// ```
+pub trait TransferInfo<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    /// Returns the time spent in each phase of the transfer.
+    fn timings(&self) -> Result<Timings, Error<H>>;
+
+    /// Returns how long the server asked the client to wait through the
+    /// `Retry-After` header, given in seconds or as an HTTP-date.
+    ///
+    /// This corresponds to `CURLINFO_RETRY_AFTER`.
+    fn retry_after(&self) -> Result<Option<Duration>, Error<H>>;
+
+    /// Returns the addresses of the connection used by the transfer and how
+    /// many new connections it needed.
+    ///
+    /// This corresponds to `CURLINFO_PRIMARY_IP`, `CURLINFO_PRIMARY_PORT`,
+    /// `CURLINFO_LOCAL_IP`, `CURLINFO_LOCAL_PORT` and `CURLINFO_NUM_CONNECTS`.
+    fn connection_info(&self) -> Result<ConnectionInfo, Error<H>>;
+
+    /// Returns the certificate chain of the server, starting with its own
+    /// certificate, e.g. to log or pin it after the handshake.
+    ///
+    /// The chain is only gathered when `AsyncCurl::certinfo` was enabled,
+    /// and is empty otherwise or when the transfer did not use TLS.
+    ///
+    /// This corresponds to `CURLINFO_CERTINFO`.
+    fn cert_chain(&self) -> Result<Vec<CertInfo>, Error<H>>;
+
+    /// Returns the cookies held by the cookie engine of the handle, one
+    /// Netscape cookie file line each, e.g. to persist them elsewhere.
+    ///
+    /// This is empty unless the cookie engine was enabled, e.g. through
+    /// `AsyncCurl::cookie_file`, and corresponds to `CURLINFO_COOKIELIST`.
+    fn cookies(&self) -> Result<Vec<String>, Error<H>>;
+
+    /// Returns how many bytes the transfer downloaded and uploaded,
+    /// and the sizes that were announced for them, as `u64` unlike the
+    /// `f64` values of `Easy2::download_size` and its siblings.
+    ///
+    /// This corresponds to `CURLINFO_SIZE_DOWNLOAD_T`, `CURLINFO_SIZE_UPLOAD_T`,
+    /// `CURLINFO_CONTENT_LENGTH_DOWNLOAD_T` and `CURLINFO_CONTENT_LENGTH_UPLOAD_T`.
+    fn sizes(&self) -> Result<TransferSizes, Error<H>>;
+}
+
+impl<H> TransferInfo<H> for Easy2<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn timings(&self) -> Result<Timings, Error<H>> {
+        let timings = || -> Result<Timings, curl::Error> {
+            Ok(Timings {
+                namelookup: self.namelookup_time()?,
+                connect: self.connect_time()?,
+                appconnect: self.appconnect_time()?,
+                pretransfer: self.pretransfer_time()?,
+                starttransfer: self.starttransfer_time()?,
+                total: self.total_time()?,
+                redirect: self.redirect_time()?,
+            })
+        };
+        timings().map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })
+    }
+
+    fn retry_after(&self) -> Result<Option<Duration>, Error<H>> {
+        let seconds = getinfo_off_t(self, CURLINFO_RETRY_AFTER)?;
+        Ok((seconds > 0).then(|| Duration::from_secs(seconds as u64)))
+    }
+
+    fn connection_info(&self) -> Result<ConnectionInfo, Error<H>> {
+        let addresses = || -> Result<ConnectionInfo, curl::Error> {
+            Ok(ConnectionInfo {
+                primary_ip: self.primary_ip()?.map(str::to_string),
+                primary_port: self.primary_port()?,
+                local_ip: self.local_ip()?.map(str::to_string),
+                local_port: self.local_port()?,
+                new_connections: 0,
+            })
+        };
+        let mut info = addresses().map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        info.new_connections = getinfo_long(self, curl_sys::CURLINFO_NUM_CONNECTS)? as u32;
+        Ok(info)
+    }
+
+    fn cert_chain(&self) -> Result<Vec<CertInfo>, Error<H>> {
+        let mut certinfo: *const curl_sys::curl_certinfo = std::ptr::null();
+        let rc = unsafe {
+            curl_sys::curl_easy_getinfo(self.raw(), curl_sys::CURLINFO_CERTINFO, &mut certinfo)
+        };
+        if rc != curl_sys::CURLE_OK {
+            let err = curl::Error::new(rc);
+            log::trace!("{err}");
+            return Err(Error::Curl(err));
+        }
+        if certinfo.is_null() {
+            return Ok(Vec::new());
+        }
+        let certinfo = unsafe { &*certinfo };
+        let count = usize::try_from(certinfo.num_of_certs).unwrap_or_default();
+        let chain = (0..count)
+            .map(|i| {
+                let entries = slist_entries(unsafe { *certinfo.certinfo.add(i) });
+                CertInfo::parse(entries.iter().map(String::as_str))
+            })
+            .collect();
+        Ok(chain)
+    }
+
+    fn cookies(&self) -> Result<Vec<String>, Error<H>> {
+        let mut list: *mut curl_sys::curl_slist = std::ptr::null_mut();
+        let rc = unsafe {
+            curl_sys::curl_easy_getinfo(self.raw(), curl_sys::CURLINFO_COOKIELIST, &mut list)
+        };
+        if rc != curl_sys::CURLE_OK {
+            let err = curl::Error::new(rc);
+            log::trace!("{err}");
+            return Err(Error::Curl(err));
+        }
+        let cookies = slist_entries(list);
+        // Unlike the other lists, this one is handed over to the caller.
+        unsafe { curl_sys::curl_slist_free_all(list) };
+        Ok(cookies)
+    }
+
+    fn sizes(&self) -> Result<TransferSizes, Error<H>> {
+        // libcurl reports -1 when a content length is unknown.
+        let length = |info| getinfo_off_t(self, info).map(|value| u64::try_from(value).ok());
+        Ok(TransferSizes {
+            download: getinfo_off_t(self, CURLINFO_SIZE_DOWNLOAD_T)?.max(0) as u64,
+            upload: getinfo_off_t(self, CURLINFO_SIZE_UPLOAD_T)?.max(0) as u64,
+            content_length_download: length(CURLINFO_CONTENT_LENGTH_DOWNLOAD_T)?,
+            content_length_upload: length(CURLINFO_CONTENT_LENGTH_UPLOAD_T)?,
+        })
+    }
+}
+
+/// Copies the strings of a libcurl list.
+fn slist_entries(mut item: *const curl_sys::curl_slist) -> Vec<String> {
+    let mut entries = Vec::new();
+    while let Some(slist) = unsafe { item.as_ref() } {
+        if !slist.data.is_null() {
+            let data = unsafe { std::ffi::CStr::from_ptr(slist.data) };
+            entries.push(data.to_string_lossy().into_owned());
+        }
+        item = slist.next;
+    }
+    entries
+}
+
+/// Returns [`Error::TruncatedBody`] when the transfer received fewer bytes
+/// of body than its `Content-Length` announced.
+///
+/// HEAD requests are skipped, since their `Content-Length` describes a
+/// body that is never sent.
+pub(crate) fn verify_content_length<H>(easy: &Easy2<H>) -> Result<(), Error<H>>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    if effective_method(easy).as_deref() == Some("HEAD") {
+        return Ok(());
+    }
+    let sizes = easy.sizes()?;
+    match sizes.content_length_download {
+        Some(expected) if sizes.download != expected => {
+            log::trace!(
+                "Received {} of the {} bytes announced by Content-Length",
+                sizes.download,
+                expected
+            );
+            Err(Error::TruncatedBody {
+                expected,
+                received: sizes.download,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Returns the method of the last request sent by the transfer.
+pub(crate) fn effective_method<H>(easy: &Easy2<H>) -> Option<String>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    let mut value: *const std::ffi::c_char = std::ptr::null();
+    let rc =
+        unsafe { curl_sys::curl_easy_getinfo(easy.raw(), CURLINFO_EFFECTIVE_METHOD, &mut value) };
+    if rc != curl_sys::CURLE_OK || value.is_null() {
+        return None;
+    }
+    let method = unsafe { std::ffi::CStr::from_ptr(value) };
+    Some(method.to_string_lossy().into_owned())
+}
+
+fn getinfo_long<H>(easy: &Easy2<H>, info: curl_sys::CURLINFO) -> Result<c_long, Error<H>>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    let mut value: c_long = 0;
+    let rc = unsafe { curl_sys::curl_easy_getinfo(easy.raw(), info, &mut value) };
+    if rc != curl_sys::CURLE_OK {
+        let err = curl::Error::new(rc);
+        log::trace!("{err}");
+        return Err(Error::Curl(err));
+    }
+    Ok(value)
+}
+
+fn getinfo_off_t<H>(
+    easy: &Easy2<H>,
+    info: curl_sys::CURLINFO,
+) -> Result<curl_sys::curl_off_t, Error<H>>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    let mut value: curl_sys::curl_off_t = 0;
+    let rc = unsafe { curl_sys::curl_easy_getinfo(easy.raw(), info, &mut value) };
+    if rc != curl_sys::CURLE_OK {
+        let err = curl::Error::new(rc);
+        log::trace!("{err}");
+        return Err(Error::Curl(err));
+    }
+    Ok(value)
+}
@@ -0,0 +1,70 @@
+use std::ffi::{c_char, c_int, c_long, c_void, CStr};
+use std::fs::File;
+use std::io::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use openssl_sys::{SSL, SSL_CTX};
+
+/// The file the TLS session secrets of a transfer are appended to,
+/// in the NSS key log format understood by Wireshark.
+pub(crate) type KeyLog = Arc<Mutex<File>>;
+
+/// Makes OpenSSL report the secrets negotiated through `ssl_ctx` to `keylog`.
+///
+/// The SSL_CTX holds its own reference to the file, released by OpenSSL
+/// when the context is freed.
+pub(crate) fn install(ssl_ctx: *mut c_void, keylog: &KeyLog) -> Result<(), curl::Error> {
+    let ssl_ctx = ssl_ctx as *mut SSL_CTX;
+    let index = ex_index();
+    if index < 0 {
+        return Err(curl::Error::new(curl_sys::CURLE_SSL_ENGINE_INITFAILED));
+    }
+    let data = Arc::into_raw(keylog.clone()) as *mut c_void;
+    unsafe {
+        let previous = openssl_sys::SSL_CTX_get_ex_data(ssl_ctx, index);
+        if openssl_sys::SSL_CTX_set_ex_data(ssl_ctx, index, data) != 1 {
+            drop(Arc::from_raw(data as *const Mutex<File>));
+            return Err(curl::Error::new(curl_sys::CURLE_SSL_ENGINE_INITFAILED));
+        }
+        if !previous.is_null() {
+            drop(Arc::from_raw(previous as *const Mutex<File>));
+        }
+        openssl_sys::SSL_CTX_set_keylog_callback(ssl_ctx, Some(write_line));
+    }
+    Ok(())
+}
+
+/// The ex_data slot of an SSL_CTX that holds its key log file.
+fn ex_index() -> c_int {
+    static INDEX: OnceLock<c_int> = OnceLock::new();
+    *INDEX.get_or_init(|| unsafe {
+        openssl_sys::SSL_CTX_get_ex_new_index(0, std::ptr::null_mut(), None, None, Some(free_data))
+    })
+}
+
+unsafe extern "C" fn write_line(ssl: *const SSL, line: *const c_char) {
+    let ssl_ctx = openssl_sys::SSL_get_SSL_CTX(ssl);
+    let data = openssl_sys::SSL_CTX_get_ex_data(ssl_ctx, ex_index());
+    if data.is_null() || line.is_null() {
+        return;
+    }
+    let keylog = &*(data as *const Mutex<File>);
+    let line = CStr::from_ptr(line).to_bytes();
+    let mut file = keylog.lock().unwrap_or_else(|err| err.into_inner());
+    if let Err(err) = file.write_all(line).and_then(|_| file.write_all(b"\n")) {
+        log::trace!("{err}");
+    }
+}
+
+unsafe extern "C" fn free_data(
+    _parent: *mut c_void,
+    ptr: *mut c_void,
+    _ad: *mut openssl_sys::CRYPTO_EX_DATA,
+    _idx: c_int,
+    _argl: c_long,
+    _argp: *mut c_void,
+) {
+    if !ptr.is_null() {
+        drop(Arc::from_raw(ptr as *const Mutex<File>));
+    }
+}
@@ -3,9 +3,6 @@
 //!
 //! ## perform Curl Easy2 asynchronously
 //! ```rust
-//! pub mod actor;
-//! pub mod error;
-//!
 //! use async_curl::actor::{Actor, CurlActor};
 //! use curl::easy::{Easy2, Handler, WriteError};
 //!
@@ -74,7 +71,32 @@
 //! }
 //! ```
 pub mod actor;
+pub mod cache;
+pub mod circuit;
+#[cfg(feature = "bytes")]
+mod coalesce;
+pub mod connection;
+pub mod cookie;
 pub mod curl;
 pub mod error;
+pub mod handler;
+pub mod info;
+#[cfg(all(feature = "ssl-keylog", unix, not(target_os = "macos")))]
+mod keylog;
+pub mod multipart;
+pub mod plan;
+pub mod response;
+pub mod retry;
+#[cfg(feature = "tower")]
+pub mod service;
+pub mod share;
+pub mod sse;
+pub mod template;
 #[cfg(test)]
 mod tests;
+pub mod util;
+pub mod version;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+pub use actor::{Actor, CurlActor};
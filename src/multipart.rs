@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use curl::easy::Form;
+use curl::FormError;
+
+/// Multipart assembles the parts of a `multipart/form-data` body, which
+/// `AsyncCurl::multipart` then sends as a POST request.
+/// ```
+/// use async_curl::multipart::Multipart;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let multipart = Multipart::new()
+///     .text("title", "Holiday")?
+///     .file_bytes("photo", "beach.png", "image/png", vec![0x89, b'P', b'N', b'G'])?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Multipart {
+    form: Form,
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Multipart {
+    /// Creates a body without any parts.
+    pub fn new() -> Self {
+        Self { form: Form::new() }
+    }
+
+    /// Adds a plain text field.
+    pub fn text(mut self, name: &str, value: &str) -> Result<Self, FormError> {
+        self.form
+            .part(name)
+            .contents(value.as_bytes())
+            .add()
+            .map_err(|err| {
+                log::trace!("{err}");
+                err
+            })?;
+        Ok(self)
+    }
+
+    /// Adds the file at `path`, which is read while the request is sent.
+    ///
+    /// The content type is guessed from the extension of the file,
+    /// falling back to `application/octet-stream`.
+    pub fn file<P: AsRef<Path>>(mut self, name: &str, path: P) -> Result<Self, FormError> {
+        let path = path.as_ref();
+        self.form
+            .part(name)
+            .file(path)
+            .content_type(content_type_of(path))
+            .add()
+            .map_err(|err| {
+                log::trace!("{err}");
+                err
+            })?;
+        Ok(self)
+    }
+
+    /// Adds a file from memory, sent under `filename` with the given content type.
+    pub fn file_bytes(
+        mut self,
+        name: &str,
+        filename: &str,
+        content_type: &str,
+        bytes: Vec<u8>,
+    ) -> Result<Self, FormError> {
+        self.form
+            .part(name)
+            .buffer(filename, bytes)
+            .content_type(content_type)
+            .add()
+            .map_err(|err| {
+                log::trace!("{err}");
+                err
+            })?;
+        Ok(self)
+    }
+
+    pub(crate) fn into_form(self) -> Form {
+        self.form
+    }
+}
+
+/// Guesses the content type of a file from its extension.
+fn content_type_of(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("txt") => "text/plain",
+        Some("htm" | "html") => "text/html",
+        Some("css") => "text/css",
+        Some("csv") => "text/csv",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz") => "application/gzip",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("webp") => "image/webp",
+        Some("mp3") => "audio/mpeg",
+        Some("mp4") => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,102 @@
+/// RequestPlan describes the request an `AsyncCurl` was built for, as
+/// returned by `AsyncCurl::dry_run` without touching the network, so the
+/// code building requests can be tested on its own.
+///
+/// Only the options set through the builder are seen, not the ones set
+/// through `easy_mut` or on an `Easy2` given to `AsyncCurl::from_easy`.
+/// ```
+/// use std::time::Duration;
+///
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::CollectingHandler;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let plan = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+///     .url("https://example.com/items")?
+///     .header("Accept", "application/json")?
+///     .post_fields_copy(b"name=item")?
+///     .timeout(Duration::from_secs(10))?
+///     .finalize()
+///     .dry_run();
+///
+/// assert_eq!(plan.method, "POST");
+/// assert_eq!(plan.headers, ["Accept: application/json"]);
+/// assert_eq!(plan.option("timeout"), Some("10s"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestPlan {
+    /// The URL set by `url` and `query`.
+    pub url: Option<String>,
+    /// The method the setters called ask for, `GET` unless one of them
+    /// changed it.
+    pub method: String,
+    /// The headers added through `header` and similar methods, as
+    /// `name: value` lines.
+    pub headers: Vec<String>,
+    /// The body set by `post_fields_copy`, `form` or `json_body`.
+    pub body: Option<Vec<u8>>,
+    /// The other notable options that were set, by the name of their
+    /// setter, with their last value in the order they were first set.
+    ///
+    /// These are exactly:
+    /// - where the request goes: `proxy`, `noproxy`, `follow_location`,
+    ///   `max_redirections`, `protocols` and `redir_protocols`, the latter
+    ///   two also set by `allowed_protocols` and `allowed_redirect_protocols`
+    /// - how the peer is verified: `ssl_verify_host`, `ssl_verify_peer`,
+    ///   `ssl_verify_status`, `proxy_ssl_verify_host`, `proxy_ssl_verify_peer`,
+    ///   `doh_ssl_verify_host`, `doh_ssl_verify_peer`, `doh_ssl_verify_status`,
+    ///   `cainfo`, `capath`, `proxy_cainfo`, `proxy_capath` and
+    ///   `pinned_public_key`
+    /// - who the request is made as: `username`, `http_auth`, `proxy_auth`
+    ///   and `unrestricted_auth`, also set by `basic_auth`
+    /// - what is sent: `referer`, `useragent`, `cookie`, `accept_encoding`,
+    ///   `http_content_decoding`, `range`, `resume_from`, `upload`, `quote`,
+    ///   `prequote`, `postquote`, `mail_from` and `mail_rcpt`
+    /// - how it is sent: `fail_on_error`, `timeout`, `connect_timeout` and
+    ///   `http_version`
+    ///
+    /// Secrets such as passwords, keys and tokens are never recorded, a
+    /// bearer token only shows among the `headers`.
+    pub options: Vec<(&'static str, String)>,
+}
+
+impl Default for RequestPlan {
+    fn default() -> Self {
+        Self {
+            url: None,
+            method: "GET".to_string(),
+            headers: Vec::new(),
+            body: None,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl RequestPlan {
+    /// Returns the value of the option set by the setter named `name`.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(option, _)| *option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Records the value of an option, replacing the one set before.
+    pub(crate) fn record(&mut self, name: &'static str, value: String) {
+        match self.options.iter_mut().find(|(option, _)| *option == name) {
+            Some((_, previous)) => *previous = value,
+            None => self.options.push((name, value)),
+        }
+    }
+
+    /// Records a body, which makes a `GET` a `POST` as libcurl does.
+    pub(crate) fn record_body(&mut self, body: &[u8]) {
+        self.body = Some(body.to_vec());
+        if self.method == "GET" {
+            self.method = "POST".to_string();
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+/// The response of a transfer, bundling the status code,
+/// the response headers and the body.
+#[derive(Debug, Clone, Default)]
+pub struct Response {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    /// The URL the transfer ended up at, after following any redirects
+    /// (`CURLINFO_EFFECTIVE_URL`).
+    pub effective_url: Option<String>,
+    /// How many redirects were followed (`CURLINFO_REDIRECT_COUNT`).
+    pub redirect_count: u32,
+    /// The redirects that were followed, in order, when the response was
+    /// collected by a [`CollectingHandler`](crate::handler::CollectingHandler).
+    pub redirects: Vec<RedirectHop>,
+}
+
+/// One redirect followed on the way to a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectHop {
+    /// The status code of the redirect, e.g. 302.
+    pub status: u16,
+    /// The `Location` header of the redirect, as the server sent it.
+    pub location: String,
+}
+
+impl Response {
+    /// Builds a Response from the raw header pairs of a transfer,
+    /// skipping headers that are not valid HTTP header names or values.
+    /// Values with non-ASCII text are kept as opaque bytes.
+    pub fn from_parts(status: u16, headers: &[(String, String)], body: Vec<u8>) -> Self {
+        let headers = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+                let value = HeaderValue::from_bytes(value.as_bytes()).ok()?;
+                Some((name, value))
+            })
+            .fold(HeaderMap::new(), |mut map, (name, value)| {
+                map.append(name, value);
+                map
+            });
+        Self {
+            status,
+            headers,
+            body,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use curl::easy::{Easy2, Handler};
+
+use crate::error::Error;
+use crate::info::TransferInfo;
+
+/// Decides whether a failed attempt is worth another one.
+type Retryable<H> = Arc<dyn Fn(&Error<H>) -> bool + Send + Sync>;
+
+/// RetryPolicy tells `CurlActor::send_request_retry` how many times to try a
+/// request and how long to wait in between, growing the delay exponentially.
+/// ```
+/// use std::time::Duration;
+///
+/// use async_curl::retry::RetryPolicy;
+/// use curl::easy::{Handler, WriteError};
+///
+/// #[derive(Debug, Clone, Default)]
+/// pub struct ResponseHandler {
+///     data: Vec<u8>,
+/// }
+///
+/// impl Handler for ResponseHandler {
+///     fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+///         self.data.extend_from_slice(data);
+///         Ok(data.len())
+///     }
+/// }
+///
+/// let policy: RetryPolicy<ResponseHandler> = RetryPolicy::new()
+///     .max_attempts(5)
+///     .base_delay(Duration::from_millis(200))
+///     .multiplier(2.0)
+///     .jitter(0.1)
+///     .retryable(|err| err.is_timeout() || err.is_connection_error());
+/// ```
+pub struct RetryPolicy<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    max_attempts: u32,
+    base_delay: Duration,
+    multiplier: f64,
+    jitter: f64,
+    respect_retry_after: bool,
+    retryable: Retryable<H>,
+}
+
+impl<H> Default for RetryPolicy<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H> Clone for RetryPolicy<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            max_attempts: self.max_attempts,
+            base_delay: self.base_delay,
+            multiplier: self.multiplier,
+            jitter: self.jitter,
+            respect_retry_after: self.respect_retry_after,
+            retryable: self.retryable.clone(),
+        }
+    }
+}
+
+impl<H> Debug for RetryPolicy<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .field("respect_retry_after", &self.respect_retry_after)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H> RetryPolicy<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    /// Creates a policy of three attempts, starting with a delay of 100ms
+    /// that doubles after every attempt, without jitter.
+    ///
+    /// Only timeouts and failures to connect are retried by default.
+    pub fn new() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            multiplier: 2.0,
+            jitter: 0.0,
+            respect_retry_after: false,
+            retryable: Arc::new(|err| err.is_timeout() || err.is_couldnt_connect()),
+        }
+    }
+
+    /// Sets how many times the request is tried in total, including the first try.
+    ///
+    /// A value of zero is treated as one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the delay before the first retry.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Sets the factor the delay grows by after every retry.
+    ///
+    /// A factor below one is treated as one.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Randomizes every delay by up to the given fraction of it in either
+    /// direction, so clients that failed together do not retry together.
+    ///
+    /// The fraction is clamped between 0 and 1.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Retries responses with the status 429 or 503 as well, waiting for as
+    /// long as their `Retry-After` header asks, in seconds or as an HTTP-date,
+    /// instead of following the backoff schedule. Without the header the
+    /// backoff schedule is used.
+    ///
+    /// The response must not be turned into an error by `fail_on_error`,
+    /// as the header is read from the returned Easy2.
+    ///
+    /// By default this is off.
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+        self
+    }
+
+    /// Sets which errors are worth another attempt.
+    pub fn retryable<F>(mut self, retryable: F) -> Self
+    where
+        F: Fn(&Error<H>) -> bool + Send + Sync + 'static,
+    {
+        self.retryable = Arc::new(retryable);
+        self
+    }
+
+    pub(crate) fn attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub(crate) fn is_retryable(&self, err: &Error<H>) -> bool {
+        (self.retryable)(err)
+    }
+
+    /// Returns the delay before the given retry, the first retry being 1.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.base_delay.as_secs_f64() * self.multiplier.powi(exponent);
+        let spread = self.jitter * (2.0 * random_fraction() - 1.0);
+        Duration::try_from_secs_f64(delay * (1.0 + spread)).unwrap_or(Duration::MAX)
+    }
+
+    /// Returns the delay before the given retry when the response asks the
+    /// client to come back later, or `None` if the response is final.
+    pub(crate) fn throttled_delay(&self, easy2: &Easy2<H>, retry: u32) -> Option<Duration> {
+        if !self.respect_retry_after {
+            return None;
+        }
+        let status = easy2.response_code().ok()?;
+        if status != 429 && status != 503 {
+            return None;
+        }
+        let retry_after = easy2.retry_after().ok().flatten();
+        Some(retry_after.unwrap_or_else(|| self.delay(retry)))
+    }
+}
+
+/// Returns a pseudo-random number between 0 and 1, good enough to spread retries.
+fn random_fraction() -> f64 {
+    let hash = RandomState::new().build_hasher().finish();
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
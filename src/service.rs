@@ -0,0 +1,137 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use curl::easy::{Easy2, Handler};
+use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::OwnedPermit;
+use tower_service::Service;
+
+use crate::actor::{self, CurlActor, Request};
+use crate::error::Error;
+
+type ReserveFuture<H> =
+    Pin<Box<dyn Future<Output = Result<OwnedPermit<Request<H>>, SendError<()>>> + Send>>;
+
+type ResponseFuture<H> = Pin<Box<dyn Future<Output = Result<Easy2<H>, Error<H>>> + Send>>;
+
+/// CurlService adapts a [`CurlActor`] to a `tower::Service`, so timeouts,
+/// retries and rate limits from the tower ecosystem can be layered on top.
+///
+/// `poll_ready` waits for a free slot in the queue of the actor, applying
+/// its backpressure, and `call` sends the request like `send_request`.
+/// ```
+/// use async_curl::actor::CurlActor;
+/// use async_curl::handler::CollectingHandler;
+/// use async_curl::service::CurlService;
+/// use curl::easy::Easy2;
+/// use tower_service::Service;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut service = CurlService::new(CurlActor::new());
+///
+/// let mut easy2 = Easy2::new(CollectingHandler::new());
+/// easy2.url("https://www.rust-lang.org/")?;
+///
+/// std::future::poll_fn(|cx| service.poll_ready(cx)).await?;
+/// let response = service.call(easy2);
+/// # Ok(())
+/// # }
+/// ```
+pub struct CurlService<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    actor: CurlActor<H>,
+    state: State<H>,
+}
+
+/// Where the service is in reserving a slot for the next request.
+enum State<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    Idle,
+    Reserving(ReserveFuture<H>),
+    Ready(OwnedPermit<Request<H>>),
+}
+
+impl<H> CurlService<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    /// Creates the service sending its requests to `actor`.
+    pub fn new(actor: CurlActor<H>) -> Self {
+        Self {
+            actor,
+            state: State::Idle,
+        }
+    }
+}
+
+impl<H> Clone for CurlService<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    /// The clone shares the actor, but not the slot reserved by `poll_ready`.
+    fn clone(&self) -> Self {
+        Self::new(self.actor.clone())
+    }
+}
+
+impl<H> Debug for CurlService<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CurlService")
+            .field("ready", &matches!(self.state, State::Ready(_)))
+            .finish()
+    }
+}
+
+impl<H> Service<Easy2<H>> for CurlService<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    type Response = Easy2<H>;
+    type Error = Error<H>;
+    type Future = ResponseFuture<H>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        loop {
+            match &mut self.state {
+                State::Idle => {
+                    let sender = self.actor.request_sender();
+                    self.state = State::Reserving(Box::pin(sender.reserve_owned()));
+                }
+                State::Reserving(reserve) => match reserve.as_mut().poll(cx) {
+                    Poll::Ready(Ok(permit)) => self.state = State::Ready(permit),
+                    Poll::Ready(Err(_)) => {
+                        self.state = State::Idle;
+                        let err = curl::Error::new(curl_sys::CURLE_SEND_ERROR);
+                        log::trace!("The background worker is no longer running.");
+                        return Poll::Ready(Err(Error::Curl(err)));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                State::Ready(_) => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+
+    /// Queues the request in the slot reserved by `poll_ready` right away,
+    /// without waiting for the returned future to be polled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `poll_ready` did not return `Ready(Ok(()))` first.
+    fn call(&mut self, easy2: Easy2<H>) -> Self::Future {
+        match std::mem::replace(&mut self.state, State::Idle) {
+            State::Ready(permit) => Box::pin(actor::dispatch_reserved(permit, easy2)),
+            _ => panic!("CurlService::call was called before poll_ready was ready"),
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::ffi::c_void;
+use std::fmt::Debug;
+use std::sync::{Arc, Condvar, Mutex};
+
+use curl::easy::{Easy2, Handler};
+use curl::ShareError;
+
+use crate::error::Error;
+
+/// The caches a [`CurlShare`] can hold for the transfers attached to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareData {
+    /// Resolved host names.
+    Dns,
+    /// Cookies received and sent by the transfers.
+    Cookies,
+    /// TLS session ids, so connections to the same host can skip a full handshake.
+    SslSessions,
+    /// Open connections, so a later transfer can reuse them.
+    Connections,
+}
+
+impl ShareData {
+    fn lock_data(self) -> curl_sys::curl_lock_data {
+        match self {
+            ShareData::Dns => curl_sys::CURL_LOCK_DATA_DNS,
+            ShareData::Cookies => curl_sys::CURL_LOCK_DATA_COOKIE,
+            ShareData::SslSessions => curl_sys::CURL_LOCK_DATA_SSL_SESSION,
+            ShareData::Connections => curl_sys::CURL_LOCK_DATA_CONNECT,
+        }
+    }
+}
+
+/// CurlShare wraps a libcurl share handle, letting the transfers of one or
+/// more actors use the same DNS, cookie, TLS session and connection caches
+/// instead of each Easy2 keeping its own.
+///
+/// libcurl locks the caches through callbacks, so a share can be attached
+/// to actors running on different threads.
+/// ```
+/// use async_curl::actor::{CurlActor, CurlActorBuilder};
+/// use async_curl::handler::CollectingHandler;
+/// use async_curl::share::{CurlShare, ShareData};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let share = CurlShare::new(&[ShareData::Dns, ShareData::SslSessions])?;
+/// let actor: CurlActor<CollectingHandler> = CurlActorBuilder::new().share(share).build();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CurlShare {
+    inner: Arc<ShareInner>,
+}
+
+impl Debug for CurlShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CurlShare")
+            .field("data", &self.inner.data)
+            .finish()
+    }
+}
+
+impl CurlShare {
+    /// Creates a share handle holding the given caches.
+    pub fn new(data: &[ShareData]) -> Result<Self, ShareError> {
+        let handle = unsafe { curl_sys::curl_share_init() };
+        if handle.is_null() {
+            return Err(ShareError::new(curl_sys::CURLSHE_NOMEM));
+        }
+        let inner = Arc::new(ShareInner {
+            handle,
+            data: data.to_vec(),
+            locks: Default::default(),
+        });
+        let user_data = Arc::as_ptr(&inner) as *mut c_void;
+        let lock: curl_sys::curl_lock_function = lock_data;
+        let unlock: curl_sys::curl_unlock_function = unlock_data;
+        unsafe {
+            setopt(handle, curl_sys::CURLSHOPT_USERDATA, user_data)?;
+            setopt(handle, curl_sys::CURLSHOPT_LOCKFUNC, lock as *const c_void)?;
+            setopt(
+                handle,
+                curl_sys::CURLSHOPT_UNLOCKFUNC,
+                unlock as *const c_void,
+            )?;
+            for data in data {
+                let rc = curl_sys::curl_share_setopt(
+                    handle,
+                    curl_sys::CURLSHOPT_SHARE,
+                    data.lock_data(),
+                );
+                if rc != curl_sys::CURLSHE_OK {
+                    let err = ShareError::new(rc);
+                    log::trace!("{err}");
+                    return Err(err);
+                }
+            }
+        }
+        Ok(Self { inner })
+    }
+
+    /// Returns the caches held by the share.
+    pub fn data(&self) -> &[ShareData] {
+        &self.inner.data
+    }
+
+    /// Makes the Easy2 use the caches of the share.
+    pub(crate) fn attach<H>(&self, easy2: &mut Easy2<H>) -> Result<(), Error<H>>
+    where
+        H: Handler + Debug + Send + 'static,
+    {
+        set_share(easy2, self.inner.handle)
+    }
+
+    /// Makes the Easy2 use caches of its own again,
+    /// so it can outlive the share.
+    pub(crate) fn detach<H>(&self, easy2: &mut Easy2<H>) -> Result<(), Error<H>>
+    where
+        H: Handler + Debug + Send + 'static,
+    {
+        set_share(easy2, std::ptr::null_mut())
+    }
+}
+
+struct ShareInner {
+    handle: *mut curl_sys::CURLSH,
+    data: Vec<ShareData>,
+    locks: [DataLock; 8],
+}
+
+// The share handle is only touched by libcurl while it holds the lock of the
+// data involved, and by `Drop` once no transfer refers to it anymore.
+unsafe impl Send for ShareInner {}
+unsafe impl Sync for ShareInner {}
+
+impl Drop for ShareInner {
+    fn drop(&mut self) {
+        let rc = unsafe { curl_sys::curl_share_cleanup(self.handle) };
+        if rc != curl_sys::CURLSHE_OK {
+            log::trace!("{}", ShareError::new(rc));
+        }
+    }
+}
+
+/// A lock that is taken and released by separate libcurl callbacks,
+/// so it cannot be held through a guard.
+#[derive(Default)]
+struct DataLock {
+    locked: Mutex<bool>,
+    released: Condvar,
+}
+
+impl DataLock {
+    fn lock(&self) {
+        let mut locked = self.locked.lock().unwrap_or_else(|err| err.into_inner());
+        while *locked {
+            locked = self
+                .released
+                .wait(locked)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        *locked = true;
+    }
+
+    fn unlock(&self) {
+        let mut locked = self.locked.lock().unwrap_or_else(|err| err.into_inner());
+        *locked = false;
+        self.released.notify_one();
+    }
+}
+
+extern "C" fn lock_data(
+    _easy: *mut curl_sys::CURL,
+    data: curl_sys::curl_lock_data,
+    _access: curl_sys::curl_lock_access,
+    user_data: *mut c_void,
+) {
+    let inner = unsafe { &*(user_data as *const ShareInner) };
+    if let Some(lock) = inner.locks.get(data as usize) {
+        lock.lock();
+    }
+}
+
+extern "C" fn unlock_data(
+    _easy: *mut curl_sys::CURL,
+    data: curl_sys::curl_lock_data,
+    user_data: *mut c_void,
+) {
+    let inner = unsafe { &*(user_data as *const ShareInner) };
+    if let Some(lock) = inner.locks.get(data as usize) {
+        lock.unlock();
+    }
+}
+
+unsafe fn setopt(
+    handle: *mut curl_sys::CURLSH,
+    option: curl_sys::CURLSHoption,
+    value: *const c_void,
+) -> Result<(), ShareError> {
+    let rc = curl_sys::curl_share_setopt(handle, option, value);
+    if rc != curl_sys::CURLSHE_OK {
+        let err = ShareError::new(rc);
+        log::trace!("{err}");
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn set_share<H>(easy2: &mut Easy2<H>, share: *mut curl_sys::CURLSH) -> Result<(), Error<H>>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    let rc = unsafe { curl_sys::curl_easy_setopt(easy2.raw(), curl_sys::CURLOPT_SHARE, share) };
+    if rc != curl_sys::CURLE_OK {
+        let err = curl::Error::new(rc);
+        log::trace!("{err}");
+        return Err(Error::Curl(err));
+    }
+    Ok(())
+}
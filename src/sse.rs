@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use curl::easy::{Handler, WriteError};
+use tokio::sync::mpsc::Receiver;
+use tokio::task::JoinHandle;
+
+use crate::actor::CurlActor;
+use crate::curl::AsyncCurl;
+use crate::error::Error;
+use crate::handler::{ChannelSink, Flush};
+
+/// One event of a `text/event-stream` body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The `event:` field, `message` when the event has none.
+    pub event: String,
+    /// The `data:` fields of the event, joined by newlines.
+    pub data: String,
+    /// The last `id:` field seen so far in the stream, which carries over
+    /// to the events without one.
+    pub id: Option<String>,
+    /// The reconnection time of the `retry:` field of the event.
+    pub retry: Option<Duration>,
+}
+
+/// SseHandler is a [`Handler`] that parses a `text/event-stream` body into
+/// [`SseEvent`]s and sends each of them to a channel as soon as it is
+/// complete, instead of buffering the body.
+///
+/// When the receiver falls behind, the handler pauses the transfer until
+/// there is room again, and dropping the receiver aborts the transfer with
+/// `Error::HandlerAborted`. See [`EventStream`] for a GET that runs in the
+/// background.
+#[derive(Debug)]
+pub struct SseHandler {
+    sink: ChannelSink<SseEvent>,
+    /// The bytes of the line that is not complete yet.
+    line: Vec<u8>,
+    event: Option<String>,
+    data: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Handler for SseHandler {
+    /// This will parse the chunk into events, or pause the transfer if the
+    /// receiver has not caught up with the events of the last chunk yet.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        match self.sink.flush() {
+            Flush::Done => {}
+            Flush::Full => return Err(WriteError::Pause),
+            Flush::Closed => return Ok(0),
+        }
+        for &byte in data {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.line);
+                self.parse_line(line.strip_suffix(b"\r").unwrap_or(&line));
+            } else {
+                self.line.push(byte);
+            }
+        }
+        // The events that don't fit are sent before the next chunk is taken.
+        match self.sink.flush() {
+            Flush::Done | Flush::Full => Ok(data.len()),
+            Flush::Closed => Ok(0),
+        }
+    }
+}
+
+impl SseHandler {
+    /// Creates the handler together with the receiver of the events,
+    /// holding at most `capacity` events in between.
+    pub fn new(capacity: usize) -> (Self, Receiver<SseEvent>) {
+        let (sink, receiver) = ChannelSink::new(capacity);
+        (
+            Self {
+                sink,
+                line: Vec::new(),
+                event: None,
+                data: None,
+                id: None,
+                retry: None,
+            },
+            receiver,
+        )
+    }
+
+    /// Ends the event stream once the events of the last chunk were sent,
+    /// waiting for room in the channel, so the receiver returns `None` after
+    /// them. An event that was not ended by a blank line is dropped.
+    pub async fn close(&mut self) {
+        self.sink.close().await;
+    }
+
+    fn parse_line(&mut self, line: &[u8]) {
+        if line.is_empty() {
+            self.dispatch();
+            return;
+        }
+        let line = String::from_utf8_lossy(line);
+        // A line starting with a colon is a comment, e.g. a keep-alive.
+        let (field, value) = match line.split_once(':') {
+            Some(("", _)) => return,
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_ref(), ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Some(Duration::from_millis(millis));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Ends the event at a blank line, which is only sent if it had data.
+    fn dispatch(&mut self) {
+        let event = self.event.take();
+        let retry = self.retry.take();
+        if let Some(data) = self.data.take() {
+            self.sink.push(SseEvent {
+                event: event.unwrap_or_else(|| "message".to_string()),
+                data,
+                id: self.id.clone(),
+                retry,
+            });
+        }
+    }
+}
+
+/// EventStream performs a GET of a Server-Sent Events endpoint in the
+/// background and yields its events as they arrive.
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::sse::EventStream;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut stream = EventStream::get(CurlActor::new(), "https://example.com/events", 16)?;
+/// while let Some(event) = stream.next().await {
+///     println!("{}: {}", event.event, event.data);
+/// }
+/// stream.finish().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct EventStream {
+    events: Receiver<SseEvent>,
+    transfer: JoinHandle<Result<(), Error<SseHandler>>>,
+}
+
+impl EventStream {
+    /// Starts the GET of `url` with `Accept: text/event-stream`,
+    /// holding at most `capacity` events that were not received yet.
+    pub fn get(
+        curl: CurlActor<SseHandler>,
+        url: &str,
+        capacity: usize,
+    ) -> Result<Self, Error<SseHandler>> {
+        let (handler, events) = SseHandler::new(capacity);
+        let client = AsyncCurl::new(curl, handler)
+            .url(url)?
+            .header("Accept", "text/event-stream")?
+            .finalize();
+        let transfer = tokio::spawn(async move {
+            let mut easy = client.perform().await?;
+            easy.get_mut().close().await;
+            Ok(())
+        });
+        Ok(Self { events, transfer })
+    }
+
+    /// Returns the next event, or `None` once the transfer has ended.
+    pub async fn next(&mut self) -> Option<SseEvent> {
+        self.events.recv().await
+    }
+
+    /// Returns the receiver of the events, e.g. to `select!` on it.
+    pub fn events(&mut self) -> &mut Receiver<SseEvent> {
+        &mut self.events
+    }
+
+    /// Stops the transfer, whether or not it has ended yet.
+    pub fn close(self) {
+        self.transfer.abort();
+    }
+
+    /// Waits for the transfer to end and returns why it did, e.g. once
+    /// `next` returned `None`. The events that were not received are dropped,
+    /// which aborts a transfer that is still running once more data arrives.
+    pub async fn finish(self) -> Result<(), Error<SseHandler>> {
+        drop(self.events);
+        self.transfer
+            .await
+            .map_err(|err| Error::Io(err.into()))
+            .and_then(|result| result)
+    }
+}
@@ -0,0 +1,350 @@
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use curl::easy::{Easy2, Handler};
+use tokio::task::JoinSet;
+
+use crate::actor::{Actor, CurlActor};
+use crate::curl::{AsyncCurl, Build};
+use crate::error::Error;
+use crate::handler::{CollectingHandler, FileHandler};
+use crate::response::Response;
+
+/// One recorded group of builder calls.
+type Step<C, A> =
+    Arc<dyn Fn(AsyncCurl<C, Build, A>) -> Result<AsyncCurl<C, Build, A>, Error<C>> + Send + Sync>;
+
+/// RequestTemplate records builder calls once, e.g. the common headers, TLS
+/// settings and timeouts of an API, and replays them onto a fresh `Easy2`
+/// for every request, since an `Easy2` itself cannot be cloned.
+///
+/// The URL is set on every built client, as it ends the [`Build`] stage.
+/// The clients are built for the [`CurlActor`] unless the template is used
+/// with another [`Actor`], such as a mock transport in tests.
+/// ```
+/// use std::time::Duration;
+///
+/// use async_curl::actor::CurlActor;
+/// use async_curl::handler::CollectingHandler;
+/// use async_curl::template::RequestTemplate;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let template = RequestTemplate::new()
+///     .then(|client| client.header("Accept", "application/json"))
+///     .then(|client| client.connect_timeout(Duration::from_secs(5)));
+///
+/// let actor = CurlActor::new();
+/// let first = template
+///     .build(actor.clone(), CollectingHandler::new())?
+///     .url("https://www.rust-lang.org/")?
+///     .finalize();
+/// let second = template
+///     .build(actor, CollectingHandler::new())?
+///     .url("https://crates.io/")?
+///     .finalize();
+/// # Ok(())
+/// # }
+/// ```
+pub struct RequestTemplate<C, A = CurlActor<C>>
+where
+    C: Handler + Debug + Send + 'static,
+{
+    steps: Vec<Step<C, A>>,
+}
+
+impl<C, A> Default for RequestTemplate<C, A>
+where
+    C: Handler + Debug + Send + 'static,
+    A: Actor<C>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C, A> Clone for RequestTemplate<C, A>
+where
+    C: Handler + Debug + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            steps: self.steps.clone(),
+        }
+    }
+}
+
+impl<C, A> Debug for RequestTemplate<C, A>
+where
+    C: Handler + Debug + Send + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestTemplate")
+            .field("steps", &self.steps.len())
+            .finish()
+    }
+}
+
+impl<C, A> RequestTemplate<C, A>
+where
+    C: Handler + Debug + Send + 'static,
+    A: Actor<C>,
+{
+    /// Creates a template without any recorded calls.
+    pub fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+
+    /// Records builder calls to replay on every request, after
+    /// the calls recorded before.
+    pub fn then<F>(mut self, step: F) -> Self
+    where
+        F: Fn(AsyncCurl<C, Build, A>) -> Result<AsyncCurl<C, Build, A>, Error<C>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.steps.push(Arc::new(step));
+        self
+    }
+
+    /// Creates a new builder with a fresh `Easy2` and replays the recorded calls on it.
+    pub fn build(&self, curl: A, collector: C) -> Result<AsyncCurl<C, Build, A>, Error<C>> {
+        self.steps
+            .iter()
+            .try_fold(AsyncCurl::new(curl, collector), |client, step| step(client))
+    }
+}
+
+/// The outcome of one of the requests of `RequestTemplate::perform_n`.
+#[derive(Debug)]
+pub struct Attempt {
+    /// The response of the request, or why it could not be built or performed.
+    pub response: Result<Response, Error<CollectingHandler>>,
+    /// The wall-clock time from queueing the request until its response,
+    /// waiting for the actor to start it included.
+    pub elapsed: Duration,
+}
+
+impl<A> RequestTemplate<CollectingHandler, A>
+where
+    A: Actor<CollectingHandler> + Clone + 'static,
+{
+    /// Performs the request to `url` built from the template `n` times, e.g.
+    /// to warm up a server or to load test it, and returns every attempt in
+    /// the order they were built, with how long it took.
+    ///
+    /// Every request is queued at once, so on a [`CurlActor`],
+    /// `CurlActorBuilder::max_concurrent` and `rate_limit` decide how many of
+    /// them run at the same time. There
+    /// is no batch path driving them through one shared `Multi`: like any
+    /// other request, each runs in a `Multi` of its own on the actor.
+    pub async fn perform_n(&self, curl: A, url: &str, n: usize) -> Vec<Attempt> {
+        let transfers: Vec<_> = (0..n)
+            .map(|_| {
+                let client = self
+                    .build(curl.clone(), CollectingHandler::new())
+                    .and_then(|client| client.url(url));
+                tokio::spawn(async move {
+                    let start = Instant::now();
+                    let response = match client {
+                        Ok(client) => client.finalize().perform_response().await,
+                        Err(err) => Err(err),
+                    };
+                    Attempt {
+                        response,
+                        elapsed: start.elapsed(),
+                    }
+                })
+            })
+            .collect();
+
+        let mut attempts = Vec::with_capacity(n);
+        for transfer in transfers {
+            attempts.push(transfer.await.unwrap_or_else(|err| Attempt {
+                response: Err(Error::Io(err.into())),
+                elapsed: Duration::ZERO,
+            }));
+        }
+        attempts
+    }
+}
+
+impl<A> RequestTemplate<FileHandler, A>
+where
+    A: Actor<FileHandler> + Clone + 'static,
+{
+    /// Downloads `url` into the file at `path` as up to `chunks` byte ranges
+    /// transferred at the same time, each written at its own offset of the file.
+    /// Every request, the `HEAD` that learns the size included, is built from
+    /// the template. Returns the number of bytes written.
+    ///
+    /// Without a `Content-Length`, or if the server answers a range with the
+    /// whole body instead of `206 Partial Content`, the other ranges are
+    /// aborted and the file is downloaded again in a single transfer. Any
+    /// response that is not a success, the `HEAD` included, fails the
+    /// download. The file is removed if the download fails.
+    pub async fn download_ranges<P: AsRef<Path>>(
+        &self,
+        curl: A,
+        url: &str,
+        path: P,
+        chunks: usize,
+    ) -> Result<u64, Error<FileHandler>> {
+        let path = path.as_ref();
+        let result = self.try_download_ranges(curl, url, path, chunks).await;
+        if result.is_err() {
+            if let Err(err) = std::fs::remove_file(path) {
+                log::trace!("{err}");
+            }
+        }
+        result
+    }
+
+    async fn try_download_ranges(
+        &self,
+        curl: A,
+        url: &str,
+        path: &Path,
+        chunks: usize,
+    ) -> Result<u64, Error<FileHandler>> {
+        let head = self
+            .build(curl.clone(), FileHandler::new())?
+            .url(url)?
+            .nobody(true)?
+            .fail_on_error(true)?
+            .finalize()
+            .perform()
+            .await?;
+        check_status(&head)?;
+        let length = head.content_length_download().map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })?;
+        curl.recycle(head);
+        let chunks = chunks.max(1) as u64;
+        if length < 1.0 || chunks == 1 {
+            return self.download_whole(curl, url, path).await;
+        }
+        let length = length as u64;
+
+        create_file(path)?.set_len(length).map_err(io_error)?;
+
+        let chunk_len = length.div_ceil(chunks);
+        let refused = Arc::new(AtomicBool::new(false));
+        let mut transfers = JoinSet::new();
+        for start in (0..length).step_by(chunk_len as usize) {
+            let end = (start + chunk_len).min(length) - 1;
+            // Every range gets a file of its own, as clones would share one cursor.
+            let mut file = OpenOptions::new()
+                .write(true)
+                .open(path)
+                .map_err(io_error)?;
+            file.seek(SeekFrom::Start(start)).map_err(io_error)?;
+            let handler = FileHandler::with_file(file).partial_only(refused.clone());
+            let response = self
+                .build(curl.clone(), handler)?
+                .url(url)?
+                .range(&format!("{start}-{end}"))?
+                .fail_on_error(true)?
+                .finalize()
+                .perform();
+            transfers.spawn(async move { (end - start + 1, response.await) });
+        }
+
+        let mut written = 0;
+        let mut ranges_honored = true;
+        while let Some(transfer) = transfers.join_next().await {
+            let (expected, response) = transfer.map_err(|err| Error::Io(err.into()))?;
+            let mut easy = match response {
+                Ok(easy) if easy.response_code().ok() == Some(206) => easy,
+                // Any other answer to one range stops the others at once,
+                // as the server would not answer them differently.
+                Ok(_) => {
+                    ranges_honored = false;
+                    break;
+                }
+                Err(_) if refused.load(Ordering::SeqCst) => {
+                    ranges_honored = false;
+                    break;
+                }
+                Err(err) => return Err(err),
+            };
+            if easy.get_ref().written() != expected {
+                return Err(Error::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "expected {expected} bytes of a range, got {}",
+                        easy.get_ref().written()
+                    ),
+                )));
+            }
+            written += easy.get_ref().written();
+            *easy.get_mut() = FileHandler::new();
+            curl.recycle(easy);
+        }
+
+        if ranges_honored {
+            Ok(written)
+        } else {
+            transfers.abort_all();
+            log::trace!("The server does not honor ranges, downloading in a single transfer.");
+            self.download_whole(curl, url, path).await
+        }
+    }
+
+    async fn download_whole(
+        &self,
+        curl: A,
+        url: &str,
+        path: &Path,
+    ) -> Result<u64, Error<FileHandler>> {
+        let file = create_file(path)?;
+        let mut easy = self
+            .build(curl.clone(), FileHandler::with_file(file))?
+            .url(url)?
+            .fail_on_error(true)?
+            .finalize()
+            .perform()
+            .await?;
+        check_status(&easy)?;
+        let written = easy.get_ref().written();
+        *easy.get_mut() = FileHandler::new();
+        curl.recycle(easy);
+        Ok(written)
+    }
+}
+
+fn create_file(path: &Path) -> Result<File, Error<FileHandler>> {
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .map_err(io_error)
+}
+
+/// Fails on a response that is not a success, which `fail_on_error` lets
+/// through below `400`, such as a redirect that was not followed.
+fn check_status(easy: &Easy2<FileHandler>) -> Result<(), Error<FileHandler>> {
+    let status = easy.response_code().map_err(|err| {
+        log::trace!("{err}");
+        Error::Curl(err)
+    })?;
+    if status == 0 || (200..300).contains(&status) {
+        return Ok(());
+    }
+    let mut err = curl::Error::new(curl_sys::CURLE_HTTP_RETURNED_ERROR);
+    err.set_extra(format!("The server answered with status {status}"));
+    log::trace!("{err}");
+    Err(Error::Curl(err))
+}
+
+fn io_error(err: io::Error) -> Error<FileHandler> {
+    log::trace!("{err}");
+    Error::Io(err)
+}
@@ -1,21 +1,48 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use curl::easy::Easy2;
 use curl::easy::Handler;
+use curl::easy::InfoType;
 use curl::easy::WriteError;
 use http_types::StatusCode;
 use log::LevelFilter;
 use tokio::sync::Mutex;
+#[cfg(feature = "serde")]
+use wiremock::matchers::body_json;
+use wiremock::matchers::body_string;
+use wiremock::matchers::body_string_contains;
+use wiremock::matchers::header;
+use wiremock::matchers::header_exists;
 use wiremock::matchers::method;
 use wiremock::matchers::path;
+use wiremock::matchers::query_param;
 use wiremock::Mock;
 use wiremock::MockServer;
 use wiremock::ResponseTemplate;
 
 use crate::actor::Actor;
 use crate::actor::CurlActor;
-use crate::curl::AsyncCurl;
+use crate::actor::CurlActorBuilder;
+use crate::actor::Priority;
+use crate::cookie::CookieJar;
+use crate::curl::{AsyncCurl, Method, Protocol, RawOption};
+use crate::error::{CurlError, Error};
+use crate::handler::{
+    BoundedResponseHandler, CallbackHandler, CatchUnwindHandler, CollectingHandler,
+    DecodingHandler, FileHandler, LinesHandler, ProgressInfo, ReadSource, StreamHandler,
+    TeeHandler,
+};
+use crate::info::TransferInfo;
+use crate::multipart::Multipart;
+use crate::response::Response;
+use crate::retry::RetryPolicy;
+use crate::share::{CurlShare, ShareData};
+use crate::sse::{EventStream, SseHandler};
+use crate::template::RequestTemplate;
+use crate::util::{url_decode, url_encode};
+use crate::version::version_info;
 
 #[derive(Debug, Clone, Default)]
 pub struct ResponseHandler {
@@ -140,6 +167,50 @@ async fn test_error() {
     let _ = result.unwrap_err();
 }
 
+/// Fails a request to a host that cannot be resolved.
+async fn connection_error() -> Error<ResponseHandler> {
+    let mut easy2 = Easy2::new(ResponseHandler::new());
+    easy2.url("https://no-connection").unwrap();
+    CurlActor::new().send_request(easy2).await.unwrap_err()
+}
+
+#[tokio::test]
+async fn test_error_source() {
+    let err = connection_error().await;
+    // The underlying curl error is kept as the source of the chain.
+    let source = std::error::Error::source(&err).unwrap();
+    assert!(source.downcast_ref::<curl::Error>().is_some());
+}
+
+#[tokio::test]
+async fn test_error_curl_code() {
+    let err = connection_error().await;
+    assert!(err.curl_code().is_some());
+    assert!(Error::<ResponseHandler>::Timeout.curl_code().is_none());
+}
+
+#[tokio::test]
+async fn test_error_predicates() {
+    let err = connection_error().await;
+    assert!(err.is_connection_error());
+    assert!(!err.is_timeout());
+}
+
+#[tokio::test]
+async fn test_error_into_io_error() {
+    let err = connection_error().await;
+    let message = err.to_string();
+    let io_err = std::io::Error::from(err);
+    assert_eq!(io_err.kind(), std::io::ErrorKind::ConnectionRefused);
+    assert_eq!(io_err.to_string(), message);
+}
+
+#[tokio::test]
+async fn test_error_detail() {
+    let err = connection_error().await;
+    assert!(err.detail().unwrap().contains("no-connection"));
+}
+
 #[tokio::test]
 async fn test_concurrency_abort() {
     const MOCK_BODY_RESPONSE: &str = r#"{"token":"12345"}"#;
@@ -210,3 +281,3593 @@ async fn test_curl_builder() {
     assert_eq!(body, Some(MOCK_BODY_RESPONSE.as_bytes().to_vec()));
     assert_eq!(status, StatusCode::Ok);
 }
+
+#[derive(Debug, Clone, Default)]
+pub struct AbortingHandler;
+
+impl Handler for AbortingHandler {
+    /// Refuse the very first chunk so libcurl stops the transfer.
+    fn write(&mut self, _stream: &[u8]) -> Result<usize, WriteError> {
+        Ok(0)
+    }
+}
+
+#[tokio::test]
+async fn test_handler_aborted() {
+    const MOCK_BODY_RESPONSE: &str = r#"{"token":"12345"}"#;
+    let server = start_mock_server(
+        "/async-test",
+        MOCK_BODY_RESPONSE.to_string(),
+        StatusCode::Ok,
+    )
+    .await;
+    let url = format!("{}{}", server.uri(), "/async-test");
+
+    let curl = CurlActor::new();
+    let mut easy2 = Easy2::new(AbortingHandler);
+    easy2.url(url.as_str()).unwrap();
+    easy2.get(true).unwrap();
+
+    let err = curl.send_request(easy2).await.unwrap_err();
+    assert!(matches!(err, Error::HandlerAborted(_)));
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_WRITE_ERROR));
+
+    // The handler type can be erased to store errors of different handlers together.
+    let errors: Vec<CurlError> = vec![err.into(), Error::<ResponseHandler>::Timeout.into()];
+    assert!(matches!(errors[0], CurlError::HandlerAborted(_)));
+    assert!(matches!(errors[1], CurlError::Timeout));
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PanickingHandler {
+    panic: bool,
+}
+
+impl Handler for PanickingHandler {
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        if self.panic {
+            panic!("handler failed");
+        }
+        Ok(data.len())
+    }
+}
+
+#[tokio::test]
+async fn test_handler_panic() {
+    let server = start_mock_server("/panic", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}/panic", server.uri());
+    let curl = CurlActor::new();
+
+    let mut easy2 = Easy2::new(CatchUnwindHandler::new(PanickingHandler { panic: true }));
+    easy2.url(url.as_str()).unwrap();
+    let err = curl.send_request(easy2).await.unwrap_err();
+    assert!(matches!(&err, Error::HandlerPanic(message) if message == "handler failed"));
+
+    // The worker keeps performing the next requests.
+    let mut easy2 = Easy2::new(CatchUnwindHandler::new(PanickingHandler { panic: false }));
+    easy2.url(url.as_str()).unwrap();
+    let easy2 = curl.send_request(easy2).await.unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_handler_panic_concurrent() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("slow body")
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/panic"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string("body"))
+        .mount(&server)
+        .await;
+    // Both transfers are driven by the one worker at the same time.
+    let curl: CurlActor<CatchUnwindHandler<PanickingHandler>> =
+        CurlActorBuilder::new().capacity(2).build();
+
+    let mut easy2 = Easy2::new(CatchUnwindHandler::new(PanickingHandler { panic: false }));
+    easy2.url(&format!("{}/slow", server.uri())).unwrap();
+    let slow = tokio::spawn({
+        let curl = curl.clone();
+        async move { curl.send_request(easy2).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut easy2 = Easy2::new(CatchUnwindHandler::new(PanickingHandler { panic: true }));
+    easy2.url(&format!("{}/panic", server.uri())).unwrap();
+    let err = curl.send_request(easy2).await.unwrap_err();
+    assert!(matches!(&err, Error::HandlerPanic(message) if message == "handler failed"));
+
+    // Only the transfer whose handler panicked fails.
+    let easy2 = slow.await.unwrap().unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FlagHandler {
+    written: Arc<AtomicBool>,
+}
+
+impl Handler for FlagHandler {
+    /// Record that the body has started to arrive.
+    fn write(&mut self, stream: &[u8]) -> Result<usize, WriteError> {
+        self.written.store(true, Ordering::SeqCst);
+        Ok(stream.len())
+    }
+}
+
+#[tokio::test]
+async fn test_drop_cancels_transfer() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("slow body")
+                .set_delay(Duration::from_millis(800)),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/slow");
+
+    let curl = CurlActor::new();
+    let handler = FlagHandler::default();
+    let written = handler.written.clone();
+
+    let mut easy2 = Easy2::new(handler);
+    easy2.url(url.as_str()).unwrap();
+    easy2.get(true).unwrap();
+
+    let http_task = tokio::spawn(async move {
+        let _ = curl.send_request(easy2).await;
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    http_task.abort();
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(!written.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_actor_builder() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .and(header("x-default", "actor"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("slow body")
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/override"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/slow");
+
+    let mut default_headers = http::HeaderMap::new();
+    default_headers.insert("x-default", http::HeaderValue::from_static("actor"));
+    let curl: CurlActor<ResponseHandler> = CurlActorBuilder::new()
+        .capacity(4)
+        .max_concurrent(1)
+        .default_headers(default_headers)
+        .build();
+
+    let mut easy1 = curl.easy_handle(ResponseHandler::new());
+    easy1.url(url.as_str()).unwrap();
+    let mut easy2 = curl.easy_handle(ResponseHandler::new());
+    easy2.url(url.as_str()).unwrap();
+
+    let start = Instant::now();
+    let (first, second) = tokio::join!(curl.send_request(easy1), curl.send_request(easy2));
+    let elapsed = start.elapsed();
+
+    assert_eq!(first.unwrap().response_code().unwrap(), 200);
+    assert_eq!(second.unwrap().response_code().unwrap(), 200);
+    // Only one transfer may run at a time, so the delays add up.
+    assert!(elapsed >= Duration::from_millis(600));
+
+    // A header set on the request replaces the default one of the same name.
+    let url = format!("{}{}", server.uri(), "/override");
+    AsyncCurl::new(curl, ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .header("X-Default", "request")
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    let requests = server.received_requests().await.unwrap();
+    let request = requests.last().unwrap();
+    let values: Vec<&str> = request
+        .headers
+        .get(&"x-default".into())
+        .unwrap()
+        .iter()
+        .map(|value| value.as_str())
+        .collect();
+    assert_eq!(values, ["request"]);
+}
+
+#[tokio::test]
+async fn test_try_send_request() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("slow body")
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/slow");
+
+    // A single transfer at a time keeps the worker waiting with the second
+    // request, so the third one fills the queue.
+    let curl: CurlActor<ResponseHandler> = CurlActorBuilder::new().max_concurrent(1).build();
+    let make_easy = || {
+        let mut easy2 = Easy2::new(ResponseHandler::new());
+        easy2.url(url.as_str()).unwrap();
+        easy2
+    };
+
+    let mut responses = Vec::new();
+    for _ in 0..3 {
+        responses.push(curl.try_send_request(make_easy()).unwrap());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let easy2 = match curl.try_send_request(make_easy()) {
+        Err(tokio::sync::mpsc::error::TrySendError::Full(easy2)) => easy2,
+        _ => panic!("the queue should be full"),
+    };
+    assert_eq!(easy2.effective_url().unwrap(), Some(url.as_str()));
+
+    for response in responses {
+        assert_eq!(response.await.unwrap().response_code().unwrap(), 200);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelHandler {
+    label: &'static str,
+    order: Arc<std::sync::Mutex<Vec<&'static str>>>,
+}
+
+impl Handler for LabelHandler {
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.order.lock().unwrap().push(self.label);
+        Ok(data.len())
+    }
+}
+
+#[tokio::test]
+async fn test_send_request_prioritized() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("slow body")
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/fast"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string("fast body"))
+        .mount(&server)
+        .await;
+
+    let curl: CurlActor<LabelHandler> = CurlActorBuilder::new()
+        .capacity(4)
+        .max_concurrent(1)
+        .build();
+    let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let send = |label, path, priority| {
+        let curl = curl.clone();
+        let mut easy2 = Easy2::new(LabelHandler {
+            label,
+            order: order.clone(),
+        });
+        easy2.url(&format!("{}{}", server.uri(), path)).unwrap();
+        tokio::spawn(async move { curl.send_request_prioritized(easy2, priority).await })
+    };
+
+    // The slow request takes the only slot while the others queue up.
+    let mut requests = vec![send("slow", "/slow", Priority::Normal)];
+    for (label, priority) in [
+        ("low", Priority::Low),
+        ("normal", Priority::Normal),
+        ("high", Priority::High),
+    ] {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        requests.push(send(label, "/fast", priority));
+    }
+    for request in requests {
+        request.await.unwrap().unwrap();
+    }
+    assert_eq!(*order.lock().unwrap(), ["slow", "high", "normal", "low"]);
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ThreadHandler {
+    threads: Arc<std::sync::Mutex<std::collections::HashSet<std::thread::ThreadId>>>,
+}
+
+impl Handler for ThreadHandler {
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        let thread = std::thread::current().id();
+        self.threads.lock().unwrap().insert(thread);
+        Ok(data.len())
+    }
+}
+
+#[tokio::test]
+async fn test_actor_workers() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("slow body")
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/slow");
+
+    let curl: CurlActor<ThreadHandler> = CurlActorBuilder::new().capacity(4).workers(4).build();
+    let handler = ThreadHandler::default();
+    let threads = handler.threads.clone();
+    // Let every worker thread start waiting for the queue.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let requests: Vec<_> = (0..4)
+        .map(|_| {
+            let curl = curl.clone();
+            let mut easy2 = Easy2::new(handler.clone());
+            easy2.url(url.as_str()).unwrap();
+            tokio::spawn(async move { curl.send_request(easy2).await })
+        })
+        .collect();
+    for request in requests {
+        let easy2 = request.await.unwrap().unwrap();
+        assert_eq!(easy2.response_code().unwrap(), 200);
+    }
+    // The transfers were spread across the worker threads.
+    assert!(threads.lock().unwrap().len() > 1);
+}
+
+#[tokio::test]
+async fn test_transfer_handle_abort() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("slow body")
+                .set_delay(Duration::from_millis(500)),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/slow");
+
+    let curl = CurlActorBuilder::new().capacity(2).build();
+
+    let mut easy1 = Easy2::new(ResponseHandler::new());
+    easy1.url(url.as_str()).unwrap();
+    let mut easy2 = Easy2::new(ResponseHandler::new());
+    easy2.url(url.as_str()).unwrap();
+
+    let (aborted, aborted_handle) = curl.send_request_with_handle(easy1);
+    let (completed, completed_handle) = curl.send_request_with_handle(easy2);
+
+    let aborted = tokio::spawn(aborted);
+    let completed = tokio::spawn(completed);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    aborted_handle.abort();
+
+    let aborted = aborted.await.unwrap();
+    let mut completed = completed.await.unwrap().unwrap();
+
+    assert!(matches!(aborted, Err(Error::Cancelled)));
+    assert_eq!(completed.response_code().unwrap(), 200);
+    assert_eq!(
+        completed.get_mut().take(),
+        Some("slow body".as_bytes().to_vec())
+    );
+    assert!(aborted_handle.is_finished());
+    assert!(completed_handle.is_finished());
+}
+
+#[tokio::test]
+async fn test_default_timeout() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("slow body")
+                .set_delay(Duration::from_millis(1000)),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/slow");
+
+    let curl = CurlActorBuilder::new()
+        .default_timeout(Duration::from_millis(200))
+        .build();
+
+    let mut easy2 = Easy2::new(ResponseHandler::new());
+    easy2.url(url.as_str()).unwrap();
+    let start = Instant::now();
+    let result = curl.send_request(easy2).await;
+    assert!(matches!(result, Err(Error::Timeout)));
+    let err = result.unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(
+        std::io::Error::from(err).kind(),
+        std::io::ErrorKind::TimedOut
+    );
+    assert!(start.elapsed() < Duration::from_millis(800));
+
+    // An explicit per-request timeout overrides the actor default.
+    let mut easy2 = Easy2::new(ResponseHandler::new());
+    easy2.url(url.as_str()).unwrap();
+    let result = curl
+        .send_request_timeout(easy2, Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(result.response_code().unwrap(), 200);
+
+    // So does a deadline, which is missed as well as met here.
+    let mut easy2 = Easy2::new(ResponseHandler::new());
+    easy2.url(url.as_str()).unwrap();
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(400);
+    let result = curl.send_request_deadline(easy2, deadline).await;
+    assert!(matches!(result, Err(Error::Timeout)));
+    assert!(tokio::time::Instant::now() < deadline + Duration::from_millis(400));
+
+    let mut easy2 = Easy2::new(ResponseHandler::new());
+    easy2.url(url.as_str()).unwrap();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    let result = curl.send_request_deadline(easy2, deadline).await.unwrap();
+    assert_eq!(result.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_perform_response() {
+    const MOCK_BODY_RESPONSE: &str = r#"{"token":"12345"}"#;
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/response"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Created)
+                .insert_header("x-request-id", "abc")
+                .set_body_string(MOCK_BODY_RESPONSE),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/response");
+
+    let response = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform_response()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status, 201);
+    assert_eq!(response.headers.get("x-request-id").unwrap(), "abc");
+    assert_eq!(response.body, MOCK_BODY_RESPONSE.as_bytes());
+}
+
+#[tokio::test]
+async fn test_collecting_handler() {
+    const MOCK_BODY_RESPONSE: &str = r#"{"token":"12345"}"#;
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/headers"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .insert_header("x-request-id", "abc")
+                .set_body_raw(MOCK_BODY_RESPONSE, "application/json"),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/headers");
+
+    let curl = CurlActor::new();
+    let mut easy2 = Easy2::new(CollectingHandler::new());
+    easy2.url(url.as_str()).unwrap();
+
+    let result = curl.send_request(easy2).await.unwrap();
+    let handler = result.get_ref();
+
+    assert_eq!(
+        handler.header_value("Content-Type"),
+        Some("application/json")
+    );
+    assert_eq!(handler.header_value("X-Request-Id"), Some("abc"));
+    assert!(handler
+        .headers()
+        .iter()
+        .any(|(name, value)| name == "x-request-id" && value == "abc"));
+    assert_eq!(handler.body(), MOCK_BODY_RESPONSE.as_bytes());
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_perform_json() {
+    use std::collections::HashMap;
+
+    const MOCK_BODY_RESPONSE: &str = r#"{"token":"12345"}"#;
+    let server = start_mock_server("/json", MOCK_BODY_RESPONSE.to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/json");
+
+    let body: HashMap<String, String> = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform_json()
+        .await
+        .unwrap();
+    assert_eq!(body.get("token").map(String::as_str), Some("12345"));
+
+    let err = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform_json::<Vec<u32>>()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Decode(_)));
+}
+
+#[tokio::test]
+async fn test_perform_text() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/latin1"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_raw(
+            vec![b'c', b'a', b'f', 0xE9],
+            "text/plain; charset=ISO-8859-1",
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/utf8"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_raw("caf\u{e9}", "text/plain"))
+        .mount(&server)
+        .await;
+
+    for node in ["/latin1", "/utf8"] {
+        let url = format!("{}{}", server.uri(), node);
+        let text = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+            .url(url.as_str())
+            .unwrap()
+            .finalize()
+            .perform_text()
+            .await
+            .unwrap();
+        assert_eq!(text, "caf\u{e9}");
+    }
+}
+
+#[tokio::test]
+async fn test_stream_handler() {
+    use tokio::io::AsyncReadExt;
+
+    let mock_body: Vec<u8> = (0..256 * 1024).map(|n| (n % 251) as u8).collect();
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/stream"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_raw(mock_body.clone(), "application/octet-stream"),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/stream");
+
+    // A single chunk in flight forces the transfer to pause for the reader.
+    let (handler, mut reader) = StreamHandler::new(1);
+    let transfer = AsyncCurl::new(CurlActor::new(), handler)
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform_stream();
+
+    let consumer = async move {
+        let mut body = Vec::new();
+        let mut chunk = [0u8; 1024];
+        loop {
+            let len = reader.read(&mut chunk).await.unwrap();
+            if len == 0 {
+                break body;
+            }
+            body.extend_from_slice(&chunk[..len]);
+        }
+    };
+
+    let (transfer, body) = tokio::join!(transfer, consumer);
+    assert_eq!(transfer.unwrap().response_code().unwrap(), 200);
+    assert_eq!(body, mock_body);
+}
+
+#[tokio::test]
+async fn test_stream_handler_slow_reader() {
+    use tokio::io::AsyncReadExt;
+
+    let mock_body = vec![b'x'; 64 * 1024];
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow-reader"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_raw(mock_body.clone(), "application/octet-stream"),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/slow-reader");
+
+    // 1 KiB chunks give at least 64 pauses for a reader that is always behind.
+    let (handler, mut reader) = StreamHandler::new(1);
+    let transfer = AsyncCurl::new(CurlActor::new(), handler)
+        .url(url.as_str())
+        .unwrap()
+        .buffer_size(1024)
+        .unwrap()
+        .finalize()
+        .perform_stream();
+
+    let consumer = async move {
+        let mut body = Vec::new();
+        let mut chunk = [0u8; 1024];
+        loop {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            let len = reader.read(&mut chunk).await.unwrap();
+            if len == 0 {
+                break body;
+            }
+            body.extend_from_slice(&chunk[..len]);
+        }
+    };
+
+    let start = std::time::Instant::now();
+    let (transfer, body) = tokio::join!(transfer, consumer);
+    assert_eq!(transfer.unwrap().response_code().unwrap(), 200);
+    assert_eq!(body, mock_body);
+    // Freeing a slot resumes the transfer, not the 200ms tick of the perform loop.
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        start.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn test_decoding_handler() {
+    const GZIP_BODY: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xcb\x48\xcd\xc9\xc9\x57\x48\x49\x4d\xce\x4f\x49\x4d\x51\x28\xcf\x2f\xca\x49\x01\x00\x2a\x89\xbd\x92\x13\x00\x00\x00";
+    let server = MockServer::start().await;
+    for (node, encoding) in [("/gzip", "gzip"), ("/snappy", "snappy")] {
+        Mock::given(method("GET"))
+            .and(path(node))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::Ok)
+                    .insert_header("content-encoding", encoding)
+                    .set_body_raw(GZIP_BODY, "text/plain"),
+            )
+            .mount(&server)
+            .await;
+    }
+    let curl = CurlActor::new();
+    let url = format!("{}/gzip", server.uri());
+
+    let mut easy2 = AsyncCurl::new(curl.clone(), DecodingHandler::new(CollectingHandler::new()))
+        .url(url.as_str())
+        .unwrap()
+        .accept_encoding("gzip")
+        .unwrap()
+        .finalize()
+        .perform_decoded()
+        .await
+        .unwrap();
+    let handler = easy2.get_mut();
+    assert_eq!(handler.content_encoding(), Some("gzip"));
+    assert_eq!(handler.decoded_len(), 19);
+    assert_eq!(handler.get_mut().take_body(), b"hello decoded world");
+
+    // Decoding enabled behind the builder's back is seen from the transfer.
+    let mut build = AsyncCurl::new(curl.clone(), DecodingHandler::new(CollectingHandler::new()))
+        .url(url.as_str())
+        .unwrap();
+    build.easy_mut().accept_encoding("gzip").unwrap();
+    let mut easy2 = build.finalize().perform_decoded().await.unwrap();
+    assert_eq!(
+        easy2.get_mut().get_mut().take_body(),
+        b"hello decoded world"
+    );
+
+    // Without decoding enabled the gzip body would reach the handler as is.
+    let err = AsyncCurl::new(curl.clone(), DecodingHandler::new(CollectingHandler::new()))
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform_decoded()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Decode(_)), "{err}");
+
+    let url = format!("{}/snappy", server.uri());
+    let err = AsyncCurl::new(curl, DecodingHandler::new(CollectingHandler::new()))
+        .url(url.as_str())
+        .unwrap()
+        .accept_encoding("")
+        .unwrap()
+        .finalize()
+        .perform_decoded()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Decode(_)), "{err}");
+}
+
+/// PausingHandler pauses the transfer on every other chunk it is given.
+#[derive(Debug, Default)]
+pub struct PausingHandler {
+    data: Vec<u8>,
+    paused: bool,
+}
+
+impl Handler for PausingHandler {
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.paused = !self.paused;
+        if self.paused {
+            return Err(WriteError::Pause);
+        }
+        self.data.extend_from_slice(data);
+        Ok(data.len())
+    }
+}
+
+#[tokio::test]
+async fn test_tee_handler() {
+    let mock_body: Vec<u8> = (0..64 * 1024).map(|n| (n % 251) as u8).collect();
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/tee"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_raw(mock_body.clone(), "application/octet-stream"),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}/tee", server.uri());
+
+    let handler = TeeHandler::new(CollectingHandler::new(), PausingHandler::default());
+    let easy2 = AsyncCurl::new(CurlActor::new(), handler)
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    let (first, second) = easy2.get_ref().get_ref();
+    // The chunks the second handler paused on reached the first one only once.
+    assert_eq!(first.body(), mock_body);
+    assert_eq!(second.data, mock_body);
+}
+
+#[cfg(feature = "sha2")]
+#[tokio::test]
+async fn test_hashing_handler() {
+    use crate::handler::HashingHandler;
+
+    let server = start_mock_server("/hash", "abc".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/hash");
+
+    let handler = TeeHandler::new(CollectingHandler::new(), HashingHandler::sha256());
+    let easy2 = AsyncCurl::new(CurlActor::new(), handler)
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    let (body, hash) = easy2.get_ref().get_ref();
+    assert_eq!(body.body(), b"abc");
+    assert_eq!(hash.len(), 3);
+    assert_eq!(
+        hash.digest_hex(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert!(hash.matches_hex("BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD"));
+    assert!(!HashingHandler::sha256().matches_hex(&hash.digest_hex()));
+}
+
+#[tokio::test]
+async fn test_lines_handler() {
+    let lines: Vec<String> = (0..2000).map(|n| format!(r#"{{"n":{n}}}"#)).collect();
+    let mock_body = format!("{}\r\nlast", lines.join("\n"));
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/lines"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string(mock_body))
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/lines");
+
+    let (handler, mut receiver) = LinesHandler::new(1, 64);
+    let transfer = AsyncCurl::new(CurlActor::new(), handler)
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform_lines();
+    let consumer = async move {
+        let mut received = Vec::new();
+        while let Some(line) = receiver.recv().await {
+            received.push(line);
+        }
+        received
+    };
+
+    let (transfer, received) = tokio::join!(transfer, consumer);
+    assert_eq!(transfer.unwrap().response_code().unwrap(), 200);
+    // The unterminated last line is sent once the transfer has finished.
+    assert_eq!(received.len(), lines.len() + 1);
+    assert_eq!(received[..lines.len()], lines[..]);
+    assert_eq!(received[lines.len()], "last");
+
+    // A line over the limit aborts the transfer.
+    let (handler, _receiver) = LinesHandler::new(1, 4);
+    let exceeded = handler.exceeded_flag();
+    let err = AsyncCurl::new(CurlActor::new(), handler)
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform_lines()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::HandlerAborted(_)));
+    assert!(exceeded.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_download_to() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/file"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string("hello world"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::PartialContent)
+                .insert_header("Content-Range", "bytes 6-10/11")
+                .set_body_string("world"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(StatusCode::NotFound))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/broken"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::InternalServerError).set_body_string("oops"),
+        )
+        .mount(&server)
+        .await;
+
+    let file = std::env::temp_dir().join(format!("async-curl-download-{}", std::process::id()));
+
+    let url = format!("{}/file", server.uri());
+    let written = AsyncCurl::new(CurlActor::new(), FileHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .download_to(&file)
+        .await
+        .unwrap();
+    assert_eq!(written, 11);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello world");
+
+    std::fs::write(&file, "hello ").unwrap();
+    let url = format!("{}/rest", server.uri());
+    let written = AsyncCurl::new(CurlActor::new(), FileHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .resume_from(6)
+        .unwrap()
+        .download_to(&file)
+        .await
+        .unwrap();
+    assert_eq!(written, 5);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello world");
+
+    let url = format!("{}/missing", server.uri());
+    let result = AsyncCurl::new(CurlActor::new(), FileHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .fail_on_error(true)
+        .unwrap()
+        .download_to(&file)
+        .await;
+    assert!(result.is_err());
+    assert!(!file.exists());
+
+    // Without `fail_on_error`, the error page is not taken for the file either.
+    let url = format!("{}/broken", server.uri());
+    let err = AsyncCurl::new(CurlActor::new(), FileHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .download_to(&file)
+        .await
+        .unwrap_err();
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_HTTP_RETURNED_ERROR));
+    assert!(!file.exists());
+}
+
+#[tokio::test]
+async fn test_upload_stream() {
+    use tokio::io::AsyncWriteExt;
+
+    let server = MockServer::start().await;
+    let body = "streamed upload body ".repeat(1000);
+    Mock::given(method("PUT"))
+        .and(path("/upload"))
+        .and(body_string(body.clone()))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+
+    let url = format!("{}/upload", server.uri());
+    let easy = AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(CollectingHandler::new()),
+    )
+    .url(url.as_str())
+    .unwrap()
+    .upload_stream(std::io::Cursor::new(body.clone()), Some(body.len() as u64))
+    .unwrap()
+    .finalize()
+    .perform()
+    .await;
+    assert_eq!(easy.unwrap().response_code().unwrap(), 200);
+
+    // A reader that is not always ready pauses the upload until it has data.
+    let (mut writer, reader) = tokio::io::duplex(4096);
+    let chunks = body.clone();
+    let producer = tokio::spawn(async move {
+        for chunk in chunks.as_bytes().chunks(4096) {
+            writer.write_all(chunk).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+    let easy = AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(CollectingHandler::new()),
+    )
+    .url(url.as_str())
+    .unwrap()
+    .upload_stream(reader, None)
+    .unwrap()
+    .finalize()
+    .perform()
+    .await;
+    producer.await.unwrap();
+    assert_eq!(easy.unwrap().response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_upload_reader() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/upload"))
+        .and(body_string("uploaded body"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/moved"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::TemporaryRedirect).insert_header("location", "/form"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/form"))
+        .and(body_string("posted body"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+
+    let easy = AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(CollectingHandler::new()),
+    )
+    .url(format!("{}/upload", server.uri()).as_str())
+    .unwrap()
+    .upload_reader(std::io::Cursor::new(b"uploaded body".to_vec()))
+    .upload(true)
+    .unwrap()
+    .in_filesize(13)
+    .unwrap()
+    .finalize()
+    .perform()
+    .await;
+    assert_eq!(easy.unwrap().response_code().unwrap(), 200);
+
+    // The body is rewound to be posted again to the redirect target.
+    struct Source {
+        body: std::io::Cursor<&'static [u8]>,
+        seeks: Arc<AtomicUsize>,
+    }
+    impl ReadSource for Source {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, curl::easy::ReadError> {
+            Ok(std::io::Read::read(&mut self.body, buf).unwrap())
+        }
+
+        fn seek(&mut self, whence: std::io::SeekFrom) -> curl::easy::SeekResult {
+            self.seeks.fetch_add(1, Ordering::SeqCst);
+            std::io::Seek::seek(&mut self.body, whence).unwrap();
+            curl::easy::SeekResult::Ok
+        }
+    }
+    let seeks = Arc::new(AtomicUsize::new(0));
+    let easy = AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(CollectingHandler::new()),
+    )
+    .url(format!("{}/moved", server.uri()).as_str())
+    .unwrap()
+    .upload_reader(Source {
+        body: std::io::Cursor::new(b"posted body"),
+        seeks: seeks.clone(),
+    })
+    .post(true)
+    .unwrap()
+    .post_field_size(11)
+    .unwrap()
+    .follow_location(true)
+    .unwrap()
+    .finalize()
+    .perform()
+    .await;
+    assert_eq!(easy.unwrap().response_code().unwrap(), 200);
+    assert_eq!(seeks.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_connect() {
+    let server = start_mock_server("/raw", "raw body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}/raw", server.uri());
+
+    let mut connection = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .connect()
+        .await
+        .unwrap();
+    connection
+        .send_all(b"GET /raw HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut response = Vec::new();
+    let mut buf = [0; 1024];
+    loop {
+        let len = connection.recv(&mut buf).await.unwrap();
+        if len == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..len]);
+    }
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("raw body"));
+}
+
+#[cfg(feature = "websocket")]
+#[tokio::test]
+async fn test_websocket_supported() {
+    let result = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url("ws://127.0.0.1:1/socket")
+        .unwrap()
+        .finalize()
+        .websocket()
+        .await;
+    let err = result.unwrap_err();
+    if crate::websocket::supported() {
+        // Nothing listens on the port, so the upgrade fails to connect.
+        assert_eq!(err.curl_code(), Some(curl_sys::CURLE_COULDNT_CONNECT));
+    } else {
+        assert_eq!(err.curl_code(), Some(4));
+        assert!(err.detail().unwrap().contains("does not support WebSocket"));
+    }
+}
+
+#[cfg(feature = "websocket")]
+#[test]
+fn test_websocket_max_frame_size() {
+    use crate::websocket::{check_frame_size, DEFAULT_MAX_FRAME_SIZE};
+
+    assert!(check_frame_size(DEFAULT_MAX_FRAME_SIZE as u64, DEFAULT_MAX_FRAME_SIZE).is_ok());
+    // A header may claim up to 2^63 bytes, which is refused instead of allocated.
+    let err = check_frame_size(i64::MAX as u64, DEFAULT_MAX_FRAME_SIZE).unwrap_err();
+    assert_eq!(err.code(), curl_sys::CURLE_RECV_ERROR);
+    assert!(err
+        .extra_description()
+        .unwrap()
+        .contains("larger than the maximum"));
+}
+
+#[cfg(feature = "bytes")]
+#[tokio::test]
+async fn test_bytes_handler() {
+    use crate::handler::BytesHandler;
+
+    let body = "x".repeat(100_000);
+    let server = start_mock_server("/bytes", body.clone(), StatusCode::Ok).await;
+    let url = format!("{}/bytes", server.uri());
+
+    let mut easy = AsyncCurl::new(CurlActor::new(), BytesHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy.get_ref().body(), body.as_bytes());
+    let bytes = easy.get_mut().take_bytes();
+    assert_eq!(bytes, body.as_bytes());
+    assert!(easy.get_ref().body().is_empty());
+    let shared = bytes.clone();
+    assert_eq!(shared.as_ptr(), bytes.as_ptr());
+}
+
+#[test]
+fn test_collecting_handler_reserves_content_length() {
+    let mut handler = CollectingHandler::new();
+    assert!(handler.header(b"HTTP/1.1 200 OK\r\n"));
+    assert!(handler.header(b"content-length: 100000\r\n"));
+    assert_eq!(handler.header_value("Content-Length"), Some("100000"));
+    assert!(handler.take_body().capacity() >= 100_000);
+
+    let mut handler = CollectingHandler::new();
+    assert!(handler.header(b"Content-Length: 99999999999\r\n"));
+    assert!(handler.take_body().capacity() < 100_000_000);
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn test_curl_service() {
+    use crate::service::CurlService;
+    use tower_service::Service;
+
+    let server = start_mock_server("/service", "service".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/service");
+
+    let mut service = CurlService::new(CurlActor::new());
+    let mut responses = Vec::new();
+    for _ in 0..3 {
+        let mut easy2 = Easy2::new(ResponseHandler::new());
+        easy2.url(url.as_str()).unwrap();
+        std::future::poll_fn(|cx| service.poll_ready(cx))
+            .await
+            .unwrap();
+        responses.push(service.call(easy2));
+    }
+    for response in responses {
+        let mut easy2 = response.await.unwrap();
+        assert_eq!(easy2.response_code().unwrap(), 200);
+        assert_eq!(easy2.get_mut().take().unwrap(), b"service");
+    }
+}
+
+#[cfg(feature = "stream")]
+#[tokio::test]
+async fn test_stream() {
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_core::Stream;
+
+    struct Requests(Vec<Easy2<ResponseHandler>>);
+
+    impl Stream for Requests {
+        type Item = Easy2<ResponseHandler>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(self.0.pop())
+        }
+    }
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_delay(Duration::from_millis(500)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/fast"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+
+    // Popped from the back, so the slow request is sent first.
+    let requests = ["/fast", "/slow"]
+        .iter()
+        .map(|node| {
+            let mut easy2 = Easy2::new(ResponseHandler::new());
+            easy2.url(&format!("{}{}", server.uri(), node)).unwrap();
+            easy2
+        })
+        .collect();
+
+    let mut responses = Box::pin(CurlActor::new().stream(Requests(requests)));
+    let mut finished = Vec::new();
+    while let Some(response) = std::future::poll_fn(|cx| responses.as_mut().poll_next(cx)).await {
+        let easy2 = response.unwrap();
+        finished.push(easy2.effective_url().unwrap().unwrap().to_string());
+    }
+    assert_eq!(
+        finished,
+        [
+            format!("{}{}", server.uri(), "/fast"),
+            format!("{}{}", server.uri(), "/slow")
+        ]
+    );
+}
+
+#[cfg(feature = "bytes")]
+#[tokio::test]
+async fn test_send_request_coalesced() {
+    use crate::handler::BytesHandler;
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/coalesced"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("shared")
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/coalesced");
+
+    let actor = CurlActor::new();
+    let built = Arc::new(AtomicUsize::new(0));
+    let calls = (0..5).map(|_| {
+        let actor = actor.clone();
+        let built = built.clone();
+        let url = url.clone();
+        tokio::spawn(async move {
+            actor
+                .send_request_coalesced(url.clone(), || {
+                    built.fetch_add(1, Ordering::SeqCst);
+                    let mut easy2 = Easy2::new(BytesHandler::new());
+                    easy2.url(&url).unwrap();
+                    easy2
+                })
+                .await
+        })
+    });
+    for call in calls.collect::<Vec<_>>() {
+        assert_eq!(call.await.unwrap().unwrap(), "shared");
+    }
+    assert_eq!(built.load(Ordering::SeqCst), 1);
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+#[test]
+fn test_dry_run() {
+    let plan = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url("https://example.com/items")
+        .unwrap()
+        .query(&[("q", "a b")])
+        .unwrap()
+        .bearer_auth("token")
+        .unwrap()
+        .form(&[("name", "item")])
+        .unwrap()
+        .follow_location(true)
+        .unwrap()
+        .max_redirections(3)
+        .unwrap()
+        .max_redirections(5)
+        .unwrap()
+        .finalize()
+        .dry_run();
+    assert_eq!(
+        plan.url.as_deref(),
+        Some("https://example.com/items?q=a%20b")
+    );
+    assert_eq!(plan.method, "POST");
+    assert_eq!(
+        plan.headers,
+        [
+            "Authorization: Bearer token",
+            "Content-Type: application/x-www-form-urlencoded"
+        ]
+    );
+    assert_eq!(plan.body.as_deref(), Some(&b"name=item"[..]));
+    assert_eq!(
+        plan.options,
+        [
+            ("follow_location", "true".to_string()),
+            ("max_redirections", "5".to_string())
+        ]
+    );
+
+    let plan = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url("https://example.com/items/1")
+        .unwrap()
+        .nobody(true)
+        .unwrap()
+        .finalize()
+        .dry_run();
+    assert_eq!(plan.method, "HEAD");
+    assert!(plan.body.is_none());
+    assert!(plan.option("timeout").is_none());
+}
+
+#[test]
+fn test_dry_run_security_options() {
+    let plan = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url("https://example.com/upload")
+        .unwrap()
+        .allowed_redirect_protocols(&[Protocol::Https])
+        .unwrap()
+        .proxy_ssl_verify_peer(false)
+        .unwrap()
+        .cainfo("/etc/ssl/certs/ca.pem")
+        .unwrap()
+        .basic_auth("user", "secret")
+        .unwrap()
+        .upload(true)
+        .unwrap()
+        .finalize()
+        .dry_run();
+    assert_eq!(plan.method, "PUT");
+    assert_eq!(plan.option("redir_protocols"), Some("https"));
+    assert_eq!(plan.option("proxy_ssl_verify_peer"), Some("false"));
+    assert_eq!(plan.option("cainfo"), Some("/etc/ssl/certs/ca.pem"));
+    assert!(plan.option("http_auth").unwrap().contains("basic: true"));
+    assert_eq!(plan.option("username"), Some("user"));
+    assert_eq!(plan.option("upload"), Some("true"));
+    assert!(plan
+        .options
+        .iter()
+        .all(|(_, value)| !value.contains("secret")));
+}
+
+/// MockActor answers every request with a canned body and records its URL.
+#[derive(Clone, Default)]
+struct MockActor {
+    urls: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+#[async_trait::async_trait]
+impl Actor<CollectingHandler> for MockActor {
+    async fn send_request(
+        &self,
+        mut easy2: Easy2<CollectingHandler>,
+    ) -> Result<Easy2<CollectingHandler>, Error<CollectingHandler>> {
+        let url = easy2.effective_url()?.unwrap_or_default().to_string();
+        self.urls.lock().unwrap().push(url);
+        easy2.get_mut().write(b"canned").unwrap();
+        Ok(easy2)
+    }
+}
+
+#[tokio::test]
+async fn test_mock_actor() {
+    let actor = MockActor::default();
+    let text = AsyncCurl::new(actor.clone(), CollectingHandler::new())
+        .url("https://example.com/items")
+        .unwrap()
+        .finalize()
+        .perform_text()
+        .await
+        .unwrap();
+    assert_eq!(text, "canned");
+    assert_eq!(*actor.urls.lock().unwrap(), ["https://example.com/items"]);
+}
+
+#[tokio::test]
+async fn test_dyn_actor() {
+    let mock = MockActor::default();
+    let actor: Arc<dyn Actor<CollectingHandler>> = Arc::new(mock.clone());
+    let text = AsyncCurl::new(actor, CollectingHandler::new())
+        .url("https://example.com/items")
+        .unwrap()
+        .finalize()
+        .perform_text()
+        .await
+        .unwrap();
+    assert_eq!(text, "canned");
+    assert_eq!(mock.urls.lock().unwrap().len(), 1);
+
+    let actor: Arc<dyn Actor<CollectingHandler>> = Arc::new(CurlActor::new());
+    let server = start_mock_server("/dyn", "body".to_string(), StatusCode::Ok).await;
+    let text = AsyncCurl::new(actor, CollectingHandler::new())
+        .url(&format!("{}/dyn", server.uri()))
+        .unwrap()
+        .finalize()
+        .perform_text()
+        .await
+        .unwrap();
+    assert_eq!(text, "body");
+}
+
+#[tokio::test]
+async fn test_http_cache() {
+    use crate::cache::HttpCache;
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/etag"))
+        .and(header("If-None-Match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(StatusCode::NotModified))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/etag"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .insert_header("ETag", "\"v1\"")
+                .set_body_string("config"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/modified"))
+        .and(header_exists("If-Modified-Since"))
+        .respond_with(ResponseTemplate::new(StatusCode::NotModified))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/modified"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .insert_header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+                .set_body_string("metadata"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let cache = HttpCache::new();
+    for (node, body) in [("/etag", "config"), ("/modified", "metadata")] {
+        let url = format!("{}{}", server.uri(), node);
+        for _ in 0..2 {
+            let response = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+                .url(url.as_str())
+                .unwrap()
+                .finalize()
+                .perform_cached(&cache)
+                .await
+                .unwrap();
+            assert_eq!(response.status, 200);
+            assert_eq!(response.body, body.as_bytes());
+        }
+    }
+    assert_eq!(cache.len(), 2);
+}
+
+#[tokio::test]
+async fn test_circuit_breaker() {
+    use crate::circuit::{CircuitBreaker, CircuitState};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/flaky"))
+        .respond_with(ResponseTemplate::new(StatusCode::ServiceUnavailable))
+        .up_to_n_times(3)
+        .expect(3)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/flaky"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let url = format!("{}/flaky", server.uri());
+
+    let breaker = CircuitBreaker::new(CurlActor::<CollectingHandler>::new())
+        .failure_threshold(2)
+        .cooldown(Duration::from_millis(200));
+    let get = || async {
+        AsyncCurl::new(breaker.clone(), CollectingHandler::new())
+            .url(url.as_str())
+            .unwrap()
+            .finalize()
+            .perform()
+            .await
+            .map(|easy2| easy2.response_code().unwrap())
+    };
+
+    assert_eq!(get().await.unwrap(), 503);
+    assert_eq!(breaker.state(), CircuitState::Closed);
+    assert_eq!(get().await.unwrap(), 503);
+    assert_eq!(breaker.state(), CircuitState::Open);
+    assert!(matches!(get().await, Err(Error::CircuitOpen)));
+
+    // The probe fails, which opens the circuit for another cooldown.
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    assert_eq!(get().await.unwrap(), 503);
+    assert_eq!(breaker.state(), CircuitState::Open);
+    assert!(matches!(get().await, Err(Error::CircuitOpen)));
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(get().await.unwrap(), 200);
+    assert_eq!(breaker.state(), CircuitState::Closed);
+}
+
+#[tokio::test]
+async fn test_bounded_response_handler() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/large"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string("x".repeat(4096)))
+        .mount(&server)
+        .await;
+
+    let url = format!("{}/large", server.uri());
+    let easy = AsyncCurl::new(CurlActor::new(), BoundedResponseHandler::new(4096))
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert!(!easy.get_ref().limit_exceeded());
+    assert_eq!(easy.get_ref().get_data().len(), 4096);
+
+    let handler = BoundedResponseHandler::new(1024);
+    let exceeded = handler.exceeded_flag();
+    let result = AsyncCurl::new(CurlActor::new(), handler)
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await;
+    assert!(matches!(result, Err(Error::HandlerAborted(_))));
+    assert!(exceeded.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_on_progress() {
+    let body = "x".repeat(64 * 1024);
+    let server = start_mock_server("/progress", body.clone(), StatusCode::Ok).await;
+
+    let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let progress = reports.clone();
+    let url = format!("{}/progress", server.uri());
+    AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(CollectingHandler::new()),
+    )
+    .url(url.as_str())
+    .unwrap()
+    .on_progress(move |info| progress.lock().unwrap().push(info))
+    .unwrap()
+    .finalize()
+    .perform()
+    .await
+    .unwrap();
+
+    let reports = reports.lock().unwrap();
+    let last = reports.last().unwrap();
+    assert_eq!(last.downloaded, body.len() as u64);
+    assert_eq!(last.download_total, body.len() as u64);
+}
+
+#[tokio::test]
+async fn test_perform_with_progress() {
+    let body = "x".repeat(64 * 1024);
+    let server = start_mock_server("/progress", body.clone(), StatusCode::Ok).await;
+
+    let url = format!("{}/progress", server.uri());
+    let (response, mut progress) = AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(CollectingHandler::new()),
+    )
+    .url(url.as_str())
+    .unwrap()
+    .finalize()
+    .perform_with_progress();
+
+    let watcher = tokio::spawn(async move {
+        let mut last = ProgressInfo::default();
+        while progress.changed().await.is_ok() {
+            last = *progress.borrow_and_update();
+        }
+        last
+    });
+    response.await.unwrap();
+
+    let last = watcher.await.unwrap();
+    assert_eq!(last.downloaded, body.len() as u64);
+}
+
+#[tokio::test]
+async fn test_send_request_retry() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/flaky"))
+        .respond_with(ResponseTemplate::new(StatusCode::InternalServerError))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/flaky"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+
+    let actor = CurlActor::new();
+    let attempts = AtomicUsize::new(0);
+    let url = format!("{}/flaky", server.uri());
+    let make_easy = || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        let mut easy2 = Easy2::new(ResponseHandler::new());
+        easy2.url(url.as_str()).unwrap();
+        easy2.fail_on_error(true).unwrap();
+        easy2
+    };
+
+    let policy = RetryPolicy::new()
+        .base_delay(Duration::from_millis(10))
+        .retryable(|err| err.curl_code() == Some(curl_sys::CURLE_HTTP_RETURNED_ERROR));
+    let easy2 = actor.send_request_retry(make_easy, &policy).await.unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+    // The default policy gives up on errors that are not worth retrying.
+    attempts.store(0, Ordering::SeqCst);
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(StatusCode::NotFound))
+        .mount(&server)
+        .await;
+    let url = format!("{}/missing", server.uri());
+    let make_easy = || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        let mut easy2 = Easy2::new(ResponseHandler::new());
+        easy2.url(url.as_str()).unwrap();
+        easy2.fail_on_error(true).unwrap();
+        easy2
+    };
+    let result = actor
+        .send_request_retry(make_easy, &RetryPolicy::new())
+        .await;
+    assert!(result.is_err());
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+    // Failing to connect is retried until the attempts run out.
+    attempts.store(0, Ordering::SeqCst);
+    let make_easy = || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        let mut easy2 = Easy2::new(ResponseHandler::new());
+        easy2.url("http://127.0.0.1:1/").unwrap();
+        easy2
+    };
+    let policy = RetryPolicy::new()
+        .max_attempts(3)
+        .base_delay(Duration::from_millis(10))
+        .jitter(0.5);
+    let result = actor.send_request_retry(make_easy, &policy).await;
+    assert!(result.unwrap_err().is_couldnt_connect());
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retry_after() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/throttled"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::TooManyRequests).insert_header("Retry-After", "1"),
+        )
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/throttled"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+
+    let actor = CurlActor::new();
+    let url = format!("{}/throttled", server.uri());
+    let make_easy = || {
+        let mut easy2 = Easy2::new(ResponseHandler::new());
+        easy2.url(url.as_str()).unwrap();
+        easy2
+    };
+    let policy = RetryPolicy::new()
+        .base_delay(Duration::from_millis(10))
+        .respect_retry_after(true);
+
+    let start = Instant::now();
+    let easy2 = actor.send_request_retry(make_easy, &policy).await.unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+    assert!(start.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_rate_limit() {
+    let server = start_mock_server("/limited", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/limited");
+
+    let curl: CurlActor<ResponseHandler> =
+        CurlActorBuilder::new().capacity(4).rate_limit(5, 1).build();
+    let send = || {
+        let mut easy2 = Easy2::new(ResponseHandler::new());
+        easy2.url(url.as_str()).unwrap();
+        curl.send_request(easy2)
+    };
+
+    let start = Instant::now();
+    let watch_queue = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        curl.queued()
+    };
+    let (first, second, third, queued) = tokio::join!(send(), send(), send(), watch_queue);
+    let elapsed = start.elapsed();
+
+    assert!(first.is_ok() && second.is_ok() && third.is_ok());
+    // The first request takes the only token, the others wait 200ms each.
+    assert!(queued >= 1);
+    assert!(elapsed >= Duration::from_millis(400));
+    assert_eq!(curl.queued(), 0);
+    assert_eq!(curl.in_flight(), 0);
+}
+
+#[tokio::test]
+async fn test_timings() {
+    let server = start_mock_server("/timings", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/timings");
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+
+    let timings = easy2.timings().unwrap();
+    assert!(timings.total > Duration::ZERO);
+    assert!(timings.connect <= timings.starttransfer);
+    assert!(timings.starttransfer <= timings.total);
+    assert_eq!(timings.appconnect, Duration::ZERO);
+    assert_eq!(timings.redirect, Duration::ZERO);
+}
+
+#[tokio::test]
+async fn test_effective_url() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/old"))
+        .respond_with(ResponseTemplate::new(StatusCode::Found).insert_header("Location", "/new"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/new"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string("moved"))
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/old");
+
+    let response = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .follow_location(true)
+        .unwrap()
+        .finalize()
+        .perform_response()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"moved");
+    assert_eq!(
+        response.effective_url,
+        Some(format!("{}{}", server.uri(), "/new"))
+    );
+    assert_eq!(response.redirect_count, 1);
+}
+
+#[tokio::test]
+async fn test_redirect_chain() {
+    use crate::response::RedirectHop;
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/authorize"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Found).insert_header("Location", "/login?next=1"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/login"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::PermanentRedirect).insert_header("Location", "/done"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/done"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string("done"))
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/authorize");
+
+    let response = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .follow_location(true)
+        .unwrap()
+        .finalize()
+        .perform_response()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.redirect_count, 2);
+    assert_eq!(
+        response.redirects,
+        [
+            RedirectHop {
+                status: 302,
+                location: "/login?next=1".to_string(),
+            },
+            RedirectHop {
+                status: 308,
+                location: "/done".to_string(),
+            },
+        ]
+    );
+
+    // A redirect that is not followed is the response itself.
+    let response = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform_response()
+        .await
+        .unwrap();
+    assert_eq!(response.status, 302);
+    assert!(response.redirects.is_empty());
+}
+
+#[tokio::test]
+async fn test_connection_info() {
+    let server = start_mock_server("/connection", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/connection");
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    let info = easy2.connection_info().unwrap();
+    assert_eq!(info.primary_ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(info.primary_port, server.address().port());
+    assert_eq!(info.local_ip.as_deref(), Some("127.0.0.1"));
+    assert!(info.local_port > 0);
+    assert!(!info.is_reused());
+}
+
+#[tokio::test]
+async fn test_transfer_sizes() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/sizes"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string("x".repeat(1000)))
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/sizes");
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .post_fields_copy(b"hello")
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    let sizes = easy2.sizes().unwrap();
+    assert_eq!(sizes.download, 1000);
+    assert_eq!(sizes.content_length_download, Some(1000));
+    assert_eq!(sizes.upload, 5);
+    assert_eq!(sizes.content_length_upload, Some(5));
+    assert!(!sizes.is_truncated());
+}
+
+#[tokio::test]
+async fn test_verify_content_length() {
+    use std::io::{Read, Write};
+
+    let server = start_mock_server("/verify", "x".repeat(1000), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/verify");
+
+    let mut easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .verify_content_length(true)
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.get_mut().take().unwrap().len(), 1000);
+
+    // The Content-Length of a HEAD response announces a body that never comes.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/head", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = stream.read(&mut [0; 1024]).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n")
+            .unwrap();
+    });
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .nobody(true)
+        .unwrap()
+        .verify_content_length(true)
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.sizes().unwrap().content_length_download, Some(10));
+}
+
+#[tokio::test]
+async fn test_cert_chain() {
+    let server = start_mock_server("/certs", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/certs");
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert!(easy2.cert_chain().unwrap().is_empty());
+
+    // Without TLS there is no chain to gather, even when asked for.
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .certinfo(true)
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert!(easy2.cert_chain().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_cookies() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/login"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .insert_header("set-cookie", "session=abc; Path=/"),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/login");
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert!(easy2.cookies().unwrap().is_empty());
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .cookie_file("")
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    let cookies = easy2.cookies().unwrap();
+    assert_eq!(cookies.len(), 1);
+    assert!(cookies[0].starts_with("127.0.0.1\t"));
+    assert!(cookies[0].ends_with("\tsession\tabc"));
+}
+
+#[test]
+fn test_url_encode() {
+    assert_eq!(
+        url_encode("name=Jürgen & co/1~2"),
+        "name%3DJ%C3%BCrgen%20%26%20co%2F1~2"
+    );
+    assert_eq!(url_encode(""), "");
+    assert_eq!(
+        url_decode("name%3DJ%C3%BCrgen%20%26%20co%2F1~2"),
+        "name=Jürgen & co/1~2".as_bytes()
+    );
+    assert_eq!(url_decode("%FF+"), b"\xff+");
+}
+
+#[tokio::test]
+async fn test_cookie_store() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/login"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .insert_header("set-cookie", "session=abc; Path=/"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/profile"))
+        .and(header("cookie", "session=abc"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+    let actor = CurlActor::new();
+    let jar = CookieJar::new();
+
+    AsyncCurl::new(actor.clone(), ResponseHandler::new())
+        .url(format!("{}/login", server.uri()).as_str())
+        .unwrap()
+        .post(true)
+        .unwrap()
+        .cookie_store(&jar)
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    let cookies = jar.cookies();
+    assert_eq!(cookies.len(), 1);
+    assert!(cookies[0].ends_with("\tsession\tabc"));
+
+    let easy2 = AsyncCurl::new(actor.clone(), ResponseHandler::new())
+        .url(format!("{}/profile", server.uri()).as_str())
+        .unwrap()
+        .cookie_store(&jar)
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+
+    // Without the jar the session cookie is not sent.
+    jar.clear();
+    let easy2 = AsyncCurl::new(actor, ResponseHandler::new())
+        .url(format!("{}/profile", server.uri()).as_str())
+        .unwrap()
+        .cookie_store(&jar)
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 404);
+    assert!(jar.cookies().is_empty());
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_json_body() {
+    use std::collections::HashMap;
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/json"))
+        .and(header("content-type", "application/json"))
+        .and(body_json(serde_json::json!({"token": "12345"})))
+        .respond_with(ResponseTemplate::new(StatusCode::Created))
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/json");
+
+    let body = HashMap::from([("token", "12345")]);
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .json_body(&body)
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 201);
+
+    // Map keys must be strings in JSON.
+    let err = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .json_body(&HashMap::from([(vec![1u8], 1u8)]))
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Encode(_)));
+}
+
+#[tokio::test]
+async fn test_query() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/search"))
+        .and(query_param("q", "a&b=c d"))
+        .and(query_param("lang", "en"))
+        .and(query_param("page", "2"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/search?lang=en#top");
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .query(&[("q", "a&b=c d"), ("page", "2")])
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+    assert_eq!(
+        easy2.effective_url().unwrap().unwrap(),
+        format!(
+            "{}{}",
+            server.uri(),
+            "/search?lang=en&q=a%26b%3Dc%20d&page=2#top"
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_form() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/login"))
+        .and(header("content-type", "application/x-www-form-urlencoded"))
+        .and(body_string("user=jane%20doe&pass=a%26b%3Dc"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/login");
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .form(&[("user", "jane doe"), ("pass", "a&b=c")])
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_auth() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/basic"))
+        .and(header("authorization", "Basic dXNlcjpwYXNz"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/bearer"))
+        .and(header("authorization", "Bearer token123"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+
+    let url = format!("{}{}", server.uri(), "/basic");
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .basic_auth("user", "pass")
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+
+    let url = format!("{}{}", server.uri(), "/bearer");
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .bearer_auth("token123")
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_header() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/headers"))
+        .and(header("x-first", "1"))
+        .and(header("x-second", "2"))
+        .and(header("x-third", "3"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+    let url = format!("{}{}", server.uri(), "/headers");
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .header("x-first", "1")
+        .unwrap()
+        .headers([
+            ("x-second".to_string(), "2".to_string()),
+            ("x-third".to_string(), "3".to_string()),
+        ])
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_headers_from_map() {
+    use std::io::{BufRead, BufReader, Write};
+
+    // wiremock keeps a single value per request header, so read the raw request instead.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/map", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            request.push(line.trim_end().to_string());
+        }
+        (&stream)
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        request
+    });
+
+    let mut map = http::HeaderMap::new();
+    map.insert("x-single", "1".parse().unwrap());
+    map.append("x-multi", "a".parse().unwrap());
+    map.append("x-multi", "b".parse().unwrap());
+
+    let response = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .headers_from_map(&map)
+        .unwrap()
+        .finalize()
+        .perform_response()
+        .await
+        .unwrap();
+    assert_eq!(response.status, 200);
+
+    let request = server.join().unwrap();
+    assert!(request.contains(&"x-single: 1".to_string()));
+    assert!(request.contains(&"x-multi: a".to_string()));
+    assert!(request.contains(&"x-multi: b".to_string()));
+
+    // Non-ASCII values are kept as opaque bytes.
+    let headers = [("x-name".to_string(), "caf\u{e9}".to_string())];
+    let response = Response::from_parts(200, &headers, Vec::new());
+    assert_eq!(
+        response.headers.get("x-name").unwrap().as_bytes(),
+        "caf\u{e9}".as_bytes()
+    );
+}
+
+#[tokio::test]
+async fn test_method() {
+    let server = MockServer::start().await;
+    for name in ["GET", "HEAD", "POST", "PUT", "DELETE", "PATCH", "PROPFIND"] {
+        Mock::given(method(name))
+            .and(path("/method"))
+            .respond_with(ResponseTemplate::new(StatusCode::Ok).insert_header("x-method", name))
+            .mount(&server)
+            .await;
+    }
+    let url = format!("{}{}", server.uri(), "/method");
+
+    let methods = [
+        Method::Get,
+        Method::Head,
+        Method::Post,
+        Method::Put,
+        Method::Delete,
+        Method::Patch,
+        Method::Custom("PROPFIND".to_string()),
+    ];
+    for expected in methods {
+        // Start from a different method to check that it gets replaced.
+        let response = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+            .url(url.as_str())
+            .unwrap()
+            .post_fields_copy(b"body")
+            .unwrap()
+            .method(Method::Custom("OPTIONS".to_string()))
+            .unwrap()
+            .method(expected.clone())
+            .unwrap()
+            .finalize()
+            .perform_response()
+            .await
+            .unwrap();
+        assert_eq!(response.headers.get("x-method").unwrap(), expected.as_str());
+    }
+}
+
+#[tokio::test]
+async fn test_request_template() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("x-api-key", "secret"))
+        .and(header("accept", "application/json"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+
+    let template = RequestTemplate::new()
+        .then(|client| client.header("x-api-key", "secret"))
+        .then(|client| client.header("accept", "application/json"));
+
+    let actor = CurlActor::new();
+    for node in ["/first", "/second"] {
+        let url = format!("{}{}", server.uri(), node);
+        let easy2 = template
+            .build(actor.clone(), ResponseHandler::new())
+            .unwrap()
+            .url(url.as_str())
+            .unwrap()
+            .finalize()
+            .perform()
+            .await
+            .unwrap();
+        assert_eq!(easy2.response_code().unwrap(), 200);
+    }
+}
+
+#[tokio::test]
+async fn test_request_template_perform_n() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("x-api-key", "secret"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("warm")
+                .set_delay(Duration::from_millis(100)),
+        )
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let template = RequestTemplate::new().then(|client| client.header("x-api-key", "secret"));
+    let curl = CurlActorBuilder::new().capacity(3).build();
+    let attempts = template.perform_n(curl, &server.uri(), 3).await;
+    assert_eq!(attempts.len(), 3);
+    for attempt in attempts {
+        let response = attempt.response.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"warm");
+        assert!(attempt.elapsed >= Duration::from_millis(100));
+    }
+}
+
+#[tokio::test]
+async fn test_request_template_mock_actor() {
+    let template = RequestTemplate::new().then(|client| client.header("x-api-key", "secret"));
+    let actor = MockActor::default();
+    let text = template
+        .build(actor.clone(), CollectingHandler::new())
+        .unwrap()
+        .url("https://example.com/items")
+        .unwrap()
+        .finalize()
+        .perform_text()
+        .await
+        .unwrap();
+    assert_eq!(text, "canned");
+
+    let attempts = template
+        .perform_n(actor.clone(), "https://example.com/warm", 2)
+        .await;
+    for attempt in attempts {
+        assert_eq!(attempt.response.unwrap().body, b"canned");
+    }
+    assert_eq!(actor.urls.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_easy_mut() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("user-agent", "custom-agent"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+
+    let mut client = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(server.uri().as_str())
+        .unwrap();
+    client.easy_mut().useragent("custom-agent").unwrap();
+    let easy2 = client.finalize().perform().await.unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_from_easy() {
+    let server = start_mock_server("/easy", "from easy".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/easy");
+
+    let mut easy2 = Easy2::new(CollectingHandler::new());
+    easy2.url(url.as_str()).unwrap();
+    let response = AsyncCurl::from_easy(CurlActor::new(), easy2)
+        .perform_response()
+        .await
+        .unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"from easy");
+}
+
+#[test]
+fn test_negotiate_auth() {
+    // libcurl refuses SPNEGO when it was built without a GSS-API library.
+    let spnego = curl::Version::get().feature_spnego();
+    match AsyncCurl::new(CurlActor::new(), ResponseHandler::new()).negotiate_auth() {
+        Ok(_) => assert!(spnego),
+        Err(err) => {
+            assert!(!spnego);
+            assert_eq!(err.curl_code(), Some(crate::curl::CURLE_NOT_BUILT_IN));
+        }
+    }
+
+    AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .service_name("HTTP")
+        .unwrap()
+        .proxy_service_name("HTTP")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_xoauth2_bearer() {
+    let server = start_mock_server("/oauth", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}/oauth", server.uri());
+
+    // HTTP uses neither without `http_auth` asking for a bearer, so the request is unchanged.
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .xoauth2_bearer("token")
+        .unwrap()
+        .login_options("AUTH=*")
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_quote_lists_outlive_transfer() {
+    // libcurl rejects `CURLOPT_QUOTE` when it was built without FTP.
+    if !version_info().supports_protocol("ftp") {
+        return;
+    }
+    let server = start_mock_server("/quote", "quoted".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/quote");
+
+    let mut quote = curl::easy::List::new();
+    quote.append("SITE CHMOD 644 file").unwrap();
+    let mut postquote = curl::easy::List::new();
+    postquote.append("*DELE file").unwrap();
+    let easy2 = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .quote(quote)
+        .unwrap()
+        .postquote(postquote)
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.get_ref().body(), b"quoted");
+
+    // The lists were unset, so the returned Easy2 can be performed again.
+    let response = AsyncCurl::from_easy(CurlActor::new(), easy2)
+        .perform_response()
+        .await
+        .unwrap();
+    assert_eq!(response.status, 200);
+}
+
+#[tokio::test]
+async fn test_mail_rcpt_list_outlives_transfer() {
+    // libcurl rejects the SMTP options when it was built without SMTP.
+    let smtp = version_info().supports_protocol("smtp");
+    let mut rcpt = curl::easy::List::new();
+    rcpt.append("<bob@example.com>").unwrap();
+    let client = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+        .mail_from("<alice@example.com>")
+        .and_then(|client| client.mail_rcpt(rcpt));
+    let client = match client {
+        Ok(client) => client,
+        Err(err) => {
+            assert!(!smtp);
+            assert_eq!(err.curl_code(), Some(curl_sys::CURLE_UNKNOWN_OPTION));
+            return;
+        }
+    };
+    assert!(smtp);
+    let server = start_mock_server("/mail", "sent".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/mail");
+
+    let easy2 = client
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.get_ref().body(), b"sent");
+
+    // The list was unset, so the returned Easy2 can be performed again.
+    let response = AsyncCurl::from_easy(CurlActor::new(), easy2)
+        .perform_response()
+        .await
+        .unwrap();
+    assert_eq!(response.status, 200);
+}
+
+#[tokio::test]
+async fn test_default_buffer_size() {
+    #[derive(Debug, Default)]
+    struct ChunkHandler {
+        body: Vec<u8>,
+        largest: usize,
+    }
+
+    impl Handler for ChunkHandler {
+        fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+            self.body.extend_from_slice(data);
+            self.largest = self.largest.max(data.len());
+            Ok(data.len())
+        }
+    }
+
+    let body = "x".repeat(100_000);
+    let server = start_mock_server("/buffer", body.clone(), StatusCode::Ok).await;
+    let url = format!("{}/buffer", server.uri());
+
+    let curl = CurlActorBuilder::new()
+        .default_buffer_size(1024)
+        .default_upload_buffer_size(16 * 1024)
+        .build();
+    let easy2 = AsyncCurl::new(curl, ChunkHandler::default())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.get_ref().body, body.as_bytes());
+    assert!(easy2.get_ref().largest <= 1024);
+}
+
+#[tokio::test]
+async fn test_handle_pool() {
+    let server = start_mock_server("/pool", "pooled".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/pool");
+
+    let actor = CurlActor::with_handle_pool(1);
+    let text = AsyncCurl::new(actor.clone(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .finalize()
+        .perform_text()
+        .await
+        .unwrap();
+    assert_eq!(text, "pooled");
+    assert_eq!(actor.pooled(), 1);
+
+    // The next builder reuses the pooled handle, reset to a new handler.
+    let mut client = AsyncCurl::new(actor.clone(), CollectingHandler::new())
+        .url(url.as_str())
+        .unwrap();
+    assert_eq!(actor.pooled(), 0);
+    assert!(client.easy_mut().get_ref().body().is_empty());
+    let easy2 = client.finalize().perform().await.unwrap();
+    assert_eq!(easy2.get_ref().body(), b"pooled");
+    // The connection of the first request is kept open for the next one.
+    assert!(easy2.connection_info().unwrap().is_reused());
+    actor.recycle(easy2);
+    assert_eq!(actor.pooled(), 1);
+
+    // Without a pool, handles are simply dropped.
+    let actor: CurlActor<CollectingHandler> = CurlActor::new();
+    actor.recycle(Easy2::new(CollectingHandler::new()));
+    assert_eq!(actor.pooled(), 0);
+}
+
+#[tokio::test]
+async fn test_share() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/login"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok).insert_header("set-cookie", "session=42"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/account"))
+        .and(header("cookie", "session=42"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+
+    let share = CurlShare::new(&[ShareData::Cookies, ShareData::Connections]).unwrap();
+    assert_eq!(share.data(), &[ShareData::Cookies, ShareData::Connections]);
+    let actor = CurlActorBuilder::new().share(share).build();
+    let mut responses = Vec::new();
+    for endpoint in ["/login", "/account"] {
+        let mut easy2 = Easy2::new(ResponseHandler::new());
+        easy2.url(&format!("{}{}", server.uri(), endpoint)).unwrap();
+        easy2.cookie_file("").unwrap();
+        responses.push(actor.send_request(easy2).await.unwrap());
+    }
+    assert_eq!(responses[1].response_code().unwrap(), 200);
+    assert!(!responses[0].connection_info().unwrap().is_reused());
+    assert!(responses[1].connection_info().unwrap().is_reused());
+}
+
+#[tokio::test]
+async fn test_upkeep_interval() {
+    let server = start_mock_server("/upkeep", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}{}", server.uri(), "/upkeep");
+
+    let share = CurlShare::new(&[ShareData::Connections]).unwrap();
+    let actor = CurlActorBuilder::new()
+        .share(share)
+        .upkeep_interval(Duration::from_millis(20))
+        .workers(2)
+        .build();
+    let mut responses = Vec::new();
+    for _ in 0..2 {
+        let mut easy2 = Easy2::new(ResponseHandler::new());
+        easy2.url(url.as_str()).unwrap();
+        responses.push(actor.send_request(easy2).await.unwrap());
+        // Leaves the connection idle while the share is kept up.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(responses[1].response_code().unwrap(), 200);
+    assert!(responses[1].connection_info().unwrap().is_reused());
+}
+
+#[tokio::test]
+async fn test_download_ranges() {
+    const BODY: &str = "the quick brown fox jumps over the lazy dog";
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/ranges"))
+        .respond_with(|request: &wiremock::Request| {
+            let range = request.headers.get(&"range".into()).unwrap().as_str();
+            let (start, end) = range.trim_start_matches("bytes=").split_once('-').unwrap();
+            let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+            ResponseTemplate::new(StatusCode::PartialContent)
+                .insert_header(
+                    "Content-Range",
+                    format!("bytes {start}-{end}/{}", BODY.len()).as_str(),
+                )
+                .set_body_string(&BODY[start..=end])
+        })
+        .mount(&server)
+        .await;
+    Mock::given(path("/ranges"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string(BODY))
+        .mount(&server)
+        .await;
+    Mock::given(path("/whole"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string(BODY))
+        .mount(&server)
+        .await;
+
+    let template = RequestTemplate::new();
+    for endpoint in ["/ranges", "/whole"] {
+        let file = std::env::temp_dir().join(format!(
+            "async-curl-ranges{}-{}",
+            endpoint.replace('/', "-"),
+            std::process::id()
+        ));
+        let url = format!("{}{}", server.uri(), endpoint);
+        let written = template
+            .download_ranges(CurlActorBuilder::new().build(), url.as_str(), &file, 4)
+            .await
+            .unwrap();
+        assert_eq!(written, BODY.len() as u64);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), BODY);
+        std::fs::remove_file(&file).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_download_ranges_error() {
+    const BODY: &str = "the quick brown fox jumps over the lazy dog";
+    let server = MockServer::start().await;
+    Mock::given(path("/missing"))
+        .respond_with(ResponseTemplate::new(StatusCode::NotFound).set_body_string(BODY))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/broken"))
+        .respond_with(ResponseTemplate::new(StatusCode::InternalServerError))
+        .mount(&server)
+        .await;
+    Mock::given(path("/broken"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string(BODY))
+        .mount(&server)
+        .await;
+
+    let template = RequestTemplate::new();
+    for endpoint in ["/missing", "/broken"] {
+        let file = std::env::temp_dir().join(format!(
+            "async-curl-ranges{}-{}",
+            endpoint.replace('/', "-"),
+            std::process::id()
+        ));
+        let url = format!("{}{}", server.uri(), endpoint);
+        let err = template
+            .download_ranges(CurlActorBuilder::new().build(), url.as_str(), &file, 4)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.curl_code(),
+            Some(curl_sys::CURLE_HTTP_RETURNED_ERROR),
+            "{endpoint}"
+        );
+        assert!(!file.exists());
+    }
+}
+
+#[tokio::test]
+async fn test_resume_download() {
+    const BODY: &str = "hello world";
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/file"))
+        .respond_with(
+            |request: &wiremock::Request| match request.headers.get(&"range".into()) {
+                Some(range) => {
+                    let start: usize = range
+                        .as_str()
+                        .trim_start_matches("bytes=")
+                        .trim_end_matches('-')
+                        .parse()
+                        .unwrap();
+                    let content_range = format!("bytes {start}-10/{}", BODY.len());
+                    ResponseTemplate::new(StatusCode::PartialContent)
+                        .insert_header("Content-Range", content_range.as_str())
+                        .set_body_string(&BODY[start..])
+                }
+                None => ResponseTemplate::new(StatusCode::Ok).set_body_string(BODY),
+            },
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/short"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::PartialContent)
+                .insert_header("Content-Range", "bytes 6-19/20")
+                .set_body_string("world"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/complete"))
+        .respond_with(
+            ResponseTemplate::new(416)
+                .insert_header("Content-Range", "bytes */11")
+                .set_body_string("range not satisfiable"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/broken"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::InternalServerError).set_body_string("oops"),
+        )
+        .mount(&server)
+        .await;
+
+    let file = std::env::temp_dir().join(format!("async-curl-resume-{}", std::process::id()));
+    let url = format!("{}/file", server.uri());
+    let download = || {
+        AsyncCurl::new(CurlActor::new(), FileHandler::new())
+            .url(url.as_str())
+            .unwrap()
+    };
+
+    let _ = std::fs::remove_file(&file);
+    let size = download().resume_download(&file).await.unwrap();
+    assert_eq!(size, 11);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), BODY);
+
+    std::fs::write(&file, "hello ").unwrap();
+    let size = download().resume_download(&file).await.unwrap();
+    assert_eq!(size, 11);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), BODY);
+
+    std::fs::write(&file, "hello ").unwrap();
+    let url = format!("{}/short", server.uri());
+    let err = AsyncCurl::new(CurlActor::new(), FileHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .resume_download(&file)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Io(ref err) if err.kind() == std::io::ErrorKind::UnexpectedEof));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello world");
+
+    // A 416 for the size of the file means it is already complete.
+    let url = format!("{}/complete", server.uri());
+    let size = AsyncCurl::new(CurlActor::new(), FileHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .resume_download(&file)
+        .await
+        .unwrap();
+    assert_eq!(size, 11);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello world");
+
+    // An error page is not appended to the partial file.
+    std::fs::write(&file, "hello ").unwrap();
+    let url = format!("{}/broken", server.uri());
+    let err = AsyncCurl::new(CurlActor::new(), FileHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .resume_download(&file)
+        .await
+        .unwrap_err();
+    // libcurl fails a resumed transfer whose response is not a range itself.
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_RANGE_ERROR));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello ");
+
+    // Nor is a 416 for a file of another size taken as complete.
+    let url = format!("{}/complete", server.uri());
+    let err = AsyncCurl::new(CurlActor::new(), FileHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .resume_download(&file)
+        .await
+        .unwrap_err();
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_HTTP_RETURNED_ERROR));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello ");
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[tokio::test]
+async fn test_multipart() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/upload"))
+        .and(body_string_contains("name=\"title\"\r\n\r\nHoliday"))
+        .and(body_string_contains(
+            "name=\"photo\"; filename=\"beach.png\"\r\nContent-Type: image/png",
+        ))
+        .and(body_string_contains(
+            "Content-Type: text/plain\r\n\r\nhello world",
+        ))
+        .respond_with(ResponseTemplate::new(StatusCode::Created))
+        .mount(&server)
+        .await;
+    let url = format!("{}/upload", server.uri());
+
+    let file =
+        std::env::temp_dir().join(format!("async-curl-multipart-{}.txt", std::process::id()));
+    std::fs::write(&file, "hello world").unwrap();
+    let multipart = Multipart::new()
+        .text("title", "Holiday")
+        .unwrap()
+        .file_bytes("photo", "beach.png", "image/png", b"png".to_vec())
+        .unwrap()
+        .file("notes", &file)
+        .unwrap();
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .multipart(multipart)
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 201);
+}
+
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn test_tracing_span() {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    #[derive(Default)]
+    struct Spans(Mutex<Vec<(&'static str, HashMap<&'static str, String>)>>);
+
+    struct Fields<'a>(&'a mut HashMap<&'static str, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name(), format!("{value:?}"));
+        }
+    }
+
+    struct Recorder(Arc<Spans>);
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.0 .0.lock().unwrap();
+            let mut fields = HashMap::new();
+            span.record(&mut Fields(&mut fields));
+            spans.push((span.metadata().name(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.0 .0.lock().unwrap();
+            let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+            values.record(&mut Fields(fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    let server = start_mock_server("/traced", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}/traced", server.uri());
+
+    let spans = Arc::new(Spans::default());
+    let _guard = tracing::subscriber::set_default(Recorder(spans.clone()));
+    let mut easy2 = Easy2::new(ResponseHandler::new());
+    easy2.url(&url).unwrap();
+    CurlActor::new().send_request(easy2).await.unwrap();
+
+    let spans = spans.0.lock().unwrap();
+    let (name, fields) = &spans[0];
+    assert_eq!(*name, "send_request");
+    assert_eq!(fields["url"], url);
+    assert_eq!(fields["method"], "GET");
+    assert_eq!(fields["status"], "200");
+    assert_eq!(fields["bytes"], "4");
+    assert!(fields.contains_key("duration"));
+}
+
+#[tokio::test]
+async fn test_on_header() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/headers"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .insert_header("x-trace", "abc")
+                .set_body_string("body"),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}/headers", server.uri());
+
+    let lines = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen = lines.clone();
+    let mut easy2 = AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(ResponseHandler::new()),
+    )
+    .url(url.as_str())
+    .unwrap()
+    .on_header(move |line| {
+        seen.lock().unwrap().push(line.to_vec());
+        true
+    })
+    .finalize()
+    .perform()
+    .await
+    .unwrap();
+
+    let lines = std::mem::take(&mut *lines.lock().unwrap());
+    assert!(lines[0].starts_with(b"HTTP/1.1 200"));
+    assert!(lines.iter().any(|line| line.starts_with(b"x-trace: abc")));
+    assert_eq!(lines.last().unwrap(), b"\r\n");
+    assert_eq!(easy2.get_mut().get_mut().take().unwrap(), b"body");
+
+    // Returning false stops the transfer at the first header line.
+    let err = AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(ResponseHandler::new()),
+    )
+    .url(url.as_str())
+    .unwrap()
+    .on_header(|_| false)
+    .finalize()
+    .perform()
+    .await
+    .unwrap_err();
+    assert!(matches!(err, Error::HandlerAborted(_)));
+}
+
+#[tokio::test]
+async fn test_capture_debug() {
+    let server = start_mock_server("/debug", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}/debug", server.uri());
+
+    let mut easy2 = AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(ResponseHandler::new()),
+    )
+    .url(url.as_str())
+    .unwrap()
+    .capture_debug()
+    .unwrap()
+    .finalize()
+    .perform()
+    .await
+    .unwrap();
+
+    let debug_log = easy2.get_ref().debug_log();
+    let request = debug_log
+        .iter()
+        .find(|(kind, _)| matches!(kind, InfoType::HeaderOut))
+        .unwrap();
+    assert!(String::from_utf8_lossy(&request.1).starts_with("GET /debug HTTP/1.1"));
+    assert!(debug_log.iter().any(
+        |(kind, data)| matches!(kind, InfoType::HeaderIn) && data.starts_with(b"HTTP/1.1 200")
+    ));
+    assert!(!easy2.get_mut().take_debug_log().is_empty());
+    assert!(easy2.get_ref().debug_log().is_empty());
+    assert_eq!(easy2.get_mut().get_mut().take().unwrap(), b"body");
+}
+
+#[cfg(all(feature = "ssl-keylog", unix, not(target_os = "macos")))]
+#[tokio::test]
+async fn test_ssl_keylog() {
+    let server = start_mock_server("/keylog", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}/keylog", server.uri());
+    let path = std::env::temp_dir().join(format!("async-curl-keylog-{}.txt", std::process::id()));
+
+    let mut easy2 = AsyncCurl::new(
+        CurlActor::new(),
+        CallbackHandler::new(ResponseHandler::new()),
+    )
+    .url(url.as_str())
+    .unwrap()
+    .ssl_keylog(&path)
+    .unwrap()
+    .finalize()
+    .perform()
+    .await
+    .unwrap();
+
+    // A plain HTTP transfer has no TLS secrets to log.
+    assert!(path.exists());
+    assert_eq!(std::fs::read(&path).unwrap(), b"");
+    assert_eq!(easy2.get_mut().get_mut().take().unwrap(), b"body");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_pause_resume() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .set_body_string("slow body")
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}/slow", server.uri());
+
+    let mut easy2 = Easy2::new(ResponseHandler::new());
+    easy2.url(url.as_str()).unwrap();
+    let (response, handle) = CurlActor::new().send_request_with_handle(easy2);
+    let response = tokio::spawn(response);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    handle.pause();
+
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert!(!response.is_finished());
+    assert!(!handle.is_finished());
+
+    handle.resume();
+    let mut easy2 = response.await.unwrap().unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+    assert_eq!(easy2.get_mut().take(), Some(b"slow body".to_vec()));
+}
+
+#[tokio::test]
+async fn test_protocols() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/redirect"))
+        .respond_with(ResponseTemplate::new(StatusCode::Found).insert_header("Location", "/target"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/target"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+    let url = format!("{}/redirect", server.uri());
+
+    let err = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .protocols("https")
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap_err();
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_UNSUPPORTED_PROTOCOL));
+
+    let err = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .follow_location(true)
+        .unwrap()
+        .redir_protocols("https")
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap_err();
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_UNSUPPORTED_PROTOCOL));
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .follow_location(true)
+        .unwrap()
+        .redir_protocols("http,https")
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_setopt_raw() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/redirect"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Found).insert_header("Location", "/redirect"),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}/redirect", server.uri());
+
+    let client = AsyncCurl::new(CurlActor::new(), ResponseHandler::new());
+    let Err(err) = (unsafe { client.setopt_raw(curl_sys::CURLOPT_URL, RawOption::Long(1)) }) else {
+        panic!("a long was accepted for a string option");
+    };
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT));
+
+    let client = AsyncCurl::new(CurlActor::new(), ResponseHandler::new());
+    let client = unsafe { client.setopt_raw(curl_sys::CURLOPT_URL, RawOption::Str(&url)) }
+        .unwrap()
+        .url(url.as_str())
+        .unwrap()
+        .follow_location(true)
+        .unwrap();
+    let err = unsafe { client.setopt_raw(curl_sys::CURLOPT_MAXREDIRS, RawOption::Long(2)) }
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap_err();
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_TOO_MANY_REDIRECTS));
+
+    // libcurl does not copy the body given to CURLOPT_POSTFIELDS.
+    let client = AsyncCurl::new(CurlActor::new(), ResponseHandler::new());
+    let Err(err) =
+        (unsafe { client.setopt_raw(curl_sys::CURLOPT_POSTFIELDS, RawOption::Str("a=b")) })
+    else {
+        panic!("a string was accepted for a pointer libcurl does not copy");
+    };
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT));
+
+    // Options the builder wraps go through their typed setter.
+    let client = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url("http://localhost/")
+        .unwrap();
+    let client = unsafe { client.setopt_raw(curl_sys::CURLOPT_URL, RawOption::Str(&url)) }.unwrap();
+    let plan = unsafe { client.setopt_raw(curl_sys::CURLOPT_USERAGENT, RawOption::Str("raw")) }
+        .unwrap()
+        .finalize()
+        .dry_run();
+    assert_eq!(plan.url.as_deref(), Some(url.as_str()));
+    assert_eq!(plan.option("useragent"), Some("raw"));
+}
+
+#[tokio::test]
+async fn test_allowed_protocols() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/file"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Found)
+                .insert_header("Location", "file:///etc/passwd"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/redirect"))
+        .respond_with(ResponseTemplate::new(StatusCode::Found).insert_header("Location", "/target"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/target"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok))
+        .mount(&server)
+        .await;
+    let client = |endpoint: &str| {
+        AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+            .url(format!("{}{}", server.uri(), endpoint).as_str())
+            .unwrap()
+            .follow_location(true)
+            .unwrap()
+    };
+
+    let err = client("/file").finalize().perform().await.unwrap_err();
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_UNSUPPORTED_PROTOCOL));
+
+    let err = client("/target")
+        .allowed_protocols(&[Protocol::Https])
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap_err();
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_UNSUPPORTED_PROTOCOL));
+
+    let err = client("/redirect")
+        .allowed_redirect_protocols(&[Protocol::Https])
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap_err();
+    assert_eq!(err.curl_code(), Some(curl_sys::CURLE_UNSUPPORTED_PROTOCOL));
+
+    let easy2 = client("/redirect")
+        .allowed_protocols(&[Protocol::Http, Protocol::Https])
+        .unwrap()
+        .allowed_redirect_protocols(&[Protocol::Http])
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_proxy_from_env() {
+    let proxy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/through-proxy"))
+        .respond_with(ResponseTemplate::new(StatusCode::Ok).set_body_string("proxied"))
+        .mount(&proxy)
+        .await;
+    let proxy_uri = proxy.uri();
+    let env = |name: &str| match name {
+        "http_proxy" => Some(proxy_uri.clone()),
+        "no_proxy" => Some("127.0.0.1".to_string()),
+        _ => None,
+    };
+
+    let mut easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url("http://async-curl.invalid/through-proxy")
+        .unwrap()
+        .proxy_from(env)
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+    assert_eq!(easy2.get_mut().take(), Some(b"proxied".to_vec()));
+
+    // `HTTP_PROXY` is ignored, so the server is reached without the dead proxy.
+    let url = format!("{}/through-proxy", proxy.uri());
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .proxy_from(|name| (name == "HTTP_PROXY").then(|| "http://127.0.0.1:1".to_string()))
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[test]
+fn test_version_info() {
+    let info = version_info();
+    assert_eq!(info.version, curl::Version::get().version());
+    assert!(info.version_num >= 0x075500);
+    assert!(info.supports_protocol("http"));
+    assert!(info.supports_protocol("HTTP"));
+    assert!(!info.supports_protocol("async-curl"));
+}
+
+#[test]
+fn test_try_http3() {
+    let http3 = version_info().http3;
+    for only in [false, true] {
+        let client = AsyncCurl::new(CurlActor::new(), ResponseHandler::new());
+        let client = if only {
+            client.try_http3_only()
+        } else {
+            client.try_http3()
+        };
+        match client {
+            Ok(_) => assert!(http3),
+            Err(err) => {
+                assert!(!http3);
+                assert_eq!(err.curl_code(), Some(4));
+                assert!(err.detail().unwrap().contains("without HTTP/3"));
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_happy_eyeballs_timeout() {
+    let server = start_mock_server("/eyeballs", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}/eyeballs", server.uri());
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .happy_eyeballs_timeout(Duration::from_millis(50))
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[test]
+fn test_dns_options() {
+    let ares = version_info().ares_version.is_some();
+    let client = AsyncCurl::new(CurlActor::new(), ResponseHandler::new());
+    let results = [
+        client.dns_interface("lo").map(|_| ()),
+        AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+            .dns_local_ip4(std::net::Ipv4Addr::LOCALHOST)
+            .map(|_| ()),
+        AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+            .dns_local_ip6(std::net::Ipv6Addr::LOCALHOST)
+            .map(|_| ()),
+    ];
+    for result in results {
+        match result {
+            Ok(()) => assert!(ares),
+            Err(err) => {
+                assert!(!ares);
+                assert!(err.detail().unwrap().contains("without c-ares"));
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_dns_shuffle_addresses() {
+    let server = start_mock_server("/shuffle", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("http://localhost:{}/shuffle", server.address().port());
+
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .dns_shuffle_addresses(true)
+        .unwrap()
+        .ip_resolve(curl::easy::IpResolve::V4)
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_ssl_verify_status() {
+    let server = start_mock_server("/status", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}/status", server.uri());
+
+    // The stapled status is only checked on TLS connections.
+    let easy2 = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap()
+        .ssl_verify_status(true)
+        .unwrap()
+        .finalize()
+        .perform()
+        .await
+        .unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_alt_svc_and_hsts_cache() {
+    let server = start_mock_server("/cached", "body".to_string(), StatusCode::Ok).await;
+    let url = format!("{}/cached", server.uri());
+    let info = version_info();
+    let alt_svc = std::env::temp_dir().join(format!("async-curl-altsvc-{}", std::process::id()));
+    let hsts = std::env::temp_dir().join(format!("async-curl-hsts-{}", std::process::id()));
+
+    let client = AsyncCurl::new(CurlActor::new(), ResponseHandler::new())
+        .url(url.as_str())
+        .unwrap();
+    let client = match client.alt_svc_cache(&alt_svc) {
+        Ok(client) => client,
+        Err(err) => {
+            assert!(!info.altsvc, "{err}");
+            return;
+        }
+    };
+    let client = match client.hsts_cache(&hsts) {
+        Ok(client) => client,
+        Err(err) => {
+            assert!(!info.hsts, "{err}");
+            return;
+        }
+    };
+    let easy2 = client.finalize().perform().await.unwrap();
+    assert_eq!(easy2.response_code().unwrap(), 200);
+    drop(easy2);
+    let _ = std::fs::remove_file(&alt_svc);
+    let _ = std::fs::remove_file(&hsts);
+}
+
+const SSE_BODY: &str = "event: greeting\r\ndata: hello\r\ndata: world\r\nid: 1\r\n\r\n\
+: keep-alive\n\n\
+data:second\nretry: 3000\n\n\
+data: unterminated";
+
+#[tokio::test]
+async fn test_sse_handler() {
+    let (mut handler, mut events) = SseHandler::new(8);
+    // Fields may be split across chunks anywhere.
+    for byte in SSE_BODY.as_bytes() {
+        assert_eq!(handler.write(std::slice::from_ref(byte)).ok(), Some(1));
+    }
+    handler.close().await;
+
+    let first = events.try_recv().unwrap();
+    assert_eq!(first.event, "greeting");
+    assert_eq!(first.data, "hello\nworld");
+    assert_eq!(first.id.as_deref(), Some("1"));
+    let second = events.try_recv().unwrap();
+    assert_eq!(second.event, "message");
+    assert_eq!(second.data, "second");
+    assert_eq!(second.id.as_deref(), Some("1"));
+    assert_eq!(second.retry, Some(Duration::from_secs(3)));
+    assert!(events.try_recv().is_err());
+
+    // A full channel pauses the transfer until the events were received.
+    let (mut handler, mut events) = SseHandler::new(1);
+    let chunk = b"data: a\n\ndata: b\n\n";
+    assert_eq!(handler.write(chunk).ok(), Some(chunk.len()));
+    assert!(matches!(handler.write(chunk), Err(WriteError::Pause)));
+    assert_eq!(events.try_recv().unwrap().data, "a");
+    assert_eq!(handler.write(chunk).ok(), Some(chunk.len()));
+    assert_eq!(events.try_recv().unwrap().data, "b");
+    assert!(matches!(handler.write(chunk), Err(WriteError::Pause)));
+
+    // Dropping the receiver aborts the transfer.
+    drop(events);
+    assert_eq!(handler.write(chunk).ok(), Some(0));
+}
+
+#[tokio::test]
+async fn test_event_stream() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/events"))
+        .and(header("accept", "text/event-stream"))
+        .respond_with(
+            ResponseTemplate::new(StatusCode::Ok)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_string(SSE_BODY),
+        )
+        .mount(&server)
+        .await;
+    let url = format!("{}/events", server.uri());
+
+    let mut stream = EventStream::get(CurlActor::new(), &url, 1).unwrap();
+    let mut data = Vec::new();
+    while let Some(event) = stream.next().await {
+        data.push(event.data);
+    }
+    assert_eq!(data, ["hello\nworld", "second"]);
+    stream.finish().await.unwrap();
+}
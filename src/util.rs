@@ -0,0 +1,24 @@
+use curl::easy::Easy;
+
+/// Percent-encodes `s` with libcurl, for use in a query string or a
+/// `application/x-www-form-urlencoded` body. Every byte other than
+/// `a-z`, `A-Z`, `0-9`, `-`, `.`, `_` and `~` is encoded.
+/// ```
+/// use async_curl::util::url_encode;
+///
+/// assert_eq!(url_encode("a b&c"), "a%20b%26c");
+/// ```
+pub fn url_encode(s: &str) -> String {
+    Easy::new().url_encode(s.as_bytes())
+}
+
+/// Decodes the percent-encoded `s` with libcurl. The result is not
+/// necessarily UTF-8, since any byte may have been encoded.
+/// ```
+/// use async_curl::util::url_decode;
+///
+/// assert_eq!(url_decode("a%20b%26c"), b"a b&c");
+/// ```
+pub fn url_decode(s: &str) -> Vec<u8> {
+    Easy::new().url_decode(s)
+}
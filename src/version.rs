@@ -0,0 +1,77 @@
+use curl::Version;
+
+/// What the libcurl linked at runtime was built with, to check for
+/// required features at startup rather than failing on the first request.
+/// ```
+/// use async_curl::version::version_info;
+///
+/// let info = version_info();
+/// if !info.http2 {
+///     eprintln!("libcurl {} was built without HTTP/2", info.version);
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionInfo {
+    /// The version of libcurl, e.g. `8.5.0`.
+    pub version: String,
+    /// The version of libcurl as `0xXXYYZZ`, for comparisons.
+    pub version_num: u32,
+    /// The TLS backend and its version, e.g. `OpenSSL/3.0.13`.
+    pub ssl_version: Option<String>,
+    /// The version of c-ares, when DNS is resolved through it.
+    pub ares_version: Option<String>,
+    /// The schemes of the protocols libcurl supports, e.g. `https`.
+    pub protocols: Vec<String>,
+    /// Whether IPv6 is supported.
+    pub ipv6: bool,
+    /// Whether host names are resolved asynchronously.
+    pub async_dns: bool,
+    /// Whether HTTP/2 is supported.
+    pub http2: bool,
+    /// Whether HTTP/3 is supported.
+    pub http3: bool,
+    /// Whether HTTPS proxies are supported.
+    pub https_proxy: bool,
+    /// Whether gzip and deflate decoding is supported.
+    pub libz: bool,
+    /// Whether brotli decoding is supported.
+    pub brotli: bool,
+    /// Whether zstd decoding is supported.
+    pub zstd: bool,
+    /// Whether `Alt-Svc` headers are supported.
+    pub altsvc: bool,
+    /// Whether HSTS is supported.
+    pub hsts: bool,
+}
+
+impl VersionInfo {
+    /// Returns true if libcurl supports the protocol of the given scheme,
+    /// compared case-insensitively.
+    pub fn supports_protocol(&self, scheme: &str) -> bool {
+        self.protocols
+            .iter()
+            .any(|protocol| protocol.eq_ignore_ascii_case(scheme))
+    }
+}
+
+/// Returns what the libcurl linked at runtime was built with.
+pub fn version_info() -> VersionInfo {
+    let version = Version::get();
+    VersionInfo {
+        version: version.version().to_string(),
+        version_num: version.version_num(),
+        ssl_version: version.ssl_version().map(str::to_string),
+        ares_version: version.ares_version().map(str::to_string),
+        protocols: version.protocols().map(str::to_string).collect(),
+        ipv6: version.feature_ipv6(),
+        async_dns: version.feature_async_dns(),
+        http2: version.feature_http2(),
+        http3: version.feature_http3(),
+        https_proxy: version.feature_https_proxy(),
+        libz: version.feature_libz(),
+        brotli: version.feature_brotli(),
+        zstd: version.feature_zstd(),
+        altsvc: version.feature_altsvc(),
+        hsts: version.feature_hsts(),
+    }
+}
@@ -0,0 +1,353 @@
+use std::ffi::{c_int, c_uint, c_void};
+use std::fmt::Debug;
+use std::sync::OnceLock;
+
+use curl::easy::{Easy2, Handler};
+use curl::multi::Easy2Handle;
+use tokio::sync::oneshot;
+
+use crate::connection::{Connection, ConnectionIo};
+use crate::curl::CURLE_NOT_BUILT_IN;
+use crate::error::Error;
+use crate::version::version_info;
+
+const CURLWS_TEXT: c_int = 1 << 0;
+const CURLWS_BINARY: c_int = 1 << 1;
+const CURLWS_CONT: c_int = 1 << 2;
+const CURLWS_CLOSE: c_int = 1 << 3;
+const CURLWS_PING: c_int = 1 << 4;
+const CURLWS_PONG: c_int = 1 << 6;
+
+/// The largest frame `WebSocket::recv_frame` takes by default, 16 MiB.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+/// The most payload set aside before the bytes of a frame arrived,
+/// whatever length its header claims.
+const MAX_PREALLOCATION: usize = 64 * 1024;
+
+/// `struct curl_ws_frame`, which curl-sys does not define.
+#[repr(C)]
+struct CurlWsFrame {
+    age: c_int,
+    flags: c_int,
+    offset: curl_sys::curl_off_t,
+    bytesleft: curl_sys::curl_off_t,
+    len: usize,
+}
+
+type WsRecv = unsafe extern "C" fn(
+    curl: *mut curl_sys::CURL,
+    buffer: *mut c_void,
+    buflen: usize,
+    recv: *mut usize,
+    metap: *mut *const CurlWsFrame,
+) -> curl_sys::CURLcode;
+
+type WsSend = unsafe extern "C" fn(
+    curl: *mut curl_sys::CURL,
+    buffer: *const c_void,
+    buflen: usize,
+    sent: *mut usize,
+    fragsize: curl_sys::curl_off_t,
+    flags: c_uint,
+) -> curl_sys::CURLcode;
+
+/// The WebSocket API of libcurl 7.86 and later, which curl-sys does not bind.
+///
+/// The functions are looked up at runtime rather than linked, since the
+/// libcurl the crate links to, such as the one curl-sys bundles, may not
+/// export them.
+struct WsApi {
+    recv: WsRecv,
+    send: WsSend,
+}
+
+/// Returns the WebSocket API of the linked libcurl, if it exports one.
+fn ws_api() -> Option<&'static WsApi> {
+    static API: OnceLock<Option<WsApi>> = OnceLock::new();
+    API.get_or_init(|| {
+        let recv = lookup(b"curl_ws_recv\0")?;
+        let send = lookup(b"curl_ws_send\0")?;
+        // SAFETY: the symbols are the functions of the libcurl API of the same name.
+        unsafe {
+            Some(WsApi {
+                recv: std::mem::transmute::<*mut c_void, WsRecv>(recv),
+                send: std::mem::transmute::<*mut c_void, WsSend>(send),
+            })
+        }
+    })
+    .as_ref()
+}
+
+#[cfg(unix)]
+fn lookup(name: &[u8]) -> Option<*mut c_void> {
+    let symbol = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr().cast()) };
+    (!symbol.is_null()).then_some(symbol)
+}
+
+#[cfg(not(unix))]
+fn lookup(_name: &[u8]) -> Option<*mut c_void> {
+    None
+}
+
+/// Returns true if the libcurl linked at runtime supports WebSocket, which
+/// needs both the `ws` protocol and the `curl_ws_recv` and `curl_ws_send`
+/// functions to be found among the loaded symbols.
+///
+/// A libcurl linked statically, such as the one curl-sys bundles, does not
+/// export them, nor does any libcurl on platforms other than Unix.
+pub fn supported() -> bool {
+    version_info().supports_protocol("ws") && ws_api().is_some()
+}
+
+/// The type of a WebSocket [`Frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl FrameKind {
+    fn from_flags(flags: c_int) -> Self {
+        if flags & CURLWS_CLOSE != 0 {
+            Self::Close
+        } else if flags & CURLWS_PING != 0 {
+            Self::Ping
+        } else if flags & CURLWS_PONG != 0 {
+            Self::Pong
+        } else if flags & CURLWS_BINARY != 0 {
+            Self::Binary
+        } else {
+            Self::Text
+        }
+    }
+
+    fn flags(self) -> c_int {
+        match self {
+            Self::Text => CURLWS_TEXT,
+            Self::Binary => CURLWS_BINARY,
+            Self::Close => CURLWS_CLOSE,
+            Self::Ping => CURLWS_PING,
+            Self::Pong => CURLWS_PONG,
+        }
+    }
+}
+
+/// One WebSocket frame, sent or received whole.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub payload: Vec<u8>,
+    /// Whether the frame ends its message, false for all but the last
+    /// fragment of a fragmented message.
+    pub last: bool,
+}
+
+impl Frame {
+    /// A text frame carrying the whole of `text`.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            kind: FrameKind::Text,
+            payload: text.into().into_bytes(),
+            last: true,
+        }
+    }
+
+    /// A binary frame carrying the whole of `payload`.
+    pub fn binary(payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            kind: FrameKind::Binary,
+            payload: payload.into(),
+            last: true,
+        }
+    }
+
+    /// A close frame without a status code.
+    pub fn close() -> Self {
+        Self {
+            kind: FrameKind::Close,
+            payload: Vec::new(),
+            last: true,
+        }
+    }
+
+    fn flags(&self) -> c_uint {
+        let cont = if self.last { 0 } else { CURLWS_CONT };
+        (self.kind.flags() | cont) as c_uint
+    }
+}
+
+/// WebSocket is the connection left open by `AsyncCurl::websocket`, after
+/// libcurl upgraded it to a WebSocket, sending and receiving frames through
+/// the actor's background worker like a [`Connection`].
+///
+/// This needs a libcurl built with WebSocket support and linked
+/// dynamically, see [`supported`].
+/// ```no_run
+/// use async_curl::actor::CurlActor;
+/// use async_curl::curl::AsyncCurl;
+/// use async_curl::handler::CollectingHandler;
+/// use async_curl::websocket::Frame;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut websocket = AsyncCurl::new(CurlActor::new(), CollectingHandler::new())
+///     .url("wss://example.com/socket")?
+///     .finalize()
+///     .websocket()
+///     .await?;
+///
+/// websocket.send_frame(Frame::text("hello")).await?;
+/// let frame = websocket.recv_frame().await?;
+/// websocket.close().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct WebSocket<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    connection: Connection<H>,
+    max_frame_size: usize,
+}
+
+impl<H> WebSocket<H>
+where
+    H: Handler + Debug + Send + 'static,
+{
+    pub(crate) fn new(connection: Connection<H>) -> Self {
+        Self {
+            connection,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    /// Sets the largest frame `recv_frame` takes, [`DEFAULT_MAX_FRAME_SIZE`]
+    /// unless set. A frame whose header announces more fails with
+    /// `CURLE_RECV_ERROR` before its payload is read, after which the
+    /// WebSocket should be closed.
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = max;
+        self
+    }
+
+    /// Sends `frame` whole.
+    ///
+    /// This corresponds to `curl_ws_send`.
+    pub async fn send_frame(&mut self, frame: Frame) -> Result<(), Error<H>> {
+        let (reply, result) = oneshot::channel();
+        self.connection
+            .request(ConnectionIo::SendFrame(frame, reply))?;
+        result.await?.map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })
+    }
+
+    /// Receives the next frame whole, which is a fragment when the
+    /// host sent a fragmented message.
+    ///
+    /// This corresponds to `curl_ws_recv`.
+    pub async fn recv_frame(&mut self) -> Result<Frame, Error<H>> {
+        let (reply, result) = oneshot::channel();
+        self.connection
+            .request(ConnectionIo::RecvFrame(self.max_frame_size, reply))?;
+        result.await?.map_err(|err| {
+            log::trace!("{err}");
+            Error::Curl(err)
+        })
+    }
+
+    /// Sends a close frame, closes the connection and returns the Easy2
+    /// once the worker let go of it.
+    pub async fn close(mut self) -> Result<Easy2<H>, Error<H>> {
+        self.send_frame(Frame::close()).await?;
+        self.connection.close().await
+    }
+}
+
+/// Sends what `curl_ws_send` accepts of `data`, the rest of the
+/// payload of `frame`.
+pub(crate) fn send<H>(
+    handle: &Easy2Handle<H>,
+    data: &[u8],
+    frame: &Frame,
+) -> Result<usize, curl::Error> {
+    let api = ws_api().ok_or_else(|| curl::Error::new(CURLE_NOT_BUILT_IN))?;
+    let mut len = 0;
+    let rc = unsafe {
+        (api.send)(
+            handle.raw(),
+            data.as_ptr() as *const _,
+            data.len(),
+            &mut len,
+            0,
+            frame.flags(),
+        )
+    };
+    if rc != curl_sys::CURLE_OK {
+        return Err(curl::Error::new(rc));
+    }
+    Ok(len)
+}
+
+/// Receives what `curl_ws_recv` has of the current frame into `partial`,
+/// returning the frame once all of it arrived, or failing if it announces
+/// more than `max_frame_size` bytes.
+pub(crate) fn recv<H>(
+    handle: &Easy2Handle<H>,
+    partial: &mut Option<Frame>,
+    max_frame_size: usize,
+) -> Result<Option<Frame>, curl::Error> {
+    let api = ws_api().ok_or_else(|| curl::Error::new(CURLE_NOT_BUILT_IN))?;
+    let mut buf = [0; 16 * 1024];
+    let mut len = 0;
+    let mut meta: *const CurlWsFrame = std::ptr::null();
+    let rc = unsafe {
+        (api.recv)(
+            handle.raw(),
+            buf.as_mut_ptr() as *mut _,
+            buf.len(),
+            &mut len,
+            &mut meta,
+        )
+    };
+    if rc != curl_sys::CURLE_OK {
+        return Err(curl::Error::new(rc));
+    }
+    // SAFETY: libcurl points `meta` at a frame it keeps until the next call.
+    let meta = unsafe { &*meta };
+    let bytesleft = u64::try_from(meta.bytesleft).unwrap_or(0);
+    let received = partial.as_ref().map_or(0, |frame| frame.payload.len()) + len;
+    if let Err(err) = check_frame_size(received as u64 + bytesleft, max_frame_size) {
+        *partial = None;
+        return Err(err);
+    }
+    // The length in the header is the host's claim, so only part of it is set aside.
+    let frame = partial.get_or_insert_with(|| Frame {
+        kind: FrameKind::from_flags(meta.flags),
+        payload: Vec::with_capacity(len + (bytesleft as usize).min(MAX_PREALLOCATION)),
+        last: meta.flags & CURLWS_CONT == 0,
+    });
+    frame.payload.extend_from_slice(&buf[..len]);
+    if bytesleft > 0 {
+        return Ok(None);
+    }
+    Ok(partial.take())
+}
+
+/// Fails with `CURLE_RECV_ERROR` if a frame of `size` bytes is larger than
+/// `max_frame_size`.
+pub(crate) fn check_frame_size(size: u64, max_frame_size: usize) -> Result<(), curl::Error> {
+    if size <= max_frame_size as u64 {
+        return Ok(());
+    }
+    let mut err = curl::Error::new(curl_sys::CURLE_RECV_ERROR);
+    err.set_extra(format!(
+        "The frame of {size} bytes is larger than the maximum of {max_frame_size}"
+    ));
+    Err(err)
+}